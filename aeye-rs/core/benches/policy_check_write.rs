@@ -0,0 +1,59 @@
+//! Times `PolicyEngine::check_write` over a patch touching 10k paths.
+//!
+//! Run with `cargo bench -p aeye-core --bench policy_check_write`.
+
+use aeye_core::{AEyeConfig, PolicyEngine};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const PATH_COUNT: usize = 10_000;
+const ITERATIONS: u32 = 10;
+
+fn main() -> anyhow::Result<()> {
+    let repo = tempfile::tempdir()?;
+    std::fs::create_dir(repo.path().join(".git"))?;
+
+    let config = AEyeConfig {
+        deny_globs: [
+            "*.pem",
+            "*.key",
+            "*/secrets/*",
+            "*/node_modules/*",
+            "*.min.js",
+        ]
+        .map(String::from)
+        .to_vec(),
+        write_allowlist: ["*/src/*", "*/tests/*", "*/docs/*", "*.md", "*.toml"]
+            .map(String::from)
+            .to_vec(),
+        ..AEyeConfig::default()
+    };
+    let engine = PolicyEngine::new(config, Some(repo.path().to_path_buf()))?;
+
+    let extensions = ["rs", "md", "toml", "js", "pem"];
+    let dirs = ["src", "tests", "docs", "secrets", "scripts"];
+    let paths: Vec<PathBuf> = (0..PATH_COUNT)
+        .map(|i| {
+            let dir = dirs[i % dirs.len()];
+            let extension = extensions[i % extensions.len()];
+            PathBuf::from(format!("{dir}/module_{}/file_{i}.{extension}", i / 100))
+        })
+        .collect();
+
+    let mut best = Duration::MAX;
+    let mut allowed = 0;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        allowed = paths
+            .iter()
+            .filter(|path| engine.check_write(path).is_allowed())
+            .count();
+        best = best.min(start.elapsed());
+    }
+
+    println!(
+        "check_write: {PATH_COUNT} paths in {best:?} (best of {ITERATIONS}), {allowed} allowed, {:?}/path",
+        best / PATH_COUNT as u32
+    );
+    Ok(())
+}
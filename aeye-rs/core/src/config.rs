@@ -1,21 +1,79 @@
+use crate::config_loader::ConfigProvenance;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Tier 0: A-Eye may only explain code; nothing is planned or written.
+pub const TIER_EXPLAIN_ONLY: u8 = 0;
+/// Tier 1: A-Eye may produce plans and diffs, but never applies them.
+pub const TIER_PLAN_AND_DIFF: u8 = 1;
+/// Tier 2 and above: supervised execution. Actions that modify the repo or
+/// run commands are allowed, but each one requires explicit user approval.
+pub const TIER_SUPERVISED_EXECUTION: u8 = 2;
+
+/// A-Eye settings, as read from `a-eye.yaml`, `.nlpg/config.toml` or the
+/// `[aeye]` table of the global `config.toml`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct AEyeConfig {
+    /// Tier A-Eye runs at: 0 explains only, 1 plans and diffs, 2 and above
+    /// allow supervised execution.
+    #[serde(default = "default_tier")]
+    pub default_tier: u8,
+    /// Overrides for the minimum tier required by a named action (e.g.
+    /// `tools.apply`). Actions not listed here fall back to the built-in
+    /// requirements in [`crate::policy::default_action_tier`].
+    #[serde(default)]
+    pub action_tiers: BTreeMap<String, u8>,
+    /// Paths matching any of these globs are never written.
     #[serde(default)]
     pub deny_globs: Vec<String>,
+    /// When non-empty, only paths matching one of these globs may be written.
+    /// A glob may match either the absolute path or the path relative to the
+    /// repo root, so `src/*` allows writes under the repo's `src`. Entries
+    /// must stay inside the repository.
     #[serde(default)]
     pub write_allowlist: Vec<String>,
+    /// Regexes; shell commands matching any of them are denied.
     #[serde(default)]
     pub shell_deny_patterns: Vec<String>,
+    /// Treat files matched by the repo's `.gitignore` files like the built-in
+    /// protected classes: writing them needs supervised execution and
+    /// explicit approval.
+    #[serde(default)]
+    pub protect_ignored_files: bool,
+    /// Starlark execpolicy rule files evaluated against every sub-command of
+    /// a shell invocation. Relative paths are resolved against the repo root.
+    #[serde(default)]
+    pub execpolicy_rules: Vec<PathBuf>,
+    /// Profile declared in the execpolicy rule files whose rules apply on top
+    /// of the base rules, e.g. `ci` or `readonly`.
+    #[serde(default)]
+    pub execpolicy_profile: Option<String>,
+    /// Which layer set each value, reported alongside policy decisions so
+    /// users can find the rule that fired. Filled in by
+    /// [`crate::config_loader::ConfigLoader`].
+    #[serde(skip)]
+    pub provenance: ConfigProvenance,
+}
+
+fn default_tier() -> u8 {
+    TIER_PLAN_AND_DIFF
 }
 
 impl Default for AEyeConfig {
     fn default() -> Self {
         Self {
+            default_tier: default_tier(),
+            action_tiers: BTreeMap::new(),
             deny_globs: Vec::new(),
             write_allowlist: Vec::new(),
             shell_deny_patterns: Vec::new(),
+            protect_ignored_files: false,
+            execpolicy_rules: Vec::new(),
+            execpolicy_profile: None,
+            provenance: ConfigProvenance::default(),
         }
     }
 }
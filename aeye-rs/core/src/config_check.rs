@@ -0,0 +1,356 @@
+//! Validation for [`AEyeConfig`] and the `config check` command.
+
+use crate::config::AEyeConfig;
+use crate::config_loader::{ConfigLoader, ConfigSource};
+use anyhow::Result;
+use clap::Parser;
+use schemars::schema::RootSchema;
+use std::fmt;
+use std::path::{Component, Path};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConfigError {
+    #[error("unknown key")]
+    UnknownKey,
+    #[error("invalid value: {0}")]
+    InvalidValue(String),
+    #[error("invalid glob `{glob}`: {message}")]
+    InvalidGlob { glob: String, message: String },
+    #[error("invalid regex `{pattern}`: {message}")]
+    InvalidRegex { pattern: String, message: String },
+    #[error("allowlist entry `{entry}` points outside the repository")]
+    AllowlistOutsideRepo { entry: String },
+}
+
+/// A config problem together with where it was defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiagnostic {
+    pub error: ConfigError,
+    /// Dotted key path, e.g. `deny_globs` or `action_tiers.tools.apply`.
+    pub key: String,
+    pub source: ConfigSource,
+    /// 1-based line in `source`, when it is a file and the key could be found.
+    pub line: Option<usize>,
+}
+
+impl ConfigDiagnostic {
+    /// Builds a diagnostic, locating `needle` (or failing that, `key`) in the
+    /// source file when there is one.
+    pub(crate) fn new(
+        error: ConfigError,
+        key: String,
+        source: ConfigSource,
+        needle: Option<&str>,
+    ) -> Self {
+        let line = source
+            .path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|contents| locate_line(&contents, &key, needle));
+        Self {
+            error,
+            key,
+            source,
+            line,
+        }
+    }
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}", self.source)?,
+            None => write!(f, "{}", self.source)?,
+        }
+        write!(f, ": `{}`: {}", self.key, self.error)
+    }
+}
+
+/// Returned by [`ConfigLoader::load`] when any layer fails validation.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid A-Eye config:\n{}", render_diagnostics(.diagnostics))]
+pub struct ConfigValidationError {
+    pub diagnostics: Vec<ConfigDiagnostic>,
+}
+
+fn render_diagnostics(diagnostics: &[ConfigDiagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("  {diagnostic}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Checks the values of a merged config. Unknown keys and type errors are
+/// reported by [`ConfigLoader::check`] while the layers are merged.
+pub fn validate_config(config: &AEyeConfig, repo_root: Option<&Path>) -> Vec<ConfigDiagnostic> {
+    let mut diagnostics = Vec::new();
+    let diagnostic = |key: &str, error: ConfigError, needle: &str| {
+        ConfigDiagnostic::new(
+            error,
+            key.to_string(),
+            config.provenance.source_of(key).clone(),
+            Some(needle),
+        )
+    };
+
+    for (key, globs) in [
+        ("deny_globs", &config.deny_globs),
+        ("write_allowlist", &config.write_allowlist),
+    ] {
+        for glob in globs {
+            if let Err(err) = globset::Glob::new(glob) {
+                let error = ConfigError::InvalidGlob {
+                    glob: glob.clone(),
+                    message: err.kind().to_string(),
+                };
+                diagnostics.push(diagnostic(key, error, glob));
+            }
+        }
+    }
+
+    for pattern in &config.shell_deny_patterns {
+        if let Err(err) = regex::Regex::new(pattern) {
+            let error = ConfigError::InvalidRegex {
+                pattern: pattern.clone(),
+                message: err.to_string(),
+            };
+            diagnostics.push(diagnostic("shell_deny_patterns", error, pattern));
+        }
+    }
+
+    for entry in &config.write_allowlist {
+        if escapes_repo(Path::new(entry), repo_root) {
+            let error = ConfigError::AllowlistOutsideRepo {
+                entry: entry.clone(),
+            };
+            diagnostics.push(diagnostic("write_allowlist", error, entry));
+        }
+    }
+
+    diagnostics
+}
+
+/// Whether an allowlist entry can only match paths outside the repository:
+/// an absolute path under a different root, or a relative path whose `..`
+/// segments climb above the repo root.
+fn escapes_repo(entry: &Path, repo_root: Option<&Path>) -> bool {
+    if entry.is_absolute() {
+        return repo_root.is_some_and(|repo_root| !entry.starts_with(repo_root));
+    }
+
+    let mut depth = 0usize;
+    for component in entry.components() {
+        match component {
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    false
+}
+
+/// Finds the 1-based line that defines `key`, then the first line at or
+/// after it containing `needle`. Works for both TOML and YAML sources.
+fn locate_line(contents: &str, key: &str, needle: Option<&str>) -> Option<usize> {
+    let leaf = key.rsplit('.').next().unwrap_or(key);
+    let lines: Vec<&str> = contents.lines().collect();
+    let key_line = lines.iter().position(|line| {
+        let line = line.trim_start().trim_start_matches("- ");
+        let line = line.trim_start_matches(['"', '\'']);
+        line.strip_prefix(leaf).is_some_and(|rest| {
+            rest.trim_start_matches(['"', '\''])
+                .trim_start()
+                .starts_with(['=', ':'])
+        })
+    });
+
+    let needle_line = needle.and_then(|needle| {
+        let start = key_line.unwrap_or(0);
+        lines[start..]
+            .iter()
+            .position(|line| line.contains(needle))
+            .map(|offset| start + offset)
+    });
+
+    needle_line.or(key_line).map(|index| index + 1)
+}
+
+/// JSON Schema for A-Eye config files, for editor autocompletion.
+pub fn config_json_schema() -> RootSchema {
+    schemars::schema_for!(AEyeConfig)
+}
+
+/// Validate every A-Eye config layer and report problems with their location.
+#[derive(Debug, Parser, Clone)]
+pub struct ConfigCheckCommand {
+    /// Override a configuration value (repeatable).
+    #[arg(short = 'c', long = "config", value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+
+    /// Print the JSON Schema for A-Eye config files instead of checking them.
+    #[arg(long)]
+    pub schema: bool,
+}
+
+impl ConfigCheckCommand {
+    /// Checks the config that applies in `cwd`.
+    pub fn run(&self, cwd: &Path) -> Result<()> {
+        if self.schema {
+            println!("{}", serde_json::to_string_pretty(&config_json_schema())?);
+            return Ok(());
+        }
+
+        let report = ConfigLoader::new(cwd)
+            .cli_overrides(self.overrides.iter().cloned())
+            .check()?;
+        for diagnostic in &report.diagnostics {
+            println!("{diagnostic}");
+        }
+
+        match report.diagnostics.len() {
+            0 => {
+                println!("A-Eye config OK");
+                Ok(())
+            }
+            1 => anyhow::bail!("found 1 problem in A-Eye config"),
+            count => anyhow::bail!("found {count} problems in A-Eye config"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn setup_repo(config: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_root = dir.path().to_path_buf();
+        fs::create_dir(repo_root.join(".git")).unwrap();
+        fs::write(repo_root.join("a-eye.yaml"), config).unwrap();
+        (dir, repo_root)
+    }
+
+    #[test]
+    fn reports_located_diagnostics() {
+        let (_dir, repo_root) = setup_repo(
+            "default_tier: 1\n\
+             deny_globs:\n  - \"*.pem\"\n  - \"src/[oops\"\n\
+             shell_deny_patterns:\n  - 'rm\\s+(-rf'\n\
+             write_allowlist:\n  - \"../outside/*\"\n\
+             deny_glob:\n  - \"*.key\"\n",
+        );
+        let config_path = repo_root.join("a-eye.yaml");
+        let source = ConfigSource::Repo(config_path.clone());
+
+        let report = ConfigLoader::new(&repo_root)
+            .home(None)
+            .env([])
+            .check()
+            .unwrap();
+
+        assert_eq!(
+            report
+                .diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.key.as_str(), diagnostic.line))
+                .collect::<Vec<_>>(),
+            vec![
+                ("deny_glob", Some(9)),
+                ("deny_globs", Some(4)),
+                ("shell_deny_patterns", Some(6)),
+                ("write_allowlist", Some(8)),
+            ]
+        );
+        assert_eq!(report.diagnostics[0].error, ConfigError::UnknownKey);
+        assert_eq!(
+            report.diagnostics[3],
+            ConfigDiagnostic {
+                error: ConfigError::AllowlistOutsideRepo {
+                    entry: "../outside/*".to_string()
+                },
+                key: "write_allowlist".to_string(),
+                source,
+                line: Some(8),
+            }
+        );
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            format!("{}:9: `deny_glob`: unknown key", config_path.display())
+        );
+    }
+
+    #[test]
+    fn reports_unknown_action_tiers() {
+        let (_dir, repo_root) = setup_repo("action_tiers:\n  tools.verify: 1\n  tools.aply: 3\n");
+
+        let report = ConfigLoader::new(&repo_root)
+            .home(None)
+            .env([])
+            .check()
+            .unwrap();
+
+        assert_eq!(
+            report.diagnostics,
+            vec![ConfigDiagnostic {
+                error: ConfigError::UnknownKey,
+                key: "action_tiers.tools.aply".to_string(),
+                source: ConfigSource::Repo(repo_root.join("a-eye.yaml")),
+                line: Some(3),
+            }]
+        );
+        let config = report.config.unwrap();
+        assert_eq!(
+            config.action_tiers.into_iter().collect::<Vec<_>>(),
+            vec![("tools.verify".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn load_fails_on_invalid_config() {
+        let (_dir, repo_root) = setup_repo("default_tier: \"high\"\n");
+
+        let err = ConfigLoader::new(&repo_root)
+            .home(None)
+            .env([])
+            .cli_overrides(["typo_key=1".to_string()])
+            .load()
+            .unwrap_err();
+        let validation = err.downcast_ref::<ConfigValidationError>().unwrap();
+
+        assert_eq!(
+            validation
+                .diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "-c override: `typo_key`: unknown key".to_string(),
+                format!(
+                    "{}:1: `default_tier`: invalid value: invalid type: string \"high\", expected u8",
+                    repo_root.join("a-eye.yaml").display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn allowlist_escape_detection() {
+        let repo_root = Path::new("/repo");
+        assert!(!escapes_repo(Path::new("src/*"), Some(repo_root)));
+        assert!(!escapes_repo(Path::new("src/../docs/*"), Some(repo_root)));
+        assert!(escapes_repo(Path::new("src/../../etc/*"), Some(repo_root)));
+        assert!(escapes_repo(Path::new("/etc/*"), Some(repo_root)));
+        assert!(!escapes_repo(Path::new("/repo/src/*"), Some(repo_root)));
+    }
+
+    #[test]
+    fn schema_matches_fixture() {
+        let schema = serde_json::to_string_pretty(&config_json_schema()).unwrap();
+        assert_eq!(schema.trim(), include_str!("../config.schema.json").trim());
+    }
+}
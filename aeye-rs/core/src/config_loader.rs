@@ -0,0 +1,607 @@
+//! Layered loading for [`AEyeConfig`].
+//!
+//! Layers are merged from lowest to highest precedence:
+//!
+//! 1. Built-in defaults.
+//! 2. The `[aeye]` table of `config.toml` in the A-Eye home directory (see
+//!    [`aeye_utils_home_dir::find_codex_home`]).
+//! 3. Repo config: `a-eye.yaml` at the repo root, then `.nlpg/config.toml`.
+//! 4. `AEYE_*` environment variables, e.g. `AEYE_DEFAULT_TIER=2`.
+//! 5. CLI `-c key=value` overrides.
+//!
+//! Every effective value is tagged with the layer it came from so `status`
+//! can explain where a setting was set.
+
+use crate::config::AEyeConfig;
+use crate::config_check::{
+    ConfigDiagnostic, ConfigError, ConfigValidationError, config_json_schema, validate_config,
+};
+use crate::policy::KNOWN_ACTIONS;
+use aeye_utils_home_dir::find_codex_home;
+use aeye_utils_json_to_toml::json_to_toml;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::Value as TomlValue;
+
+/// Repo-level config files, in the order they are applied.
+pub const REPO_CONFIG_FILES: &[&str] = &["a-eye.yaml", ".nlpg/config.toml"];
+
+/// Name of the table in the global `config.toml` that holds A-Eye settings.
+pub const GLOBAL_CONFIG_TABLE: &str = "aeye";
+
+const ENV_PREFIX: &str = "AEYE_";
+
+/// Where an effective config value came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "origin")]
+pub enum ConfigSource {
+    Default,
+    Global(PathBuf),
+    Repo(PathBuf),
+    Env(String),
+    Cli,
+}
+
+impl ConfigSource {
+    /// The config file this value was read from, if it came from a file.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Global(path) | Self::Repo(path) => Some(path),
+            Self::Default | Self::Env(_) | Self::Cli => None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Global(path) | Self::Repo(path) => write!(f, "{}", path.display()),
+            Self::Env(var) => write!(f, "${var}"),
+            Self::Cli => write!(f, "-c override"),
+        }
+    }
+}
+
+/// Maps dotted key paths (e.g. `deny_globs`, `action_tiers.tools.apply`) to
+/// the layer that last set them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConfigProvenance(BTreeMap<String, ConfigSource>);
+
+impl ConfigProvenance {
+    /// Returns the source for `key`, falling back to the closest parent key
+    /// and finally to [`ConfigSource::Default`].
+    pub fn source_of(&self, key: &str) -> &ConfigSource {
+        let mut key = key;
+        loop {
+            if let Some(source) = self.0.get(key) {
+                return source;
+            }
+            match key.rsplit_once('.') {
+                Some((parent, _)) => key = parent,
+                None => return &ConfigSource::Default,
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &ConfigSource)> {
+        self.0.iter()
+    }
+
+    fn record(&mut self, key: String, source: ConfigSource) {
+        // A whole-value replacement hides anything recorded beneath it.
+        let nested_prefix = format!("{key}.");
+        self.0
+            .retain(|existing, _| !existing.starts_with(&nested_prefix));
+        self.0.insert(key, source);
+    }
+}
+
+impl FromIterator<(String, ConfigSource)> for ConfigProvenance {
+    fn from_iter<I: IntoIterator<Item = (String, ConfigSource)>>(iter: I) -> Self {
+        let mut provenance = Self::default();
+        for (key, source) in iter {
+            provenance.record(key, source);
+        }
+        provenance
+    }
+}
+
+/// Builds an [`AEyeConfig`] from every configuration layer.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    cwd: PathBuf,
+    home: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    cli_overrides: Vec<String>,
+}
+
+impl ConfigLoader {
+    /// Creates a loader rooted at `cwd` that reads the real A-Eye home
+    /// directory and process environment.
+    pub fn new(cwd: impl Into<PathBuf>) -> Self {
+        Self {
+            cwd: cwd.into(),
+            home: find_codex_home().ok(),
+            env: std::env::vars().collect(),
+            cli_overrides: Vec::new(),
+        }
+    }
+
+    pub fn home(mut self, home: Option<PathBuf>) -> Self {
+        self.home = home;
+        self
+    }
+
+    pub fn env(mut self, env: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = env.into_iter().collect();
+        self
+    }
+
+    /// Raw `key=value` strings as passed to `-c`. Values are parsed as TOML
+    /// and fall back to plain strings.
+    pub fn cli_overrides(mut self, overrides: impl IntoIterator<Item = String>) -> Self {
+        self.cli_overrides = overrides.into_iter().collect();
+        self
+    }
+
+    /// The repository containing `cwd`, whose config files are loaded.
+    pub fn repo_root(&self) -> Option<PathBuf> {
+        find_repo_root(&self.cwd)
+    }
+
+    /// Loads the effective config, failing with [`ConfigValidationError`] if
+    /// any layer has unknown keys or invalid values.
+    pub fn load(&self) -> Result<AEyeConfig> {
+        let report = self.check()?;
+        match report.config {
+            Some(config) if report.diagnostics.is_empty() => Ok(config),
+            _ => Err(ConfigValidationError {
+                diagnostics: report.diagnostics,
+            }
+            .into()),
+        }
+    }
+
+    /// Merges every layer and collects validation diagnostics instead of
+    /// stopping at the first problem. Only I/O and syntax errors are returned
+    /// as `Err`.
+    pub fn check(&self) -> Result<ConfigReport> {
+        let repo_root = self.repo_root();
+        let defaults = TomlValue::try_from(AEyeConfig::default())
+            .context("failed to serialize default A-Eye config")?;
+        let known_keys = known_keys();
+        let mut merged = defaults.clone();
+        let mut provenance = ConfigProvenance::default();
+        let mut diagnostics = Vec::new();
+
+        let mut file_layers = Vec::new();
+        if let Some(home) = &self.home {
+            let path = home.join("config.toml");
+            if let Some(global) = read_global_config(&path)? {
+                file_layers.push((global, ConfigSource::Global(path)));
+            }
+        }
+        if let Some(repo_root) = &repo_root {
+            for file in REPO_CONFIG_FILES {
+                let path = repo_root.join(file);
+                if let Some(layer) = read_config_file(&path)? {
+                    file_layers.push((layer, ConfigSource::Repo(path)));
+                }
+            }
+        }
+        for (mut layer, source) in file_layers {
+            diagnostics.extend(remove_unknown_keys(&mut layer, &known_keys, &source));
+            merge_layer(&mut merged, layer, &source, &mut provenance);
+        }
+
+        for (var, raw) in &self.env {
+            let Some(key) = env_var_key(var, &known_keys) else {
+                continue;
+            };
+            let value = parse_env_value(raw, defaults.get(&key));
+            let mut layer = toml::Table::new();
+            layer.insert(key, value);
+            merge_layer(
+                &mut merged,
+                TomlValue::Table(layer),
+                &ConfigSource::Env(var.clone()),
+                &mut provenance,
+            );
+        }
+
+        for raw in &self.cli_overrides {
+            let mut layer = parse_cli_override(raw)?;
+            diagnostics.extend(remove_unknown_keys(
+                &mut layer,
+                &known_keys,
+                &ConfigSource::Cli,
+            ));
+            merge_layer(&mut merged, layer, &ConfigSource::Cli, &mut provenance);
+        }
+
+        let config = match serde_path_to_error::deserialize::<_, AEyeConfig>(merged) {
+            Ok(mut config) => {
+                config.provenance = provenance;
+                diagnostics.extend(validate_config(&config, repo_root.as_deref()));
+                Some(config)
+            }
+            Err(err) => {
+                let key = err.path().to_string();
+                let source = provenance.source_of(&key).clone();
+                let error = ConfigError::InvalidValue(err.into_inner().message().to_string());
+                diagnostics.push(ConfigDiagnostic::new(error, key, source, None));
+                None
+            }
+        };
+
+        Ok(ConfigReport {
+            config,
+            diagnostics,
+        })
+    }
+}
+
+/// The outcome of [`ConfigLoader::check`].
+#[derive(Debug, Clone)]
+pub struct ConfigReport {
+    /// The merged config, or `None` if the layers did not deserialize.
+    pub config: Option<AEyeConfig>,
+    pub diagnostics: Vec<ConfigDiagnostic>,
+}
+
+/// Walks up from `start` looking for the directory that contains `.git`.
+pub fn find_repo_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+fn read_global_config(path: &Path) -> Result<Option<TomlValue>> {
+    let Some(TomlValue::Table(mut table)) = read_config_file(path)? else {
+        return Ok(None);
+    };
+    Ok(table.remove(GLOBAL_CONFIG_TABLE))
+}
+
+fn read_config_file(path: &Path) -> Result<Option<TomlValue>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", path.display()));
+        }
+    };
+
+    let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    );
+    let value = if is_yaml {
+        let json: serde_json::Value = serde_yaml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        // An empty YAML document parses as null.
+        if json.is_null() {
+            return Ok(None);
+        }
+        json_to_toml(json)
+    } else {
+        TomlValue::Table(
+            toml::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", path.display()))?,
+        )
+    };
+    Ok(Some(value))
+}
+
+/// The top-level keys `AEyeConfig` defines. Taken from its schema because
+/// the serialized defaults omit unset optional keys such as
+/// `execpolicy_profile`.
+fn known_keys() -> BTreeSet<String> {
+    config_json_schema()
+        .schema
+        .object
+        .map(|object| object.properties.into_keys().collect())
+        .unwrap_or_default()
+}
+
+/// Maps `AEYE_DEFAULT_TIER` to `default_tier`. Variables that do not name a
+/// top-level config key (such as `AEYE_HOME`) are ignored.
+fn env_var_key(var: &str, known_keys: &BTreeSet<String>) -> Option<String> {
+    let key = var.strip_prefix(ENV_PREFIX)?.to_ascii_lowercase();
+    known_keys.contains(&key).then_some(key)
+}
+
+/// Env values are parsed as TOML where possible. For list-valued keys a bare
+/// string is split on commas, so `AEYE_DENY_GLOBS=*.pem,.env` works.
+fn parse_env_value(raw: &str, default: Option<&TomlValue>) -> TomlValue {
+    if let Some(value) = parse_toml_value(raw) {
+        return value;
+    }
+    match default {
+        Some(TomlValue::Array(_)) => TomlValue::Array(
+            raw.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| TomlValue::String(item.to_string()))
+                .collect(),
+        ),
+        _ => TomlValue::String(raw.to_string()),
+    }
+}
+
+fn parse_cli_override(raw: &str) -> Result<TomlValue> {
+    let (key, value) = raw
+        .split_once('=')
+        .with_context(|| format!("invalid override `{raw}`: expected key=value"))?;
+    let key = key.trim();
+    let value = value.trim();
+
+    // Let the TOML parser handle dotted and quoted keys, e.g.
+    // `action_tiers."tools.apply"=3`.
+    let document = match parse_toml_value(value) {
+        Some(_) => format!("{key} = {value}"),
+        None => format!("{key} = {}", TomlValue::String(value.to_string())),
+    };
+    let table: toml::Table = toml::from_str(&document)
+        .with_context(|| format!("invalid override `{raw}`: bad key `{key}`"))?;
+    Ok(TomlValue::Table(table))
+}
+
+fn parse_toml_value(raw: &str) -> Option<TomlValue> {
+    let mut table: toml::Table = toml::from_str(&format!("value = {raw}")).ok()?;
+    table.remove("value")
+}
+
+/// Drops keys that `AEyeConfig` does not define, reporting each one so typos
+/// such as `deny_glob` or an `action_tiers` entry for `tools.aply` are not
+/// silently ignored.
+fn remove_unknown_keys(
+    layer: &mut TomlValue,
+    known_keys: &BTreeSet<String>,
+    source: &ConfigSource,
+) -> Vec<ConfigDiagnostic> {
+    let TomlValue::Table(table) = layer else {
+        return Vec::new();
+    };
+    let mut diagnostics = Vec::new();
+    table.retain(|key, _| {
+        let known = known_keys.contains(key);
+        if !known {
+            diagnostics.push(ConfigDiagnostic::new(
+                ConfigError::UnknownKey,
+                key.to_string(),
+                source.clone(),
+                None,
+            ));
+        }
+        known
+    });
+    if let Some(TomlValue::Table(action_tiers)) = table.get_mut("action_tiers") {
+        action_tiers.retain(|action, _| {
+            let known = KNOWN_ACTIONS.contains(&action);
+            if !known {
+                diagnostics.push(ConfigDiagnostic::new(
+                    ConfigError::UnknownKey,
+                    format!("action_tiers.{action}"),
+                    source.clone(),
+                    Some(action),
+                ));
+            }
+            known
+        });
+    }
+    diagnostics
+}
+
+fn merge_layer(
+    base: &mut TomlValue,
+    layer: TomlValue,
+    source: &ConfigSource,
+    provenance: &mut ConfigProvenance,
+) {
+    merge_value(base, layer, "", source, provenance);
+}
+
+/// Tables merge key by key; any other value replaces the base wholesale.
+fn merge_value(
+    base: &mut TomlValue,
+    layer: TomlValue,
+    key: &str,
+    source: &ConfigSource,
+    provenance: &mut ConfigProvenance,
+) {
+    match (base, layer) {
+        (TomlValue::Table(base), TomlValue::Table(layer)) => {
+            for (child, value) in layer {
+                let child_key = if key.is_empty() {
+                    child.clone()
+                } else {
+                    format!("{key}.{child}")
+                };
+                let entry = base
+                    .entry(child)
+                    .or_insert_with(|| TomlValue::Table(toml::Table::new()));
+                merge_value(entry, value, &child_key, source, provenance);
+            }
+        }
+        (base, layer) => {
+            *base = layer;
+            provenance.record(key.to_string(), source.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TIER_SUPERVISED_EXECUTION;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    fn setup_layers() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let repo_root = dir.path().join("repo");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(repo_root.join(".git")).unwrap();
+        fs::create_dir_all(repo_root.join(".nlpg")).unwrap();
+        fs::create_dir_all(repo_root.join("src")).unwrap();
+        fs::write(
+            home.join("config.toml"),
+            r#"
+model = "unrelated-setting"
+
+[aeye]
+default_tier = 0
+deny_globs = ["*.pem"]
+"#,
+        )
+        .unwrap();
+        fs::write(
+            repo_root.join("a-eye.yaml"),
+            "write_allowlist:\n  - \"src/*\"\naction_tiers:\n  tools.verify: 1\n",
+        )
+        .unwrap();
+        fs::write(
+            repo_root.join(".nlpg/config.toml"),
+            "deny_globs = [\".env\"]\n",
+        )
+        .unwrap();
+        (dir, home, repo_root)
+    }
+
+    #[test]
+    fn merges_layers_in_precedence_order() {
+        let (_dir, home, repo_root) = setup_layers();
+
+        let loader = ConfigLoader::new(repo_root.join("src"))
+            .home(Some(home))
+            .env([
+                ("AEYE_DEFAULT_TIER".to_string(), "2".to_string()),
+                ("AEYE_HOME".to_string(), "/ignored".to_string()),
+            ])
+            .cli_overrides([r#"action_tiers."tools.apply"=3"#.to_string()]);
+        let config = loader.load().unwrap();
+
+        assert_eq!(config.default_tier, TIER_SUPERVISED_EXECUTION);
+        assert_eq!(config.deny_globs, vec![".env".to_string()]);
+        assert_eq!(config.write_allowlist, vec!["src/*".to_string()]);
+        assert_eq!(config.action_tiers.get("tools.verify"), Some(&1));
+        assert_eq!(config.action_tiers.get("tools.apply"), Some(&3));
+        assert_eq!(loader.repo_root(), Some(repo_root.clone()));
+
+        let provenance = &config.provenance;
+        assert_eq!(
+            provenance.source_of("default_tier"),
+            &ConfigSource::Env("AEYE_DEFAULT_TIER".to_string())
+        );
+        assert_eq!(
+            provenance.source_of("deny_globs"),
+            &ConfigSource::Repo(repo_root.join(".nlpg/config.toml"))
+        );
+        assert_eq!(
+            provenance.source_of("write_allowlist"),
+            &ConfigSource::Repo(repo_root.join("a-eye.yaml"))
+        );
+        assert_eq!(
+            provenance.source_of("action_tiers.tools.apply"),
+            &ConfigSource::Cli
+        );
+        assert_eq!(
+            provenance.source_of("shell_deny_patterns"),
+            &ConfigSource::Default
+        );
+    }
+
+    #[test]
+    fn env_lists_split_on_commas() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let loader = ConfigLoader::new(dir.path())
+            .home(None)
+            .env([("AEYE_DENY_GLOBS".to_string(), "*.pem, .env".to_string())]);
+        let config = loader.load().unwrap();
+
+        assert_eq!(
+            config.deny_globs,
+            vec!["*.pem".to_string(), ".env".to_string()]
+        );
+        assert_eq!(loader.repo_root(), None);
+    }
+
+    #[test]
+    fn optional_keys_are_read_from_every_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let repo_root = dir.path().join("repo");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(repo_root.join(".git")).unwrap();
+        let profile = |loader: &ConfigLoader| {
+            let config = loader.load().unwrap();
+            let source = config.provenance.source_of("execpolicy_profile").clone();
+            (config.execpolicy_profile, source)
+        };
+
+        fs::write(
+            home.join("config.toml"),
+            "[aeye]\nexecpolicy_profile = \"global\"\n",
+        )
+        .unwrap();
+        let loader = ConfigLoader::new(&repo_root)
+            .home(Some(home.clone()))
+            .env([]);
+        assert_eq!(
+            profile(&loader),
+            (
+                Some("global".to_string()),
+                ConfigSource::Global(home.join("config.toml"))
+            )
+        );
+
+        fs::write(repo_root.join("a-eye.yaml"), "execpolicy_profile: repo\n").unwrap();
+        assert_eq!(
+            profile(&loader),
+            (
+                Some("repo".to_string()),
+                ConfigSource::Repo(repo_root.join("a-eye.yaml"))
+            )
+        );
+
+        let loader = loader.env([("AEYE_EXECPOLICY_PROFILE".to_string(), "env".to_string())]);
+        assert_eq!(
+            profile(&loader),
+            (
+                Some("env".to_string()),
+                ConfigSource::Env("AEYE_EXECPOLICY_PROFILE".to_string())
+            )
+        );
+
+        let loader = loader.cli_overrides(["execpolicy_profile=cli".to_string()]);
+        assert_eq!(
+            profile(&loader),
+            (Some("cli".to_string()), ConfigSource::Cli)
+        );
+    }
+
+    #[test]
+    fn cli_override_requires_key_value() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = ConfigLoader::new(dir.path())
+            .home(None)
+            .env([])
+            .cli_overrides(["default_tier".to_string()])
+            .load()
+            .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "invalid override `default_tier`: expected key=value"
+        );
+    }
+}
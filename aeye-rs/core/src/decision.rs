@@ -0,0 +1,139 @@
+use crate::config_loader::ConfigSource;
+use crate::protected::ProtectedClass;
+use aeye_execpolicy::Decision;
+use aeye_execpolicy::RuleSource;
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+/// The policy rule that produced a [`PolicyDecision`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum PolicyRule {
+    /// No configured rule applied, so the engine fell back to its default.
+    Default,
+    /// The path resolves to a location outside the repository root.
+    #[serde(rename_all = "camelCase")]
+    OutsideRepo { repo_root: PathBuf },
+    /// The path could not be resolved, e.g. because of a symlink loop.
+    UnresolvablePath,
+    /// The path matched an entry in `deny_globs`.
+    DenyGlob { glob: String },
+    /// The path matched an entry in `write_allowlist`.
+    WriteAllowlist { glob: String },
+    /// `write_allowlist` is non-empty and the path matched none of its entries.
+    WriteAllowlistMiss,
+    /// The path belongs to a built-in protected class such as secrets or lockfiles.
+    ProtectedFile {
+        class: ProtectedClass,
+        pattern: String,
+    },
+    /// `protect_ignored_files` is on and a `.gitignore` rule matched the path.
+    IgnoredFile {
+        pattern: String,
+        gitignore: Option<PathBuf>,
+    },
+    /// The command matched an entry in `shell_deny_patterns`.
+    ShellDenyPattern { pattern: String },
+    /// A sub-command matched a `prefix_rule` in one of the `execpolicy_rules` files.
+    #[serde(rename_all = "camelCase")]
+    ExecPolicyPrefix {
+        matched_prefix: Vec<String>,
+        decision: Decision,
+        /// The rules file and line that defined the matching rule.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        rule_source: Option<RuleSource>,
+    },
+    /// The command could not be split into simple commands for analysis.
+    UnparsableCommand,
+    /// The action's tier requirement, from `action_tiers` or the built-in defaults.
+    #[serde(rename_all = "camelCase")]
+    ActionTier {
+        action: String,
+        required_tier: u8,
+        current_tier: u8,
+    },
+}
+
+/// Why the engine reached a decision: the rule that fired, the config layer
+/// that defined it (if any), and a message suitable for showing to users.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyReason {
+    pub rule: PolicyRule,
+    /// Config layer the rule was loaded from. `None` for built-in behaviour.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<ConfigSource>,
+    pub justification: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PolicyDecision {
+    /// The operation may proceed without further approval.
+    Allow(PolicyReason),
+    /// The operation may proceed once the user explicitly approves it.
+    NeedsApproval(PolicyReason),
+    /// The operation is refused.
+    Deny(PolicyReason),
+}
+
+impl PolicyDecision {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allow(_))
+    }
+
+    pub fn needs_approval(&self) -> bool {
+        matches!(self, Self::NeedsApproval(_))
+    }
+
+    pub fn is_denied(&self) -> bool {
+        matches!(self, Self::Deny(_))
+    }
+
+    /// Returns whichever of the two decisions is more restrictive, preferring
+    /// `self` on ties.
+    pub fn stricter(self, other: Self) -> Self {
+        if other.severity() > self.severity() {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn severity(&self) -> u8 {
+        match self {
+            Self::Allow(_) => 0,
+            Self::NeedsApproval(_) => 1,
+            Self::Deny(_) => 2,
+        }
+    }
+
+    pub fn reason(&self) -> &PolicyReason {
+        match self {
+            Self::Allow(reason) | Self::NeedsApproval(reason) | Self::Deny(reason) => reason,
+        }
+    }
+}
+
+impl fmt::Display for PolicyDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self {
+            Self::Allow(_) => "allowed",
+            Self::NeedsApproval(_) => "needs approval",
+            Self::Deny(_) => "denied",
+        };
+        let reason = self.reason();
+        write!(f, "{verdict}: {}", reason.justification)?;
+        if let Some(source) = &reason.source {
+            write!(f, " (from {source})")?;
+        } else if let PolicyRule::ExecPolicyPrefix {
+            rule_source: Some(rule_source),
+            ..
+        } = &reason.rule
+        {
+            write!(f, " (from {rule_source})")?;
+        }
+        Ok(())
+    }
+}
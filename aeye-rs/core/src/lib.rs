@@ -1,5 +1,18 @@
 pub mod config;
+pub mod config_check;
+pub mod config_loader;
+pub mod decision;
+pub mod patch_policy;
 pub mod policy;
+pub mod protected;
 
 pub use config::AEyeConfig;
+pub use config_check::ConfigDiagnostic;
+pub use config_check::ConfigValidationError;
+pub use config_loader::ConfigLoader;
+pub use config_loader::ConfigSource;
+pub use decision::PolicyDecision;
+pub use decision::PolicyReason;
+pub use decision::PolicyRule;
+pub use patch_policy::PatchPolicyReport;
 pub use policy::PolicyEngine;
@@ -0,0 +1,328 @@
+//! Whole-patch policy checks, so a patch can be refused or approved as a
+//! unit before any file is touched.
+
+use crate::decision::PolicyDecision;
+use crate::policy::PolicyEngine;
+use aeye_apply_patch::{ApplyPatchAction, ApplyPatchFileChange, Hunk};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PatchOperation {
+    Add,
+    Update,
+    Delete,
+    /// An update that also renames the file to `move_path`.
+    Move,
+    /// A change to the executable bits only.
+    SetExecutable,
+    /// A new symlink pointing at `symlink_target`.
+    AddSymlink,
+}
+
+/// The policy verdict for one file touched by a patch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchFileVerdict {
+    pub path: PathBuf,
+    /// Destination of a [`PatchOperation::Move`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub move_path: Option<PathBuf>,
+    /// Where a [`PatchOperation::AddSymlink`] points, resolved against the
+    /// directory containing the link.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<PathBuf>,
+    pub operation: PatchOperation,
+    /// For a move, the stricter of the verdicts for the source (which is
+    /// removed) and the destination (which is written). For a symlink, the
+    /// stricter of the verdicts for the link and its target, since later
+    /// writes through the link land in the target.
+    pub decision: PolicyDecision,
+}
+
+/// Per-file verdicts for a patch, sorted by path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatchPolicyReport {
+    pub files: Vec<PatchFileVerdict>,
+}
+
+impl PatchPolicyReport {
+    /// True when every file may be written without approval.
+    pub fn is_allowed(&self) -> bool {
+        self.files.iter().all(|file| file.decision.is_allowed())
+    }
+
+    /// True when nothing is denied but at least one file needs approval.
+    pub fn needs_approval(&self) -> bool {
+        !self.is_denied() && self.files.iter().any(|file| file.decision.needs_approval())
+    }
+
+    /// True when any file is denied, in which case the patch must not be
+    /// applied at all.
+    pub fn is_denied(&self) -> bool {
+        self.files.iter().any(|file| file.decision.is_denied())
+    }
+
+    pub fn denied(&self) -> impl Iterator<Item = &PatchFileVerdict> {
+        self.files.iter().filter(|file| file.decision.is_denied())
+    }
+
+    pub fn needing_approval(&self) -> impl Iterator<Item = &PatchFileVerdict> {
+        self.files
+            .iter()
+            .filter(|file| file.decision.needs_approval())
+    }
+}
+
+impl PolicyEngine {
+    /// Checks every path an [`ApplyPatchAction`] would touch, including move
+    /// destinations and deleted files.
+    pub fn check_patch(&self, action: &ApplyPatchAction) -> PatchPolicyReport {
+        self.check_patch_files(action.changes().iter().map(|(path, change)| {
+            let (operation, other_path) = match change {
+                ApplyPatchFileChange::Add { .. } | ApplyPatchFileChange::AddBinary { .. } => {
+                    (PatchOperation::Add, None)
+                }
+                ApplyPatchFileChange::Delete { .. } | ApplyPatchFileChange::DeleteBinary { .. } => {
+                    (PatchOperation::Delete, None)
+                }
+                ApplyPatchFileChange::Update {
+                    move_path: None, ..
+                } => (PatchOperation::Update, None),
+                ApplyPatchFileChange::Update {
+                    move_path: Some(move_path),
+                    ..
+                } => (PatchOperation::Move, Some(move_path.clone())),
+                ApplyPatchFileChange::SetExecutable { .. } => (PatchOperation::SetExecutable, None),
+                ApplyPatchFileChange::AddSymlink { target } => (
+                    PatchOperation::AddSymlink,
+                    Some(symlink_target(path, target)),
+                ),
+            };
+            (path.clone(), other_path, operation)
+        }))
+    }
+
+    /// Like [`Self::check_patch`], for hunks from
+    /// [`aeye_apply_patch::parse_patch`] that have not been verified against
+    /// the filesystem yet. Relative paths are resolved against `cwd`.
+    pub fn check_hunks(&self, hunks: &[Hunk], cwd: &Path) -> PatchPolicyReport {
+        self.check_patch_files(hunks.iter().map(|hunk| {
+            let path = hunk.resolve_path(cwd);
+            match hunk {
+                Hunk::AddFile { .. } | Hunk::AddBinaryFile { .. } => {
+                    (path, None, PatchOperation::Add)
+                }
+                Hunk::DeleteFile { .. } => (path, None, PatchOperation::Delete),
+                Hunk::SetExecutable { .. } => (path, None, PatchOperation::SetExecutable),
+                Hunk::AddSymlink { target, .. } => {
+                    let target = symlink_target(&path, target);
+                    (path, Some(target), PatchOperation::AddSymlink)
+                }
+                Hunk::UpdateFile {
+                    move_path: None, ..
+                } => (path, None, PatchOperation::Update),
+                Hunk::UpdateFile {
+                    move_path: Some(move_path),
+                    ..
+                } => (path, Some(cwd.join(move_path)), PatchOperation::Move),
+            }
+        }))
+    }
+
+    /// Checks each file, together with the second path a move or symlink
+    /// also writes to.
+    fn check_patch_files(
+        &self,
+        files: impl Iterator<Item = (PathBuf, Option<PathBuf>, PatchOperation)>,
+    ) -> PatchPolicyReport {
+        let mut files: Vec<PatchFileVerdict> = files
+            .map(|(path, other_path, operation)| {
+                let mut decision = self.check_write(&path);
+                if let Some(other_path) = &other_path {
+                    decision = decision.stricter(self.check_write(other_path));
+                }
+                let (move_path, symlink_target) = match operation {
+                    PatchOperation::AddSymlink => (None, other_path),
+                    _ => (other_path, None),
+                };
+                PatchFileVerdict {
+                    path,
+                    move_path,
+                    symlink_target,
+                    operation,
+                    decision,
+                }
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        PatchPolicyReport { files }
+    }
+}
+
+/// `target` as the OS resolves it for a link at `link`.
+fn symlink_target(link: &Path, target: &Path) -> PathBuf {
+    match link.parent() {
+        Some(dir) => dir.join(target),
+        None => target.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AEyeConfig;
+    use crate::decision::PolicyRule;
+    use aeye_apply_patch::{MaybeApplyPatchVerified, maybe_parse_apply_patch_verified};
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    const PATCH: &str = r#"*** Begin Patch
+*** Add File: src/new.rs
++pub fn new() {}
+*** Delete File: secrets/token.txt
+*** Update File: src/lib.rs
+*** Move to: secrets/lib.rs
+@@
+-pub fn old() {}
++pub fn moved() {}
+*** Update File: README.md
+@@
+-old
++new
+*** End Patch"#;
+
+    fn setup_repo() -> (tempfile::TempDir, PathBuf, PolicyEngine) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_root = dir.path().canonicalize().unwrap();
+        fs::create_dir(repo_root.join(".git")).unwrap();
+        fs::create_dir_all(repo_root.join("src")).unwrap();
+        fs::create_dir_all(repo_root.join("secrets")).unwrap();
+        fs::write(repo_root.join("src/lib.rs"), "pub fn old() {}\n").unwrap();
+        fs::write(repo_root.join("secrets/token.txt"), "hunter2\n").unwrap();
+        fs::write(repo_root.join("README.md"), "old\n").unwrap();
+        let config = AEyeConfig {
+            deny_globs: vec!["*/secrets/*".to_string()],
+            ..AEyeConfig::default()
+        };
+        let engine = PolicyEngine::new(config, Some(repo_root.clone())).unwrap();
+        (dir, repo_root, engine)
+    }
+
+    fn summary(report: &PatchPolicyReport) -> Vec<(String, PatchOperation, bool)> {
+        report
+            .files
+            .iter()
+            .map(|file| {
+                (
+                    file.path.to_string_lossy().into_owned(),
+                    file.operation,
+                    file.decision.is_allowed(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_touched_path() {
+        let (_dir, repo_root, engine) = setup_repo();
+        let MaybeApplyPatchVerified::Body(action) = maybe_parse_apply_patch_verified(
+            &["apply_patch".to_string(), PATCH.to_string()],
+            &repo_root,
+        ) else {
+            panic!("expected a verified patch");
+        };
+
+        let report = engine.check_patch(&action);
+
+        let path = |relative: &str| repo_root.join(relative).to_string_lossy().into_owned();
+        assert_eq!(
+            summary(&report),
+            vec![
+                (path("README.md"), PatchOperation::Update, true),
+                (path("secrets/token.txt"), PatchOperation::Delete, false),
+                (path("src/lib.rs"), PatchOperation::Move, false),
+                (path("src/new.rs"), PatchOperation::Add, true),
+            ]
+        );
+        assert!(report.is_denied());
+        assert!(!report.needs_approval());
+
+        // The move source is allowed; the destination is what gets denied.
+        let moved = &report.files[2];
+        assert_eq!(moved.move_path, Some(repo_root.join("secrets/lib.rs")));
+        assert_eq!(
+            moved.decision.reason().rule,
+            PolicyRule::DenyGlob {
+                glob: "*/secrets/*".to_string()
+            }
+        );
+        assert_eq!(report.denied().count(), 2);
+    }
+
+    #[test]
+    fn checks_unverified_hunks() {
+        let (_dir, repo_root, engine) = setup_repo();
+        let args = aeye_apply_patch::parse_patch(PATCH).unwrap();
+
+        let report = engine.check_hunks(&args.hunks, &repo_root);
+
+        assert_eq!(
+            report
+                .files
+                .iter()
+                .map(|file| file.operation)
+                .collect::<Vec<_>>(),
+            vec![
+                PatchOperation::Update,
+                PatchOperation::Delete,
+                PatchOperation::Move,
+                PatchOperation::Add,
+            ]
+        );
+        assert!(report.is_denied());
+
+        let add_only = aeye_apply_patch::parse_patch(
+            "*** Begin Patch\n*** Add File: Cargo.lock\n+# generated\n*** End Patch",
+        )
+        .unwrap();
+        let report = PolicyEngine::new(
+            AEyeConfig {
+                default_tier: crate::config::TIER_SUPERVISED_EXECUTION,
+                ..AEyeConfig::default()
+            },
+            Some(repo_root.clone()),
+        )
+        .unwrap()
+        .check_hunks(&add_only.hunks, &repo_root);
+        assert!(report.needs_approval());
+        assert_eq!(report.needing_approval().count(), 1);
+    }
+
+    #[test]
+    fn symlinks_are_checked_against_their_target() {
+        let (_dir, repo_root, engine) = setup_repo();
+        let args = aeye_apply_patch::parse_patch(
+            "*** Begin Patch\n*** Add Symlink: src/token\n+../secrets/token.txt\n*** Set Executable: src/lib.rs\n*** End Patch",
+        )
+        .unwrap();
+
+        let report = engine.check_hunks(&args.hunks, &repo_root);
+
+        let path = |relative: &str| repo_root.join(relative).to_string_lossy().into_owned();
+        assert_eq!(
+            summary(&report),
+            vec![
+                (path("src/lib.rs"), PatchOperation::SetExecutable, true),
+                (path("src/token"), PatchOperation::AddSymlink, false),
+            ]
+        );
+        assert_eq!(
+            report.files[1].symlink_target,
+            Some(repo_root.join("src/../secrets/token.txt"))
+        );
+        assert_eq!(report.files[1].move_path, None);
+    }
+}
@@ -1,74 +1,425 @@
-use crate::config::AEyeConfig;
+use crate::config::{AEyeConfig, TIER_EXPLAIN_ONLY, TIER_PLAN_AND_DIFF, TIER_SUPERVISED_EXECUTION};
+use crate::config_check::ConfigError;
+use crate::config_loader::ConfigSource;
+use crate::decision::{PolicyDecision, PolicyReason, PolicyRule};
+use crate::protected::{ProtectedClass, ignored_by_git};
+use aeye_execpolicy::execpolicycheck::load_policies;
+use aeye_execpolicy::{Decision, EvaluationContext, Policy, RuleMatch};
+use aeye_utils_absolute_path::AbsolutePathBuf;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexSet};
 use std::path::{Path, PathBuf};
-use wildmatch::WildMatch;
+
+/// The recipe actions A-Eye knows about, and so the keys `action_tiers`
+/// accepts.
+pub const KNOWN_ACTIONS: &[&str] = &[
+    "system.scan",
+    "llm.explain",
+    "llm.plan",
+    "llm.patch",
+    "tools.apply",
+    "tools.verify",
+];
+
+/// Built-in minimum tier for the recipe actions A-Eye knows about. Unknown
+/// actions are treated as supervised execution.
+pub fn default_action_tier(action: &str) -> u8 {
+    match action {
+        "system.scan" | "llm.explain" => TIER_EXPLAIN_ONLY,
+        "llm.plan" | "llm.patch" => TIER_PLAN_AND_DIFF,
+        _ => TIER_SUPERVISED_EXECUTION,
+    }
+}
+
+/// Loads the execpolicy rule files listed in `config.execpolicy_rules`.
+/// Relative paths are resolved against `repo_root` when one is known. Fails
+/// if `config.execpolicy_profile` is not declared by those files.
+pub fn load_exec_policy(config: &AEyeConfig, repo_root: Option<&Path>) -> anyhow::Result<Policy> {
+    let rule_paths: Vec<PathBuf> = config
+        .execpolicy_rules
+        .iter()
+        .map(|path| match repo_root {
+            Some(repo_root) if path.is_relative() => repo_root.join(path),
+            _ => path.clone(),
+        })
+        .collect();
+    let policy = load_policies(&rule_paths)?;
+    policy.resolution_order(config.execpolicy_profile.as_deref())?;
+    Ok(policy)
+}
+
+/// Compiles `globs` into one matcher whose match indices line up with the
+/// config list, so the first configured glob can be reported.
+fn compile_globs(globs: &[String]) -> Result<GlobSet, ConfigError> {
+    let invalid_glob = |glob: &str, err: globset::Error| ConfigError::InvalidGlob {
+        glob: glob.to_string(),
+        message: err.kind().to_string(),
+    };
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| invalid_glob(glob, err))?);
+    }
+    builder.build().map_err(|err| {
+        let glob = err.glob().unwrap_or_default().to_string();
+        invalid_glob(&glob, err)
+    })
+}
+
+fn compile_regexes(patterns: &[String]) -> Result<RegexSet, ConfigError> {
+    // Compile individually first: `RegexSet` errors do not say which
+    // pattern was at fault.
+    for pattern in patterns {
+        Regex::new(pattern).map_err(|err| ConfigError::InvalidRegex {
+            pattern: pattern.clone(),
+            message: err.to_string(),
+        })?;
+    }
+    RegexSet::new(patterns).map_err(|err| ConfigError::InvalidRegex {
+        pattern: patterns.join(" | "),
+        message: err.to_string(),
+    })
+}
+
+/// First configured entry that matched, given the indices from a set match.
+fn first_match(entries: &[String], indices: impl IntoIterator<Item = usize>) -> Option<&String> {
+    indices
+        .into_iter()
+        .min()
+        .and_then(|index| entries.get(index))
+}
 
 pub struct PolicyEngine {
     config: AEyeConfig,
     repo_root: Option<PathBuf>,
+    exec_policy: Policy,
+    deny_globs: GlobSet,
+    write_allowlist: GlobSet,
+    shell_deny_patterns: RegexSet,
 }
 
 impl PolicyEngine {
-    pub fn new(config: AEyeConfig, repo_root: Option<PathBuf>) -> Self {
-        Self { config, repo_root }
+    /// Builds an engine, compiling every glob and regex in `config` up front
+    /// so a bad pattern fails here rather than being skipped on each check.
+    pub fn new(config: AEyeConfig, repo_root: Option<PathBuf>) -> Result<Self, ConfigError> {
+        // Compare against the real repo location so a symlinked checkout does
+        // not make every resolved path look like it is outside the repo.
+        let repo_root = repo_root.map(|repo_root| {
+            std::env::current_dir()
+                .and_then(|cwd| AbsolutePathBuf::resolve_symlinks_against_base(&repo_root, cwd))
+                .map(AbsolutePathBuf::into_path_buf)
+                .unwrap_or(repo_root)
+        });
+        Ok(Self {
+            deny_globs: compile_globs(&config.deny_globs)?,
+            write_allowlist: compile_globs(&config.write_allowlist)?,
+            shell_deny_patterns: compile_regexes(&config.shell_deny_patterns)?,
+            config,
+            repo_root,
+            exec_policy: Policy::empty(),
+        })
+    }
+
+    /// Evaluates shell commands against `exec_policy` in addition to
+    /// `shell_deny_patterns`. See [`load_exec_policy`].
+    pub fn with_exec_policy(mut self, exec_policy: Policy) -> Self {
+        self.exec_policy = exec_policy;
+        self
+    }
+
+    pub fn current_tier(&self) -> u8 {
+        self.config.default_tier
+    }
+
+    pub fn check_tier(&self, required_tier: u8) -> bool {
+        self.current_tier() >= required_tier
+    }
+
+    pub fn required_tier(&self, action: &str) -> u8 {
+        self.config
+            .action_tiers
+            .get(action)
+            .copied()
+            .unwrap_or_else(|| default_action_tier(action))
+    }
+
+    /// Decides whether `action` may run at the current tier. Anything that
+    /// requires supervised execution always needs an approval prompt, even
+    /// when the current tier is high enough.
+    pub fn check_action(&self, action: &str) -> PolicyDecision {
+        let required_tier = self.required_tier(action);
+        let current_tier = self.current_tier();
+        let rule = PolicyRule::ActionTier {
+            action: action.to_string(),
+            required_tier,
+            current_tier,
+        };
+        let source = if self.config.action_tiers.contains_key(action) {
+            self.config_source(&format!("action_tiers.{action}"))
+        } else {
+            None
+        };
+
+        if current_tier < required_tier {
+            PolicyDecision::Deny(PolicyReason {
+                rule,
+                source,
+                justification: format!(
+                    "`{action}` requires tier {required_tier}, but the current tier is {current_tier}"
+                ),
+            })
+        } else if required_tier >= TIER_SUPERVISED_EXECUTION {
+            PolicyDecision::NeedsApproval(PolicyReason {
+                rule,
+                source,
+                justification: format!("`{action}` is supervised and needs explicit approval"),
+            })
+        } else {
+            PolicyDecision::Allow(PolicyReason {
+                rule,
+                source,
+                justification: format!("`{action}` is allowed at tier {current_tier}"),
+            })
+        }
     }
 
-    pub fn check_write(&self, path: &Path) -> bool {
-        let path = self.resolve_path(path);
-        if !self.is_within_repo(&path) {
-            return false;
+    /// Decides whether `path` may be written. Relative paths are resolved
+    /// against the repo root, and `..` segments and symlinks are resolved
+    /// before any rule is applied, so globs and the repo boundary are checked
+    /// against the location the write would actually reach.
+    pub fn check_write(&self, path: &Path) -> PolicyDecision {
+        let path = match self.resolve_path(path) {
+            Ok(resolved) => resolved.into_path_buf(),
+            Err(err) => {
+                return PolicyDecision::Deny(PolicyReason {
+                    rule: PolicyRule::UnresolvablePath,
+                    source: None,
+                    justification: format!("could not resolve {}: {err}", path.display()),
+                });
+            }
+        };
+        if let Some(ref repo_root) = self.repo_root
+            && !path.starts_with(repo_root)
+        {
+            return PolicyDecision::Deny(PolicyReason {
+                rule: PolicyRule::OutsideRepo {
+                    repo_root: repo_root.clone(),
+                },
+                source: None,
+                justification: format!(
+                    "{} is outside the repository at {}",
+                    path.display(),
+                    repo_root.display()
+                ),
+            });
         }
 
         let path_str = path.to_string_lossy();
 
-        for glob in &self.config.deny_globs {
-            if WildMatch::new(glob).matches(&path_str) {
-                return false;
+        if let Some(glob) = first_match(&self.config.deny_globs, self.deny_globs.matches(&path)) {
+            return PolicyDecision::Deny(self.config_reason(
+                "deny_globs",
+                PolicyRule::DenyGlob { glob: glob.clone() },
+                format!("{path_str} matches deny glob `{glob}`"),
+            ));
+        }
+
+        let allowed = if self.config.write_allowlist.is_empty() {
+            PolicyReason {
+                rule: PolicyRule::Default,
+                source: None,
+                justification: format!("no write policy restricts {path_str}"),
             }
+        } else {
+            // Relative entries such as `src/*` are written against the path
+            // inside the repository.
+            let relative = self
+                .repo_root
+                .as_deref()
+                .and_then(|repo_root| path.strip_prefix(repo_root).ok());
+            let Some(glob) = first_match(
+                &self.config.write_allowlist,
+                self.write_allowlist.matches(&path).into_iter().chain(
+                    relative
+                        .map(|relative| self.write_allowlist.matches(relative))
+                        .unwrap_or_default(),
+                ),
+            ) else {
+                return PolicyDecision::Deny(self.config_reason(
+                    "write_allowlist",
+                    PolicyRule::WriteAllowlistMiss,
+                    format!("{path_str} does not match any entry in write_allowlist"),
+                ));
+            };
+            self.config_reason(
+                "write_allowlist",
+                PolicyRule::WriteAllowlist { glob: glob.clone() },
+                format!("{path_str} matches write allowlist entry `{glob}`"),
+            )
+        };
+
+        // Protection applies even to allowlisted paths: the allowlist says
+        // where A-Eye may write, not that it may overwrite secrets there.
+        if let Some(protected) = self.protected_reason(&path) {
+            return self.gate_protected_write(protected);
         }
 
-        if self.config.write_allowlist.is_empty() {
-            return true;
+        PolicyDecision::Allow(allowed)
+    }
+
+    /// Explains why `path` is protected, if it is a built-in protected file
+    /// or (with `protect_ignored_files`) ignored by git.
+    fn protected_reason(&self, path: &Path) -> Option<PolicyReason> {
+        let relative = self
+            .repo_root
+            .as_deref()
+            .and_then(|repo_root| path.strip_prefix(repo_root).ok())
+            .unwrap_or(path);
+        if let Some((class, pattern)) = ProtectedClass::of(relative) {
+            return Some(PolicyReason {
+                rule: PolicyRule::ProtectedFile {
+                    class,
+                    pattern: pattern.to_string(),
+                },
+                source: None,
+                justification: format!(
+                    "{} is protected as {class} (`{pattern}`)",
+                    relative.display()
+                ),
+            });
         }
 
-        for glob in &self.config.write_allowlist {
-            if WildMatch::new(glob).matches(&path_str) {
-                return true;
-            }
+        if !self.config.protect_ignored_files {
+            return None;
         }
+        let ignored = ignored_by_git(self.repo_root.as_deref()?, path)?;
+        let from = ignored
+            .file
+            .as_ref()
+            .map(|file| format!(" in {}", file.display()))
+            .unwrap_or_default();
+        Some(self.config_reason(
+            "protect_ignored_files",
+            PolicyRule::IgnoredFile {
+                pattern: ignored.pattern.clone(),
+                gitignore: ignored.file,
+            },
+            format!(
+                "{} is ignored by git (`{}`{from})",
+                relative.display(),
+                ignored.pattern
+            ),
+        ))
+    }
 
-        false
+    /// Protected files may only be written under supervised execution, and
+    /// even then each write needs explicit approval.
+    fn gate_protected_write(&self, mut reason: PolicyReason) -> PolicyDecision {
+        let current_tier = self.current_tier();
+        if current_tier < TIER_SUPERVISED_EXECUTION {
+            reason.justification = format!(
+                "{}; modifying it requires tier {TIER_SUPERVISED_EXECUTION}, but the current tier is {current_tier}",
+                reason.justification
+            );
+            PolicyDecision::Deny(reason)
+        } else {
+            reason.justification = format!("{} and needs explicit approval", reason.justification);
+            PolicyDecision::NeedsApproval(reason)
+        }
     }
 
-    pub fn check_shell(&self, command: &str) -> bool {
-        for pattern in &self.config.shell_deny_patterns {
-            if let Ok(regex) = regex::Regex::new(pattern) {
-                if regex.is_match(command) {
-                    return false;
-                }
+    pub fn check_shell(&self, command: &str) -> PolicyDecision {
+        if let Some(pattern) = first_match(
+            &self.config.shell_deny_patterns,
+            self.shell_deny_patterns.matches(command),
+        ) {
+            return PolicyDecision::Deny(self.config_reason(
+                "shell_deny_patterns",
+                PolicyRule::ShellDenyPattern {
+                    pattern: pattern.clone(),
+                },
+                format!("command matches shell deny pattern `{pattern}`"),
+            ));
+        }
+
+        // Commands run from the repository root with this process's
+        // environment. Commands no execpolicy rule mentions fall through to
+        // the default allow, matching the behaviour when no rules are
+        // configured.
+        let context = EvaluationContext {
+            profile: self.config.execpolicy_profile.clone(),
+            ..EvaluationContext::from_process_env()
+        };
+        let evaluation = self
+            .exec_policy
+            .check_shell(command, &context, &|_: &[String]| Decision::Allow);
+        let mut strictest = evaluation
+            .matched_rules
+            .iter()
+            .filter(|rule_match| rule_match.decision() == evaluation.decision);
+        let (matched_prefix, decision, justification, rule_source) = match strictest
+            .find(|rule_match| !matches!(rule_match, RuleMatch::HeuristicsRuleMatch { .. }))
+        {
+            Some(RuleMatch::PrefixRuleMatch {
+                matched_prefix,
+                decision,
+                justification,
+                source,
+                ..
+            }) => (matched_prefix, *decision, justification, source),
+            Some(RuleMatch::UnanalyzableShellMatch { text, reason, .. }) => {
+                return PolicyDecision::NeedsApproval(PolicyReason {
+                    rule: PolicyRule::UnparsableCommand,
+                    source: None,
+                    justification: format!("cannot check `{text}`: {reason}"),
+                });
+            }
+            Some(RuleMatch::HeuristicsRuleMatch { .. }) | None => {
+                return PolicyDecision::Allow(PolicyReason {
+                    rule: PolicyRule::Default,
+                    source: None,
+                    justification: "no shell policy matches the command".to_string(),
+                });
             }
+        };
+
+        let rendered_prefix = matched_prefix.join(" ");
+        let reason = PolicyReason {
+            rule: PolicyRule::ExecPolicyPrefix {
+                matched_prefix: matched_prefix.clone(),
+                decision,
+                rule_source: rule_source.clone(),
+            },
+            source: None,
+            justification: justification
+                .clone()
+                .unwrap_or_else(|| format!("`{rendered_prefix}` matches an execpolicy rule")),
+        };
+        match decision {
+            Decision::Allow => PolicyDecision::Allow(reason),
+            Decision::Prompt => PolicyDecision::NeedsApproval(reason),
+            Decision::Forbidden => PolicyDecision::Deny(reason),
         }
-        true
     }
 
-    fn resolve_path(&self, path: &Path) -> PathBuf {
-        if path.is_absolute() {
-            return path.to_path_buf();
+    fn config_reason(&self, key: &str, rule: PolicyRule, justification: String) -> PolicyReason {
+        PolicyReason {
+            rule,
+            source: self.config_source(key),
+            justification,
         }
+    }
 
-        if let Some(ref repo_root) = self.repo_root {
-            repo_root.join(path)
-        } else {
-            std::env::current_dir()
-                .map(|cwd| cwd.join(path))
-                .unwrap_or_else(|_| path.to_path_buf())
+    fn config_source(&self, key: &str) -> Option<ConfigSource> {
+        match self.config.provenance.source_of(key) {
+            ConfigSource::Default => None,
+            source => Some(source.clone()),
         }
     }
 
-    fn is_within_repo(&self, path: &Path) -> bool {
-        if let Some(ref repo_root) = self.repo_root {
-            path.starts_with(repo_root)
-        } else {
-            true
+    fn resolve_path(&self, path: &Path) -> std::io::Result<AbsolutePathBuf> {
+        match self.repo_root {
+            Some(ref repo_root) => AbsolutePathBuf::resolve_symlinks_against_base(path, repo_root),
+            None => AbsolutePathBuf::resolve_symlinks_against_base(path, std::env::current_dir()?),
         }
     }
 }
@@ -76,12 +427,13 @@ impl PolicyEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use aeye_execpolicy::RuleSource;
     use std::fs;
     use std::io::Write;
     use tempfile::tempdir;
 
     fn setup_test_repo() -> (tempfile::TempDir, PathBuf) {
-        let dir = tempfile::tempdir().unwrap();
+        let dir = tempdir().unwrap();
         let repo_root = dir.path().to_path_buf();
         fs::create_dir(repo_root.join(".git")).unwrap();
         fs::create_dir_all(repo_root.join("src/components")).unwrap();
@@ -104,21 +456,26 @@ mod tests {
             deny_globs: vec![],
             write_allowlist: vec![],
             shell_deny_patterns: vec![r"rm\s+-rf".to_string()],
+            ..AEyeConfig::default()
         };
 
-        let policy_engine = PolicyEngine::new(config, None);
+        let policy_engine = PolicyEngine::new(config, None).unwrap();
 
-        assert!(!policy_engine.check_shell("rm -rf /tmp/foo"));
-        assert!(policy_engine.check_shell("ls -la"));
+        assert!(policy_engine.check_shell("rm -rf /tmp/foo").is_denied());
+        assert!(policy_engine.check_shell("ls -la").is_allowed());
     }
 
     #[test]
     fn test_policy_engine_allows_by_default() {
         let config = AEyeConfig::default();
-        let policy_engine = PolicyEngine::new(config, None);
+        let policy_engine = PolicyEngine::new(config, None).unwrap();
 
-        assert!(policy_engine.check_write(Path::new("any/file.txt")));
-        assert!(policy_engine.check_shell("any command"));
+        assert!(
+            policy_engine
+                .check_write(Path::new("any/file.txt"))
+                .is_allowed()
+        );
+        assert!(policy_engine.check_shell("any command").is_allowed());
     }
 
     #[test]
@@ -127,11 +484,457 @@ mod tests {
             deny_globs: vec![],
             write_allowlist: vec![],
             shell_deny_patterns: vec![r"rm\s+-rf".to_string()],
+            ..AEyeConfig::default()
+        };
+
+        let policy_engine = PolicyEngine::new(config, None).unwrap();
+
+        assert!(policy_engine.check_shell("rm -rf /tmp/foo").is_denied());
+        assert!(policy_engine.check_shell("ls -la").is_allowed());
+    }
+
+    #[test]
+    fn test_policy_engine_defaults_to_plan_and_diff_tier() {
+        let policy_engine = PolicyEngine::new(AEyeConfig::default(), None).unwrap();
+
+        assert_eq!(policy_engine.current_tier(), TIER_PLAN_AND_DIFF);
+        assert!(policy_engine.check_tier(1));
+        assert!(!policy_engine.check_tier(2));
+        assert!(policy_engine.check_action("llm.plan").is_allowed());
+        assert_eq!(
+            policy_engine.check_action("tools.apply"),
+            PolicyDecision::Deny(PolicyReason {
+                rule: PolicyRule::ActionTier {
+                    action: "tools.apply".to_string(),
+                    required_tier: 2,
+                    current_tier: 1,
+                },
+                source: None,
+                justification: "`tools.apply` requires tier 2, but the current tier is 1"
+                    .to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_policy_engine_supervised_actions_need_approval() {
+        let config = AEyeConfig {
+            default_tier: TIER_SUPERVISED_EXECUTION,
+            ..AEyeConfig::default()
+        };
+        let policy_engine = PolicyEngine::new(config, None).unwrap();
+
+        assert!(policy_engine.check_action("system.scan").is_allowed());
+        assert!(policy_engine.check_action("tools.apply").needs_approval());
+        assert!(policy_engine.check_action("custom.deploy").needs_approval());
+    }
+
+    #[test]
+    fn test_policy_engine_action_tier_overrides() {
+        let config = AEyeConfig {
+            default_tier: TIER_EXPLAIN_ONLY,
+            action_tiers: [
+                ("llm.plan".to_string(), TIER_EXPLAIN_ONLY),
+                ("system.scan".to_string(), TIER_PLAN_AND_DIFF),
+            ]
+            .into_iter()
+            .collect(),
+            provenance: [(
+                "action_tiers".to_string(),
+                ConfigSource::Repo(PathBuf::from("/repo/a-eye.yaml")),
+            )]
+            .into_iter()
+            .collect(),
+            ..AEyeConfig::default()
         };
+        let policy_engine = PolicyEngine::new(config, None).unwrap();
 
-        let policy_engine = PolicyEngine::new(config, None);
+        let plan = policy_engine.check_action("llm.plan");
+        assert!(plan.is_allowed());
+        assert_eq!(
+            plan.reason().source,
+            Some(ConfigSource::Repo(PathBuf::from("/repo/a-eye.yaml")))
+        );
+        assert!(policy_engine.check_action("system.scan").is_denied());
+    }
+
+    #[test]
+    fn test_policy_engine_write_allowlist_matches_repo_relative_paths() {
+        let (_dir, repo_root) = setup_test_repo();
+        let config = AEyeConfig {
+            write_allowlist: vec!["src/*".to_string()],
+            ..AEyeConfig::default()
+        };
+        let policy_engine = PolicyEngine::new(config, Some(repo_root.clone())).unwrap();
+
+        assert!(
+            policy_engine
+                .check_write(Path::new("src/main.rs"))
+                .is_allowed()
+        );
+        assert!(
+            policy_engine
+                .check_write(&repo_root.join("src/lib.rs"))
+                .is_allowed()
+        );
+        assert!(
+            policy_engine
+                .check_write(Path::new("docs/src/x.md"))
+                .is_denied()
+        );
+    }
+
+    #[test]
+    fn test_policy_engine_write_decisions_carry_matching_rule() {
+        let (_dir, repo_root) = setup_test_repo();
+        let source = ConfigSource::Repo(repo_root.join("a-eye.yaml"));
+        let config = AEyeConfig {
+            deny_globs: vec!["*.json".to_string()],
+            write_allowlist: vec!["*/src/*".to_string()],
+            provenance: [
+                ("deny_globs".to_string(), source.clone()),
+                (
+                    "write_allowlist".to_string(),
+                    ConfigSource::Env("AEYE_WRITE_ALLOWLIST".to_string()),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..AEyeConfig::default()
+        };
+        let policy_engine = PolicyEngine::new(config, Some(repo_root.clone())).unwrap();
+
+        let denied = policy_engine.check_write(Path::new("credentials.json"));
+        assert_eq!(
+            denied.reason().rule,
+            PolicyRule::DenyGlob {
+                glob: "*.json".to_string()
+            }
+        );
+        assert_eq!(denied.reason().source, Some(source));
+        assert!(denied.is_denied());
+
+        let allowed = policy_engine.check_write(Path::new("src/components/button.js"));
+        assert_eq!(
+            allowed,
+            PolicyDecision::Allow(PolicyReason {
+                rule: PolicyRule::WriteAllowlist {
+                    glob: "*/src/*".to_string()
+                },
+                source: Some(ConfigSource::Env("AEYE_WRITE_ALLOWLIST".to_string())),
+                justification: format!(
+                    "{} matches write allowlist entry `*/src/*`",
+                    repo_root.join("src/components/button.js").display()
+                ),
+            })
+        );
+
+        let missed = policy_engine.check_write(Path::new("docs/guide.md"));
+        assert!(missed.is_denied());
+        assert_eq!(missed.reason().rule, PolicyRule::WriteAllowlistMiss);
+
+        let outside = policy_engine.check_write(Path::new("/etc/passwd"));
+        assert!(outside.is_denied());
+        assert_eq!(outside.reason().rule, PolicyRule::OutsideRepo { repo_root },);
+        assert_eq!(outside.reason().source, None);
+    }
+
+    #[test]
+    fn test_policy_engine_write_rejects_traversal() {
+        let (_dir, repo_root) = setup_test_repo();
+        let policy_engine =
+            PolicyEngine::new(AEyeConfig::default(), Some(repo_root.clone())).unwrap();
+
+        assert!(
+            policy_engine
+                .check_write(Path::new("src/../docs/new.md"))
+                .is_allowed()
+        );
+        let escaped = policy_engine.check_write(Path::new("src/../../outside.txt"));
+        assert!(escaped.is_denied());
+        assert_eq!(escaped.reason().rule, PolicyRule::OutsideRepo { repo_root });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_policy_engine_write_follows_symlinks() {
+        let (_dir, repo_root) = setup_test_repo();
+        let outside = tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), repo_root.join("escape")).unwrap();
+        std::os::unix::fs::symlink(repo_root.join("docs"), repo_root.join("docs-link")).unwrap();
+        std::os::unix::fs::symlink(repo_root.join("loop"), repo_root.join("loop")).unwrap();
+        let config = AEyeConfig {
+            deny_globs: vec!["*/docs/*".to_string()],
+            ..AEyeConfig::default()
+        };
+        let policy_engine = PolicyEngine::new(config, Some(repo_root.clone())).unwrap();
+
+        // The file does not exist yet, but its parent is a link out of the repo.
+        let escaped = policy_engine.check_write(Path::new("escape/new/file.txt"));
+        assert!(escaped.is_denied());
+        assert_eq!(escaped.reason().rule, PolicyRule::OutsideRepo { repo_root });
+
+        // Globs see the link target, not the link name.
+        let aliased = policy_engine.check_write(Path::new("docs-link/guide.md"));
+        assert_eq!(
+            aliased.reason().rule,
+            PolicyRule::DenyGlob {
+                glob: "*/docs/*".to_string()
+            }
+        );
+
+        let looped = policy_engine.check_write(Path::new("loop/file.txt"));
+        assert!(looped.is_denied());
+        assert_eq!(looped.reason().rule, PolicyRule::UnresolvablePath);
+    }
+
+    #[test]
+    fn test_policy_engine_protected_files_need_supervised_approval() {
+        let (_dir, repo_root) = setup_test_repo();
+        let config = AEyeConfig {
+            write_allowlist: vec!["*".to_string()],
+            ..AEyeConfig::default()
+        };
+        let plan_tier = PolicyEngine::new(config.clone(), Some(repo_root.clone())).unwrap();
+
+        let lockfile = plan_tier.check_write(Path::new("package-lock.json"));
+        assert_eq!(
+            lockfile,
+            PolicyDecision::Deny(PolicyReason {
+                rule: PolicyRule::ProtectedFile {
+                    class: ProtectedClass::Lockfile,
+                    pattern: "package-lock.json".to_string(),
+                },
+                source: None,
+                justification: "package-lock.json is protected as a lockfile \
+                    (`package-lock.json`); modifying it requires tier 2, but the current tier is 1"
+                    .to_string(),
+            })
+        );
+        assert!(plan_tier.check_write(Path::new(".git/config")).is_denied());
+        // Ignored files are only protected when opted in.
+        assert!(
+            plan_tier
+                .check_write(Path::new("credentials.json"))
+                .is_allowed()
+        );
+
+        let supervised = PolicyEngine::new(
+            AEyeConfig {
+                default_tier: TIER_SUPERVISED_EXECUTION,
+                ..config
+            },
+            Some(repo_root),
+        )
+        .unwrap();
+        assert!(
+            supervised
+                .check_write(Path::new("config/.env"))
+                .needs_approval()
+        );
+        assert!(supervised.check_write(Path::new("README.md")).is_allowed());
+    }
+
+    #[test]
+    fn test_policy_engine_protect_ignored_files() {
+        let (_dir, repo_root) = setup_test_repo();
+        let config = AEyeConfig {
+            default_tier: TIER_SUPERVISED_EXECUTION,
+            protect_ignored_files: true,
+            ..AEyeConfig::default()
+        };
+        let policy_engine = PolicyEngine::new(config, Some(repo_root.clone())).unwrap();
+
+        let decision = policy_engine.check_write(Path::new("credentials.json"));
+        assert!(decision.needs_approval());
+        assert_eq!(
+            decision.reason().rule,
+            PolicyRule::IgnoredFile {
+                pattern: "credentials.json".to_string(),
+                gitignore: Some(repo_root.join(".gitignore")),
+            }
+        );
+        assert!(
+            policy_engine
+                .check_write(Path::new("vendor/deps.lock"))
+                .needs_approval()
+        );
+        assert!(
+            policy_engine
+                .check_write(Path::new("src/components/button.js"))
+                .is_allowed()
+        );
+    }
+
+    #[test]
+    fn test_policy_engine_rejects_invalid_patterns() {
+        let bad_glob = AEyeConfig {
+            deny_globs: vec!["*.pem".to_string(), "src/[oops".to_string()],
+            ..AEyeConfig::default()
+        };
+        assert_eq!(
+            PolicyEngine::new(bad_glob, None).err(),
+            Some(ConfigError::InvalidGlob {
+                glob: "src/[oops".to_string(),
+                message: "unclosed character class; missing ']'".to_string(),
+            })
+        );
+
+        let bad_regex = AEyeConfig {
+            shell_deny_patterns: vec![r"rm\s+(-rf".to_string()],
+            ..AEyeConfig::default()
+        };
+        assert!(matches!(
+            PolicyEngine::new(bad_regex, None),
+            Err(ConfigError::InvalidRegex { pattern, .. }) if pattern == r"rm\s+(-rf"
+        ));
+    }
+
+    #[test]
+    fn test_policy_engine_shell_decision_names_pattern() {
+        let config = AEyeConfig {
+            shell_deny_patterns: vec![r"rm\s+-rf".to_string()],
+            ..AEyeConfig::default()
+        };
+        let policy_engine = PolicyEngine::new(config, None).unwrap();
+
+        let decision = policy_engine.check_shell("rm -rf /tmp/foo");
+        assert_eq!(
+            decision.to_string(),
+            r"denied: command matches shell deny pattern `rm\s+-rf`"
+        );
+    }
+
+    #[test]
+    fn test_policy_engine_shell_uses_exec_policy() {
+        let mut parser = aeye_execpolicy::PolicyParser::new();
+        parser
+            .parse(
+                "test.rules",
+                r#"
+prefix_rule(pattern = ["git", "push"], decision = "prompt")
+prefix_rule(
+    pattern = ["rm"],
+    decision = "forbidden",
+    justification = "use `trash` instead",
+)
+prefix_rule(pattern = ["terraform"], decision = "forbidden", env = {"AWS_PROFILE": "prod"})
+"#,
+            )
+            .unwrap();
+        let policy_engine = PolicyEngine::new(AEyeConfig::default(), None)
+            .unwrap()
+            .with_exec_policy(parser.build());
+
+        assert!(policy_engine.check_shell("git status").is_allowed());
+        assert!(policy_engine.check_shell("terraform plan").is_allowed());
+        assert!(
+            policy_engine
+                .check_shell("AWS_PROFILE=prod terraform apply")
+                .is_denied()
+        );
+        assert!(
+            policy_engine
+                .check_shell("git status && git push origin main")
+                .needs_approval()
+        );
+
+        assert_eq!(
+            policy_engine.check_shell("rm -rf target").to_string(),
+            "denied: use `trash` instead (from test.rules:3)"
+        );
+        assert_eq!(
+            policy_engine.check_shell("sh -c 'cargo build; rm -rf target'"),
+            PolicyDecision::Deny(PolicyReason {
+                rule: PolicyRule::ExecPolicyPrefix {
+                    matched_prefix: vec!["rm".to_string()],
+                    decision: Decision::Forbidden,
+                    rule_source: Some(RuleSource {
+                        path: "test.rules".to_string(),
+                        line: 3,
+                        end_line: 7,
+                    }),
+                },
+                source: None,
+                justification: "use `trash` instead".to_string(),
+            })
+        );
+        assert!(
+            policy_engine
+                .check_shell("echo 'unterminated")
+                .needs_approval()
+        );
+        assert_eq!(
+            policy_engine.check_shell("git status; eval \"$cmd\""),
+            PolicyDecision::NeedsApproval(PolicyReason {
+                rule: PolicyRule::UnparsableCommand,
+                source: None,
+                justification:
+                    "cannot check `eval \"$cmd\"`: `eval` runs a command built at run time"
+                        .to_string(),
+            })
+        );
+        assert!(policy_engine.check_shell("cat $(rm -rf build)").is_denied());
+        for wrapped in [
+            "sudo -E rm -rf /",
+            "env -S 'rm -rf /'",
+            "env --split-string='rm -rf /'",
+            "exec rm -rf /",
+            "timeout 5 rm -rf /",
+            "nohup rm -rf /",
+        ] {
+            assert!(policy_engine.check_shell(wrapped).is_denied(), "{wrapped}");
+        }
+    }
+
+    #[test]
+    fn test_load_exec_policy_resolves_relative_to_repo_root() {
+        let (_dir, repo_root) = setup_test_repo();
+        fs::create_dir_all(repo_root.join(".nlpg")).unwrap();
+        fs::write(
+            repo_root.join(".nlpg/default.rules"),
+            r#"
+profile("ci")
+prefix_rule(pattern = ["npm", "publish"], decision = "forbidden")
+prefix_rule(pattern = ["npm", "test"], decision = "prompt")
+prefix_rule(pattern = ["npm", "test"], profile = "ci")
+"#,
+        )
+        .unwrap();
+        let config = AEyeConfig {
+            execpolicy_rules: vec![PathBuf::from(".nlpg/default.rules")],
+            ..AEyeConfig::default()
+        };
+
+        let exec_policy = load_exec_policy(&config, Some(&repo_root)).unwrap();
+        let policy_engine = PolicyEngine::new(config.clone(), Some(repo_root.clone()))
+            .unwrap()
+            .with_exec_policy(exec_policy.clone());
+
+        assert!(
+            policy_engine
+                .check_shell("npm publish --dry-run")
+                .is_denied()
+        );
+        assert!(policy_engine.check_shell("npm test").needs_approval());
+
+        let ci = AEyeConfig {
+            execpolicy_profile: Some("ci".to_string()),
+            ..config
+        };
+        let policy_engine = PolicyEngine::new(ci.clone(), Some(repo_root.clone()))
+            .unwrap()
+            .with_exec_policy(exec_policy);
+        assert!(policy_engine.check_shell("npm test").is_allowed());
 
-        assert!(!policy_engine.check_shell("rm -rf /tmp/foo"));
-        assert!(policy_engine.check_shell("ls -la"));
+        let err = load_exec_policy(
+            &AEyeConfig {
+                execpolicy_profile: Some("release".to_string()),
+                ..ci
+            },
+            Some(&repo_root),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "unknown profile `release`");
     }
 }
@@ -0,0 +1,212 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::Match;
+use ignore::gitignore::GitignoreBuilder;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Kinds of files that are easy to damage by accident and are never written
+/// without explicit approval, whatever the write allowlist says.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProtectedClass {
+    /// Credentials and keys, e.g. `.env` and `*.pem`.
+    Secret,
+    /// Dependency lockfiles, e.g. `Cargo.lock` and `package-lock.json`.
+    Lockfile,
+    /// Anything inside a `.git/` directory.
+    GitMetadata,
+    /// CI pipeline definitions, e.g. `.github/workflows/*`.
+    CiConfig,
+}
+
+/// File-name globs for each class, matched against the last path component.
+const FILE_NAME_PATTERNS: &[(ProtectedClass, &str)] = &[
+    (ProtectedClass::Secret, ".env"),
+    (ProtectedClass::Secret, ".env.*"),
+    (ProtectedClass::Secret, "*.pem"),
+    (ProtectedClass::Secret, "*.key"),
+    (ProtectedClass::Secret, "*.p12"),
+    (ProtectedClass::Secret, "id_rsa*"),
+    (ProtectedClass::Secret, "id_ed25519*"),
+    (ProtectedClass::Lockfile, "Cargo.lock"),
+    (ProtectedClass::Lockfile, "package-lock.json"),
+    (ProtectedClass::Lockfile, "yarn.lock"),
+    (ProtectedClass::Lockfile, "pnpm-lock.yaml"),
+    (ProtectedClass::Lockfile, "poetry.lock"),
+    (ProtectedClass::Lockfile, "uv.lock"),
+    (ProtectedClass::Lockfile, "Gemfile.lock"),
+    (ProtectedClass::Lockfile, "composer.lock"),
+    (ProtectedClass::Lockfile, "go.sum"),
+    (ProtectedClass::CiConfig, ".gitlab-ci.yml"),
+    (ProtectedClass::CiConfig, ".travis.yml"),
+    (ProtectedClass::CiConfig, "azure-pipelines.yml"),
+    (ProtectedClass::CiConfig, "Jenkinsfile"),
+];
+
+/// Directory-relative globs, matched against the path below the repo root.
+const PATH_PATTERNS: &[(ProtectedClass, &str)] = &[
+    (ProtectedClass::CiConfig, ".github/workflows/*"),
+    (ProtectedClass::CiConfig, ".circleci/*"),
+];
+
+static FILE_NAME_GLOBS: LazyLock<GlobSet> = LazyLock::new(|| build_glob_set(FILE_NAME_PATTERNS));
+static PATH_GLOBS: LazyLock<GlobSet> = LazyLock::new(|| build_glob_set(PATH_PATTERNS));
+
+fn build_glob_set(patterns: &[(ProtectedClass, &str)]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for (_, pattern) in patterns {
+        #[expect(clippy::expect_used)]
+        builder.add(Glob::new(pattern).expect("built-in protected pattern must be valid"));
+    }
+    #[expect(clippy::expect_used)]
+    builder
+        .build()
+        .expect("built-in protected patterns must compile")
+}
+
+fn first_pattern(
+    patterns: &[(ProtectedClass, &'static str)],
+    indices: Vec<usize>,
+) -> Option<(ProtectedClass, &'static str)> {
+    indices
+        .into_iter()
+        .min()
+        .and_then(|index| patterns.get(index).copied())
+}
+
+impl ProtectedClass {
+    /// Classifies `relative_path` (relative to the repo root when known) and
+    /// returns the class together with the pattern that matched.
+    pub fn of(relative_path: &Path) -> Option<(Self, &'static str)> {
+        if relative_path
+            .components()
+            .any(|component| component.as_os_str() == ".git")
+        {
+            return Some((Self::GitMetadata, ".git/"));
+        }
+
+        let file_name = relative_path.file_name()?;
+        first_pattern(FILE_NAME_PATTERNS, FILE_NAME_GLOBS.matches(file_name))
+            .or_else(|| first_pattern(PATH_PATTERNS, PATH_GLOBS.matches(relative_path)))
+    }
+}
+
+impl fmt::Display for ProtectedClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Secret => "a secret",
+            Self::Lockfile => "a lockfile",
+            Self::GitMetadata => "git metadata",
+            Self::CiConfig => "CI config",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A `.gitignore` (or `.git/info/exclude`) rule that matched a path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IgnoreMatch {
+    pub pattern: String,
+    pub file: Option<PathBuf>,
+}
+
+/// Applies the repo's ignore files to `path` the way git does: every
+/// `.gitignore` between the repo root and the file is consulted, and the
+/// deepest one with an opinion wins, so `!negations` in subdirectories work.
+pub fn ignored_by_git(repo_root: &Path, path: &Path) -> Option<IgnoreMatch> {
+    let relative = path.strip_prefix(repo_root).ok()?;
+    let mut ignore_files = vec![repo_root.join(".git/info/exclude")];
+    let mut dir = repo_root.to_path_buf();
+    ignore_files.push(dir.join(".gitignore"));
+    if let Some(parent) = relative.parent() {
+        for component in parent.components() {
+            dir.push(component);
+            ignore_files.push(dir.join(".gitignore"));
+        }
+    }
+
+    for ignore_file in ignore_files.iter().rev() {
+        if !ignore_file.is_file() {
+            continue;
+        }
+        let root = match ignore_file.file_name() {
+            Some(name) if name == "exclude" => repo_root,
+            _ => ignore_file.parent().unwrap_or(repo_root),
+        };
+        let mut builder = GitignoreBuilder::new(root);
+        if builder.add(ignore_file).is_some() {
+            // Unreadable or partially invalid ignore files are skipped rather
+            // than failing the whole write check.
+            continue;
+        }
+        let Ok(gitignore) = builder.build() else {
+            continue;
+        };
+        match gitignore.matched_path_or_any_parents(path, path.is_dir()) {
+            Match::Ignore(glob) => {
+                return Some(IgnoreMatch {
+                    pattern: glob.original().to_string(),
+                    file: glob.from().map(Path::to_path_buf),
+                });
+            }
+            Match::Whitelist(_) => return None,
+            Match::None => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+
+    #[test]
+    fn classifies_builtin_protected_files() {
+        assert_eq!(
+            ProtectedClass::of(Path::new("config/.env.production")),
+            Some((ProtectedClass::Secret, ".env.*"))
+        );
+        assert_eq!(
+            ProtectedClass::of(Path::new("Cargo.lock")),
+            Some((ProtectedClass::Lockfile, "Cargo.lock"))
+        );
+        assert_eq!(
+            ProtectedClass::of(Path::new(".git/hooks/pre-commit")),
+            Some((ProtectedClass::GitMetadata, ".git/"))
+        );
+        assert_eq!(
+            ProtectedClass::of(Path::new(".github/workflows/ci.yml")),
+            Some((ProtectedClass::CiConfig, ".github/workflows/*"))
+        );
+        assert_eq!(ProtectedClass::of(Path::new("src/main.rs")), None);
+    }
+
+    #[test]
+    fn nested_gitignore_negation_wins() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_root = dir.path();
+        fs::create_dir_all(repo_root.join("logs/keep")).unwrap();
+        fs::write(repo_root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(repo_root.join("logs/keep/.gitignore"), "!important.log\n").unwrap();
+
+        assert_eq!(
+            ignored_by_git(repo_root, &repo_root.join("logs/debug.log")),
+            Some(IgnoreMatch {
+                pattern: "*.log".to_string(),
+                file: Some(repo_root.join(".gitignore")),
+            })
+        );
+        assert_eq!(
+            ignored_by_git(repo_root, &repo_root.join("logs/keep/important.log")),
+            None
+        );
+        assert_eq!(
+            ignored_by_git(repo_root, &repo_root.join("src/main.rs")),
+            None
+        );
+    }
+}
@@ -1,60 +1,109 @@
 use anyhow::Result;
 use crossterm::event;
+use crossterm::execute;
 use crossterm::terminal;
 use ratatui::prelude::*;
-use ratatui::widgets::Block;
-use ratatui::widgets::Paragraph;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use aeye_core::ConfigLoader;
+use aeye_core::PolicyEngine;
+use aeye_core::policy::load_exec_policy;
 
 use crate::args::Args;
+use crate::state::Action;
+use crate::state::AppState;
+use crate::ui;
+use crate::workflow::LocalWorkflow;
+use crate::workflow::Worker;
+use crate::workflow::WorkerEvent;
+
+/// How long to wait for input before checking for worker output.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 pub struct App {
-    args: Args,
+    pretty: bool,
     should_quit: bool,
+    state: AppState,
+    worker: Worker,
+    events: mpsc::Receiver<WorkerEvent>,
     terminal: Terminal<CrosstermBackend<std::io::Stderr>>,
 }
 
 impl App {
     pub fn new(args: Args) -> Result<Self> {
+        let loader = ConfigLoader::new(&args.directory);
+        let config = loader.load()?;
+        let repo_root = loader.repo_root();
+        let exec_policy = load_exec_policy(&config, repo_root.as_deref())?;
+        let policy = PolicyEngine::new(config, repo_root.clone())?.with_exec_policy(exec_policy);
+        let work_dir = repo_root.unwrap_or_else(|| args.directory.clone());
+
+        let (tx, events) = mpsc::channel();
+        let workflow = LocalWorkflow::new(work_dir.clone(), args.patch);
+        let worker = Worker::new(Box::new(workflow), policy, work_dir, tx);
         let terminal = Terminal::new(CrosstermBackend::new(std::io::stderr()))?;
 
         Ok(Self {
-            args,
+            pretty: !args.nopretty,
             should_quit: false,
+            state: AppState::new(args.model),
+            worker,
+            events,
             terminal,
         })
     }
 
     pub fn run(&mut self) -> Result<()> {
         terminal::enable_raw_mode()?;
+        execute!(std::io::stderr(), terminal::EnterAlternateScreen)?;
+
+        let result = self.event_loop();
+
+        execute!(std::io::stderr(), terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        result
+    }
 
-        loop {
-            if self.should_quit {
-                break;
+    fn event_loop(&mut self) -> Result<()> {
+        while !self.should_quit {
+            while let Ok(event) = self.events.try_recv() {
+                self.state.apply_event(event);
             }
             self.draw()?;
             self.handle_input()?;
         }
-
-        terminal::disable_raw_mode()?;
         Ok(())
     }
 
     fn draw(&mut self) -> Result<()> {
-        self.terminal.draw(|f| {
-            let area = f.area();
-            let paragraph = Paragraph::new("A-Eye CLI\n\nUse :quit to exit")
-                .block(Block::bordered().title("A-Eye"))
-                .centered();
-            f.render_widget(paragraph, area);
-        })?;
+        let (state, pretty) = (&self.state, self.pretty);
+        self.terminal
+            .draw(|frame| ui::render(frame, state, pretty))?;
         Ok(())
     }
 
     fn handle_input(&mut self) -> Result<()> {
-        if let event::Event::Key(key) = event::read()? {
-            if key.code == event::KeyCode::Char('q') {
-                self.should_quit = true;
+        if !event::poll(POLL_INTERVAL)? {
+            return Ok(());
+        }
+        let event::Event::Key(key) = event::read()? else {
+            return Ok(());
+        };
+        if key.kind != event::KeyEventKind::Press {
+            return Ok(());
+        }
+
+        match self.state.handle_key(key) {
+            Some(Action::SubmitIntent(intent)) => self.worker.prepare(intent),
+            Some(Action::Approve) => {
+                if let Some(review) = self.state.review.clone() {
+                    self.worker
+                        .apply_and_verify(self.state.patch.clone(), review);
+                }
             }
+            Some(Action::Quit) => self.should_quit = true,
+            None => {}
         }
         Ok(())
     }
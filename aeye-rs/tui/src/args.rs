@@ -1,16 +1,44 @@
+use aeye_core::config_check::ConfigCheckCommand;
 use clap::Parser;
+use clap::Subcommand;
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "aeye-tui")]
 #[command(about = "A-Eye Terminal UI", long_about = None)]
 pub struct Args {
+    /// Repository to work in. Config, policy and patch paths are resolved
+    /// against it.
     #[arg(short, long, default_value = ".")]
     pub directory: PathBuf,
 
+    /// Model to show in the status bar.
     #[arg(short, long)]
     pub model: Option<String>,
 
+    /// Render without colours.
     #[arg(long)]
     pub nopretty: bool,
+
+    /// Review an existing `apply_patch` file instead of asking a planner for
+    /// one. The plan is derived from the files the patch touches.
+    #[arg(long, value_name = "FILE")]
+    pub patch: Option<PathBuf>,
+
+    /// Run a command instead of starting the UI.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Inspect the A-Eye config.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Validate every config layer and report problems with their location.
+    Check(ConfigCheckCommand),
 }
@@ -1,6 +1,8 @@
 pub mod app;
 pub mod args;
+pub mod state;
 pub mod ui;
+pub mod workflow;
 
 pub use app::App;
 pub use args::Args;
@@ -1,10 +1,15 @@
 use aeye_tui::App;
 use aeye_tui::Args;
+use aeye_tui::args::Command;
+use aeye_tui::args::ConfigCommand;
 use anyhow::Result;
 use clap::Parser;
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    if let Some(Command::Config(ConfigCommand::Check(check))) = &args.command {
+        return check.run(&args.directory);
+    }
     let mut app = App::new(args)?;
     app.run()?;
     Ok(())
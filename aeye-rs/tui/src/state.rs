@@ -0,0 +1,298 @@
+//! UI state and key handling, kept separate from the terminal so it can be
+//! driven directly in tests.
+
+use crate::workflow::{LogLine, Review, WorkerEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stage {
+    /// Waiting for the user to describe what they want.
+    Intent,
+    Planning,
+    /// Showing the patch and waiting for approval.
+    Review,
+    Verifying,
+    Finished {
+        success: bool,
+    },
+    Failed(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Intent,
+    Plan,
+    Diff,
+    Log,
+}
+
+impl Pane {
+    fn next(self) -> Self {
+        match self {
+            Self::Intent => Self::Plan,
+            Self::Plan => Self::Diff,
+            Self::Diff => Self::Log,
+            Self::Log => Self::Intent,
+        }
+    }
+}
+
+/// What the app should do in response to a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    SubmitIntent(String),
+    Approve,
+    Quit,
+}
+
+pub struct AppState {
+    pub stage: Stage,
+    pub focus: Pane,
+    pub intent: String,
+    pub plan: Vec<String>,
+    pub patch: String,
+    pub review: Option<Review>,
+    pub log: Vec<LogLine>,
+    pub diff_scroll: u16,
+    /// Lines scrolled up from the bottom; 0 follows new output.
+    pub log_scroll: u16,
+    pub model: Option<String>,
+}
+
+impl AppState {
+    pub fn new(model: Option<String>) -> Self {
+        Self {
+            stage: Stage::Intent,
+            focus: Pane::Intent,
+            intent: String::new(),
+            plan: Vec::new(),
+            patch: String::new(),
+            review: None,
+            log: Vec::new(),
+            diff_scroll: 0,
+            log_scroll: 0,
+            model,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        if key.code == KeyCode::Tab {
+            self.focus = self.focus.next();
+            return None;
+        }
+
+        let typing = self.stage == Stage::Intent && self.focus == Pane::Intent;
+        if typing {
+            return self.edit_intent(key.code);
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char('y') if self.stage == Stage::Review => self.approve(),
+            KeyCode::Char('n') if self.stage == Stage::Review => {
+                self.log.push(LogLine::Info(
+                    "Patch rejected; nothing was changed".to_string(),
+                ));
+                self.restart();
+                None
+            }
+            KeyCode::Enter if matches!(self.stage, Stage::Finished { .. } | Stage::Failed(_)) => {
+                self.restart();
+                None
+            }
+            code => {
+                self.scroll(code);
+                None
+            }
+        }
+    }
+
+    fn edit_intent(&mut self, code: KeyCode) -> Option<Action> {
+        match code {
+            KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Enter if !self.intent.trim().is_empty() => {
+                self.stage = Stage::Planning;
+                self.focus = Pane::Plan;
+                self.plan.clear();
+                self.patch.clear();
+                self.review = None;
+                self.diff_scroll = 0;
+                Some(Action::SubmitIntent(self.intent.trim().to_string()))
+            }
+            KeyCode::Backspace => {
+                self.intent.pop();
+                None
+            }
+            KeyCode::Char(c) => {
+                self.intent.push(c);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn approve(&mut self) -> Option<Action> {
+        let can_approve = self.review.as_ref().is_some_and(Review::can_approve);
+        if !can_approve {
+            self.log.push(LogLine::Error(
+                "This patch is blocked by policy and cannot be approved".to_string(),
+            ));
+            return None;
+        }
+        self.stage = Stage::Verifying;
+        self.focus = Pane::Log;
+        self.log_scroll = 0;
+        Some(Action::Approve)
+    }
+
+    fn restart(&mut self) {
+        self.stage = Stage::Intent;
+        self.focus = Pane::Intent;
+    }
+
+    fn scroll(&mut self, code: KeyCode) {
+        let delta: i32 = match code {
+            KeyCode::Up | KeyCode::Char('k') => -1,
+            KeyCode::Down | KeyCode::Char('j') => 1,
+            KeyCode::PageUp => -10,
+            KeyCode::PageDown => 10,
+            _ => return,
+        };
+        match self.focus {
+            Pane::Diff => {
+                let max = self.patch.lines().count().saturating_sub(1);
+                self.diff_scroll = clamp_scroll(self.diff_scroll, delta, max);
+            }
+            // The log is anchored to the bottom, so scrolling up moves away
+            // from the newest line.
+            Pane::Log => {
+                let max = self.log.len().saturating_sub(1);
+                self.log_scroll = clamp_scroll(self.log_scroll, -delta, max);
+            }
+            Pane::Intent | Pane::Plan => {}
+        }
+    }
+
+    pub fn apply_event(&mut self, event: WorkerEvent) {
+        match event {
+            WorkerEvent::Planned(plan) => self.plan = plan,
+            WorkerEvent::Patched { patch, review } => {
+                self.patch = patch;
+                self.review = Some(*review);
+                self.stage = Stage::Review;
+                self.focus = Pane::Diff;
+            }
+            WorkerEvent::Log(line) => self.log.push(line),
+            WorkerEvent::Finished { success } => {
+                let summary = if success {
+                    LogLine::Info("Verification passed".to_string())
+                } else {
+                    LogLine::Error("Verification failed".to_string())
+                };
+                self.log.push(summary);
+                self.stage = Stage::Finished { success };
+            }
+            WorkerEvent::Failed(message) => {
+                self.log.push(LogLine::Error(message.clone()));
+                self.stage = Stage::Failed(message);
+            }
+        }
+    }
+}
+
+fn clamp_scroll(current: u16, delta: i32, max: usize) -> u16 {
+    let max = i32::try_from(max).unwrap_or(i32::MAX);
+    let next = (i32::from(current) + delta).clamp(0, max);
+    u16::try_from(next).unwrap_or(u16::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aeye_core::PolicyDecision;
+    use aeye_core::PolicyReason;
+    use aeye_core::PolicyRule;
+    use aeye_core::patch_policy::PatchPolicyReport;
+    use pretty_assertions::assert_eq;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn review(apply: PolicyDecision) -> Box<Review> {
+        Box::new(Review {
+            report: PatchPolicyReport { files: Vec::new() },
+            apply,
+            verify: PolicyDecision::Allow(reason()),
+            commands: Vec::new(),
+        })
+    }
+
+    fn reason() -> PolicyReason {
+        PolicyReason {
+            rule: PolicyRule::Default,
+            source: None,
+            justification: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn intent_is_typed_and_submitted() {
+        let mut state = AppState::new(None);
+        for c in "fix quoting".chars() {
+            assert_eq!(state.handle_key(key(KeyCode::Char(c))), None);
+        }
+        // `q` is text while typing, not quit.
+        assert_eq!(state.stage, Stage::Intent);
+        assert_eq!(state.intent, "fix quoting");
+        assert_eq!(
+            state.handle_key(key(KeyCode::Enter)),
+            Some(Action::SubmitIntent("fix quoting".to_string()))
+        );
+        assert_eq!(state.stage, Stage::Planning);
+    }
+
+    #[test]
+    fn review_requires_approvable_patch() {
+        let mut state = AppState::new(None);
+        state.apply_event(WorkerEvent::Patched {
+            patch: "*** Begin Patch\n*** End Patch".to_string(),
+            review: review(PolicyDecision::Deny(reason())),
+        });
+        assert_eq!(state.stage, Stage::Review);
+        assert_eq!(state.handle_key(key(KeyCode::Char('y'))), None);
+        assert_eq!(state.stage, Stage::Review);
+
+        state.apply_event(WorkerEvent::Patched {
+            patch: String::new(),
+            review: review(PolicyDecision::NeedsApproval(reason())),
+        });
+        assert_eq!(
+            state.handle_key(key(KeyCode::Char('y'))),
+            Some(Action::Approve)
+        );
+        assert_eq!(state.stage, Stage::Verifying);
+
+        state.apply_event(WorkerEvent::Finished { success: true });
+        assert_eq!(state.stage, Stage::Finished { success: true });
+        state.handle_key(key(KeyCode::Enter));
+        assert_eq!(state.stage, Stage::Intent);
+    }
+
+    #[test]
+    fn diff_scroll_is_clamped() {
+        let mut state = AppState::new(None);
+        state.patch = "a\nb\nc".to_string();
+        state.stage = Stage::Review;
+        state.focus = Pane::Diff;
+
+        state.handle_key(key(KeyCode::PageDown));
+        assert_eq!(state.diff_scroll, 2);
+        state.handle_key(key(KeyCode::Up));
+        state.handle_key(key(KeyCode::PageUp));
+        assert_eq!(state.diff_scroll, 0);
+    }
+}
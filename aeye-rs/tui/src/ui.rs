@@ -1,4 +1,303 @@
-pub mod ui {
-    // Placeholder for UI components
-    // Will be expanded as needed
+//! Rendering for the multi-pane layout. Everything here is a pure function of
+//! [`AppState`], so it can be drawn into a test backend.
+
+use crate::state::{AppState, Pane, Stage};
+use crate::workflow::{LogLine, Review};
+use aeye_core::PolicyDecision;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Wrap};
+
+/// Colours are dropped entirely when `pretty` is false (`--nopretty`).
+pub fn render(frame: &mut Frame, state: &AppState, pretty: bool) {
+    let theme = Theme { pretty };
+    let approval_widget = state
+        .review
+        .as_ref()
+        .filter(|_| state.stage == Stage::Review)
+        .map(|review| approval_pane(review, theme));
+    // Tall enough to list every verification command the approval covers.
+    let approval_height = approval_widget.as_ref().map_or(0, |(_, lines)| {
+        u16::try_from(lines + 2).unwrap_or(u16::MAX).clamp(7, 14)
+    });
+    let [header, body, approval, log, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(6),
+        Constraint::Length(approval_height),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [left, diff] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(body);
+    let [intent, plan] = Layout::vertical([Constraint::Length(3), Constraint::Min(3)]).areas(left);
+
+    frame.render_widget(header_line(state, theme), header);
+    frame.render_widget(intent_pane(state, theme), intent);
+    frame.render_widget(plan_pane(state, theme), plan);
+    frame.render_widget(diff_pane(state, theme), diff);
+    if let Some((pane, _)) = approval_widget {
+        frame.render_widget(pane, approval);
+    }
+    frame.render_widget(log_pane(state, theme, log.height), log);
+    frame.render_widget(footer_line(state), footer);
+}
+
+#[derive(Clone, Copy)]
+struct Theme {
+    pretty: bool,
+}
+
+impl Theme {
+    fn style(self, style: Style) -> Style {
+        if self.pretty { style } else { Style::default() }
+    }
+
+    fn block(self, title: &str, focused: bool) -> Block<'static> {
+        let block = Block::bordered().title(format!(" {title} "));
+        if focused {
+            block.border_style(self.style(Style::new().cyan().bold()))
+        } else {
+            block
+        }
+    }
+
+    fn decision(self, decision: &PolicyDecision) -> Span<'static> {
+        let (label, style) = match decision {
+            PolicyDecision::Allow(_) => ("allow", Style::new().green()),
+            PolicyDecision::NeedsApproval(_) => ("approve", Style::new().yellow()),
+            PolicyDecision::Deny(_) => ("deny", Style::new().red().bold()),
+        };
+        Span::styled(format!("[{label}]"), self.style(style))
+    }
+}
+
+fn header_line(state: &AppState, theme: Theme) -> Paragraph<'static> {
+    let stage = match &state.stage {
+        Stage::Intent => "describe your change".to_string(),
+        Stage::Planning => "planning…".to_string(),
+        Stage::Review => "review the patch".to_string(),
+        Stage::Verifying => "applying and verifying…".to_string(),
+        Stage::Finished { success: true } => "done".to_string(),
+        Stage::Finished { success: false } => "verification failed".to_string(),
+        Stage::Failed(_) => "failed".to_string(),
+    };
+    let mut spans = vec![
+        Span::styled("A-Eye", theme.style(Style::new().bold())),
+        Span::raw(format!(" · {stage}")),
+    ];
+    if let Some(model) = &state.model {
+        spans.push(Span::styled(
+            format!(" · {model}"),
+            theme.style(Style::new().dim()),
+        ));
+    }
+    Paragraph::new(Line::from(spans))
+}
+
+fn intent_pane(state: &AppState, theme: Theme) -> Paragraph<'static> {
+    let editing = state.stage == Stage::Intent && state.focus == Pane::Intent;
+    let cursor = if editing { "▏" } else { "" };
+    Paragraph::new(format!("{}{cursor}", state.intent))
+        .block(theme.block("Intent", state.focus == Pane::Intent))
+}
+
+fn plan_pane(state: &AppState, theme: Theme) -> List<'static> {
+    let items: Vec<ListItem> = if state.plan.is_empty() && state.stage == Stage::Planning {
+        vec![ListItem::new("Planning…")]
+    } else {
+        state
+            .plan
+            .iter()
+            .enumerate()
+            .map(|(index, step)| ListItem::new(format!("{}. {step}", index + 1)))
+            .collect()
+    };
+    List::new(items).block(theme.block("Plan", state.focus == Pane::Plan))
+}
+
+fn diff_pane(state: &AppState, theme: Theme) -> Paragraph<'static> {
+    let lines: Vec<Line> = state
+        .patch
+        .lines()
+        .map(|line| {
+            let style = if line.starts_with("***") {
+                Style::new().bold()
+            } else if line.starts_with("@@") {
+                Style::new().cyan()
+            } else if line.starts_with('+') {
+                Style::new().green()
+            } else if line.starts_with('-') {
+                Style::new().red()
+            } else {
+                Style::new()
+            };
+            Line::styled(line.to_string(), theme.style(style))
+        })
+        .collect();
+    Paragraph::new(lines)
+        .block(theme.block("Patch", state.focus == Pane::Diff))
+        .scroll((state.diff_scroll, 0))
+}
+
+/// The approval pane and the number of lines in it.
+fn approval_pane(review: &Review, theme: Theme) -> (Paragraph<'static>, usize) {
+    let mut lines: Vec<Line> = review
+        .report
+        .files
+        .iter()
+        .filter(|file| !file.decision.is_allowed())
+        .map(|file| {
+            Line::from(vec![
+                theme.decision(&file.decision),
+                Span::raw(format!(" {}", file.decision.reason().justification)),
+            ])
+        })
+        .collect();
+    if !review.apply.is_allowed() {
+        lines.push(Line::from(vec![
+            theme.decision(&review.apply),
+            Span::raw(format!(" {}", review.apply.reason().justification)),
+        ]));
+    }
+    if !review.verify.is_allowed() && !review.commands.is_empty() {
+        lines.push(Line::from(vec![
+            theme.decision(&review.verify),
+            Span::raw(format!(" {}", review.verify.reason().justification)),
+        ]));
+    }
+    for (command, decision) in &review.commands {
+        let decision = if review.verify.is_denied() {
+            &review.verify
+        } else {
+            decision
+        };
+        lines.push(Line::from(vec![
+            theme.decision(decision),
+            Span::raw(format!(" verify: {command}")),
+        ]));
+    }
+
+    let prompt = if review.can_approve() {
+        Line::styled(
+            format!(
+                "Apply changes to {} file(s) and run {} verification command(s)? [y] approve  [n] reject",
+                review.report.files.len(),
+                review.commands_to_run().count()
+            ),
+            theme.style(Style::new().yellow().bold()),
+        )
+    } else {
+        Line::styled(
+            "Blocked by policy. [n] reject",
+            theme.style(Style::new().red().bold()),
+        )
+    };
+    lines.insert(0, prompt);
+    let len = lines.len();
+    let pane = Paragraph::new(lines)
+        .wrap(Wrap { trim: true })
+        .block(theme.block("Approval", true));
+    (pane, len)
+}
+
+fn log_pane(state: &AppState, theme: Theme, height: u16) -> Paragraph<'static> {
+    let lines: Vec<Line> = state
+        .log
+        .iter()
+        .map(|line| match line {
+            LogLine::Info(text) => Line::styled(text.clone(), theme.style(Style::new().dim())),
+            LogLine::Command(command) => {
+                Line::styled(format!("$ {command}"), theme.style(Style::new().bold()))
+            }
+            LogLine::Stdout(text) => Line::raw(text.clone()),
+            LogLine::Stderr(text) => Line::styled(text.clone(), theme.style(Style::new().yellow())),
+            LogLine::Error(text) => Line::styled(text.clone(), theme.style(Style::new().red())),
+        })
+        .collect();
+    // Keep the newest output in view unless the user has scrolled up.
+    let visible = usize::from(height.saturating_sub(2));
+    let bottom = lines.len().saturating_sub(visible);
+    let offset = bottom.saturating_sub(usize::from(state.log_scroll));
+    Paragraph::new(lines)
+        .block(theme.block("Verification log", state.focus == Pane::Log))
+        .scroll((u16::try_from(offset).unwrap_or(u16::MAX), 0))
+}
+
+fn footer_line(state: &AppState) -> Paragraph<'static> {
+    let help = match state.stage {
+        Stage::Intent => "enter submit · tab switch pane · esc quit",
+        Stage::Review => "y approve · n reject · ↑↓ scroll · tab switch pane · q quit",
+        Stage::Finished { .. } | Stage::Failed(_) => "enter new intent · q quit",
+        Stage::Planning | Stage::Verifying => "tab switch pane · ↑↓ scroll · q quit",
+    };
+    Paragraph::new(help)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow::WorkerEvent;
+    use aeye_core::PolicyReason;
+    use aeye_core::PolicyRule;
+    use aeye_core::patch_policy::PatchPolicyReport;
+    use ratatui::backend::TestBackend;
+
+    fn rendered(state: &AppState) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|frame| render(frame, state, true)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(usize::from(buffer.area.width))
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn renders_review_with_approval_prompt() {
+        let mut state = AppState::new(Some("gpt-oss".to_string()));
+        state.intent = "add notes".to_string();
+        state.apply_event(WorkerEvent::Planned(vec!["Add notes.txt".to_string()]));
+        state.apply_event(WorkerEvent::Patched {
+            patch: "*** Begin Patch\n*** Add File: notes.txt\n+hello\n*** End Patch".to_string(),
+            review: Box::new(Review {
+                report: PatchPolicyReport { files: Vec::new() },
+                apply: PolicyDecision::NeedsApproval(PolicyReason {
+                    rule: PolicyRule::Default,
+                    source: None,
+                    justification: "`tools.apply` is supervised".to_string(),
+                }),
+                verify: PolicyDecision::NeedsApproval(PolicyReason {
+                    rule: PolicyRule::Default,
+                    source: None,
+                    justification: "`tools.verify` is supervised".to_string(),
+                }),
+                commands: vec![(
+                    "cargo test".to_string(),
+                    PolicyDecision::NeedsApproval(PolicyReason {
+                        rule: PolicyRule::Default,
+                        source: None,
+                        justification: "no rule allows `cargo`".to_string(),
+                    }),
+                )],
+            }),
+        });
+
+        let screen = rendered(&state);
+        for expected in [
+            "A-Eye · review the patch · gpt-oss",
+            "1. Add notes.txt",
+            "+hello",
+            "Apply changes to 0 file(s) and run 1 verification command(s)?",
+            "[approve] `tools.verify` is supervised",
+            "[approve] verify: cargo test",
+            "[approve] `tools.apply` is supervised",
+        ] {
+            assert!(
+                screen.contains(expected),
+                "missing {expected:?} in\n{screen}"
+            );
+        }
+    }
 }
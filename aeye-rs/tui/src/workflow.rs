@@ -0,0 +1,461 @@
+//! The plan → patch → verify pipeline behind the TUI. Each step runs on a
+//! worker thread and reports back through [`WorkerEvent`]s so the UI stays
+//! responsive while commands stream output.
+
+use aeye_apply_patch::{Hunk, parse_patch, resolve_hunk};
+use aeye_core::PolicyDecision;
+use aeye_core::PolicyEngine;
+use aeye_core::patch_policy::PatchPolicyReport;
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+/// Produces plans, patches and verification commands. Implementations run
+/// on a worker thread, so they may block.
+pub trait Workflow: Send + 'static {
+    fn plan(&mut self, intent: &str) -> Result<Vec<String>>;
+
+    /// Returns the patch in `apply_patch` format.
+    fn patch(&mut self, intent: &str, plan: &[String]) -> Result<String>;
+
+    fn verify_commands(&mut self) -> Result<Vec<String>>;
+}
+
+/// Works from files on disk: the patch passed with `--patch`, and verify
+/// commands from the `.nlpg/system.json` written by `a-eye scan`.
+pub struct LocalWorkflow {
+    repo_root: PathBuf,
+    patch_path: Option<PathBuf>,
+}
+
+impl LocalWorkflow {
+    pub fn new(repo_root: PathBuf, patch_path: Option<PathBuf>) -> Self {
+        Self {
+            repo_root,
+            patch_path,
+        }
+    }
+
+    fn read_patch(&self) -> Result<String> {
+        let Some(patch_path) = &self.patch_path else {
+            anyhow::bail!(
+                "no planner is configured; start aeye-tui with --patch <FILE> to review an existing patch"
+            );
+        };
+        let patch_path = self.repo_root.join(patch_path);
+        std::fs::read_to_string(&patch_path)
+            .with_context(|| format!("failed to read patch {}", patch_path.display()))
+    }
+}
+
+impl Workflow for LocalWorkflow {
+    fn plan(&mut self, _intent: &str) -> Result<Vec<String>> {
+        let hunks = parse_patch(&self.read_patch()?)?.hunks;
+        Ok(hunks.iter().map(describe_hunk).collect())
+    }
+
+    fn patch(&mut self, _intent: &str, _plan: &[String]) -> Result<String> {
+        self.read_patch()
+    }
+
+    fn verify_commands(&mut self) -> Result<Vec<String>> {
+        let profile_path = self.repo_root.join(".nlpg/system.json");
+        let contents = match std::fs::read_to_string(&profile_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("failed to read {}", profile_path.display()));
+            }
+        };
+        let profile: serde_json::Value = serde_json::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", profile_path.display()))?;
+        Ok(profile
+            .get("verify_commands")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|command| command.as_str().map(str::to_string))
+            .collect())
+    }
+}
+
+fn describe_hunk(hunk: &Hunk) -> String {
+    match hunk {
+        Hunk::AddFile { path, .. } => format!("Add {}", path.display()),
+        Hunk::DeleteFile { path } => format!("Delete {}", path.display()),
+        Hunk::UpdateFile {
+            path,
+            move_path: Some(move_path),
+            ..
+        } => format!("Move {} to {}", path.display(), move_path.display()),
+        Hunk::UpdateFile { path, .. } => format!("Update {}", path.display()),
+        Hunk::AddBinaryFile { path, .. } => format!("Add {} (binary)", path.display()),
+        Hunk::SetExecutable {
+            path,
+            executable: true,
+        } => format!("Make {} executable", path.display()),
+        Hunk::SetExecutable {
+            path,
+            executable: false,
+        } => format!("Make {} non-executable", path.display()),
+        Hunk::AddSymlink { path, target } => {
+            format!("Link {} to {}", path.display(), target.display())
+        }
+    }
+}
+
+/// Everything the approval prompt needs: per-file verdicts for the patch,
+/// the tier gates for applying and verifying, and the verdict for each
+/// verification command. Approving the patch also approves every command
+/// that will run, so the prompt lists them all.
+#[derive(Debug, Clone)]
+pub struct Review {
+    pub report: PatchPolicyReport,
+    pub apply: PolicyDecision,
+    pub verify: PolicyDecision,
+    pub commands: Vec<(String, PolicyDecision)>,
+}
+
+impl Review {
+    pub fn can_approve(&self) -> bool {
+        !self.report.is_denied() && !self.apply.is_denied()
+    }
+
+    /// The verification commands approving this review will run.
+    pub fn commands_to_run(&self) -> impl Iterator<Item = &str> {
+        let verify_allowed = !self.verify.is_denied();
+        self.commands
+            .iter()
+            .filter(move |(_, decision)| verify_allowed && !decision.is_denied())
+            .map(|(command, _)| command.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogLine {
+    Info(String),
+    Command(String),
+    Stdout(String),
+    Stderr(String),
+    Error(String),
+}
+
+#[derive(Debug)]
+pub enum WorkerEvent {
+    Planned(Vec<String>),
+    Patched { patch: String, review: Box<Review> },
+    Log(LogLine),
+    Finished { success: bool },
+    Failed(String),
+}
+
+/// Runs workflow steps off the UI thread.
+#[derive(Clone)]
+pub struct Worker {
+    workflow: Arc<Mutex<Box<dyn Workflow>>>,
+    policy: Arc<PolicyEngine>,
+    repo_root: PathBuf,
+    events: Sender<WorkerEvent>,
+}
+
+impl Worker {
+    pub fn new(
+        workflow: Box<dyn Workflow>,
+        policy: PolicyEngine,
+        repo_root: PathBuf,
+        events: Sender<WorkerEvent>,
+    ) -> Self {
+        Self {
+            workflow: Arc::new(Mutex::new(workflow)),
+            policy: Arc::new(policy),
+            repo_root,
+            events,
+        }
+    }
+
+    /// Plans and generates a patch for `intent`, then reviews it against
+    /// the policy.
+    pub fn prepare(&self, intent: String) {
+        let worker = self.clone();
+        std::thread::spawn(move || {
+            let event = match worker.try_prepare(&intent) {
+                Ok((patch, review)) => WorkerEvent::Patched {
+                    patch,
+                    review: Box::new(review),
+                },
+                Err(err) => WorkerEvent::Failed(format!("{err:#}")),
+            };
+            worker.send(event);
+        });
+    }
+
+    /// Applies an approved patch and runs the verification commands.
+    pub fn apply_and_verify(&self, patch: String, review: Review) {
+        let worker = self.clone();
+        std::thread::spawn(move || {
+            let success = match worker.try_apply_and_verify(&patch, &review) {
+                Ok(success) => success,
+                Err(err) => {
+                    worker.send(WorkerEvent::Log(LogLine::Error(format!("{err:#}"))));
+                    false
+                }
+            };
+            worker.send(WorkerEvent::Finished { success });
+        });
+    }
+
+    fn try_prepare(&self, intent: &str) -> Result<(String, Review)> {
+        let mut workflow = self
+            .workflow
+            .lock()
+            .map_err(|_| anyhow::anyhow!("workflow panicked in an earlier step"))?;
+        let plan = workflow.plan(intent)?;
+        self.send(WorkerEvent::Planned(plan.clone()));
+        let patch = workflow.patch(intent, &plan)?;
+        let commands = workflow.verify_commands()?;
+        drop(workflow);
+
+        let hunks = parse_patch(&patch)?.hunks;
+        let review = Review {
+            report: self.policy.check_hunks(&hunks, &self.repo_root),
+            apply: self.policy.check_action("tools.apply"),
+            verify: self.policy.check_action("tools.verify"),
+            commands: commands
+                .into_iter()
+                .map(|command| {
+                    let decision = self.policy.check_shell(&command);
+                    (command, decision)
+                })
+                .collect(),
+        };
+        Ok((patch, review))
+    }
+
+    fn try_apply_and_verify(&self, patch: &str, review: &Review) -> Result<bool> {
+        self.log(LogLine::Info("Applying patch".to_string()));
+        for line in apply_patch_in(patch, &self.repo_root)? {
+            self.log(LogLine::Stdout(line));
+        }
+
+        // A patch that cannot be verified has not been verified: report it
+        // as a failure rather than a success.
+        if review.verify.is_denied() && !review.commands.is_empty() {
+            self.log(LogLine::Error(format!(
+                "Verification not run: {}",
+                review.verify
+            )));
+            return Ok(false);
+        }
+
+        let mut success = true;
+        for (command, decision) in &review.commands {
+            if decision.is_denied() {
+                self.log(LogLine::Error(format!("Skipped `{command}`: {decision}")));
+                success = false;
+                continue;
+            }
+            self.log(LogLine::Command(command.clone()));
+            if !self.run_streaming(command)? {
+                success = false;
+                break;
+            }
+        }
+        Ok(success)
+    }
+
+    /// Runs `command` through `sh -c`, forwarding each output line as it
+    /// arrives. Returns whether the command exited successfully.
+    fn run_streaming(&self, command: &str) -> Result<bool> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(&self.repo_root)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run `{command}`"))?;
+
+        let stderr = child.stderr.take().map(|stderr| {
+            let worker = self.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                    worker.log(LogLine::Stderr(line));
+                }
+            })
+        });
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                self.log(LogLine::Stdout(line));
+            }
+        }
+        if let Some(stderr) = stderr {
+            let _ = stderr.join();
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            self.log(LogLine::Error(format!("`{command}` failed with {status}")));
+        }
+        Ok(status.success())
+    }
+
+    fn log(&self, line: LogLine) {
+        self.send(WorkerEvent::Log(line));
+    }
+
+    fn send(&self, event: WorkerEvent) {
+        // The receiver only goes away when the UI is shutting down.
+        let _ = self.events.send(event);
+    }
+}
+
+/// Applies `patch` with its paths resolved against `cwd` and returns the
+/// summary lines `apply_patch` prints.
+fn apply_patch_in(patch: &str, cwd: &Path) -> Result<Vec<String>> {
+    let hunks: Vec<Hunk> = parse_patch(patch)?
+        .hunks
+        .into_iter()
+        .map(|hunk| resolve_hunk(hunk, cwd))
+        .collect();
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    aeye_apply_patch::apply_hunks(&hunks, &mut stdout, &mut stderr).map_err(|err| {
+        anyhow::anyhow!("{}", String::from_utf8_lossy(&stderr).trim()).context(err)
+    })?;
+    Ok(String::from_utf8_lossy(&stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aeye_core::AEyeConfig;
+    use pretty_assertions::assert_eq;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    const PATCH: &str = "*** Begin Patch\n*** Add File: notes.txt\n+hello\n*** End Patch";
+
+    /// A worker running the local workflow in a fresh repo whose patch adds
+    /// `notes.txt` and whose verification step prints it through `sh`.
+    #[cfg(unix)]
+    struct Harness {
+        _dir: tempfile::TempDir,
+        repo_root: PathBuf,
+        worker: Worker,
+        rx: mpsc::Receiver<WorkerEvent>,
+    }
+
+    #[cfg(unix)]
+    impl Harness {
+        fn new(config: AEyeConfig) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let repo_root = dir.path().to_path_buf();
+            std::fs::create_dir_all(repo_root.join(".nlpg")).unwrap();
+            std::fs::write(repo_root.join("change.patch"), PATCH).unwrap();
+            std::fs::write(
+                repo_root.join(".nlpg/system.json"),
+                r#"{"verify_commands": ["cat notes.txt"]}"#,
+            )
+            .unwrap();
+            let policy = PolicyEngine::new(config, Some(repo_root.clone())).unwrap();
+            let workflow = LocalWorkflow::new(repo_root.clone(), Some("change.patch".into()));
+            let (tx, rx) = mpsc::channel();
+            let worker = Worker::new(Box::new(workflow), policy, repo_root.clone(), tx);
+            Self {
+                _dir: dir,
+                repo_root,
+                worker,
+                rx,
+            }
+        }
+
+        fn recv(&self) -> WorkerEvent {
+            self.rx.recv_timeout(Duration::from_secs(10)).unwrap()
+        }
+
+        /// Prepares the change and returns its plan, patch and review.
+        fn prepare(&self) -> (Vec<String>, String, Review) {
+            self.worker.prepare("add notes".to_string());
+            let WorkerEvent::Planned(plan) = self.recv() else {
+                panic!("expected a plan");
+            };
+            let WorkerEvent::Patched { patch, review } = self.recv() else {
+                panic!("expected a patch");
+            };
+            (plan, patch, *review)
+        }
+
+        /// Applies and verifies the patch, returning whether the run
+        /// succeeded and the lines it logged.
+        fn apply_and_verify(&self, patch: String, review: Review) -> (bool, Vec<LogLine>) {
+            self.worker.apply_and_verify(patch, review);
+            let mut log = Vec::new();
+            loop {
+                match self.recv() {
+                    WorkerEvent::Log(line) => log.push(line),
+                    WorkerEvent::Finished { success } => return (success, log),
+                    other => panic!("unexpected event {other:?}"),
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn local_workflow_prepares_and_applies_patch() {
+        let harness = Harness::new(AEyeConfig {
+            default_tier: 2,
+            ..AEyeConfig::default()
+        });
+
+        let (plan, patch, review) = harness.prepare();
+        assert_eq!(plan, vec!["Add notes.txt".to_string()]);
+        assert!(review.can_approve());
+        assert_eq!(review.commands.len(), 1);
+
+        let (success, log) = harness.apply_and_verify(patch, review);
+        assert!(success);
+        assert_eq!(
+            std::fs::read_to_string(harness.repo_root.join("notes.txt")).unwrap(),
+            "hello\n"
+        );
+        assert!(log.contains(&LogLine::Command("cat notes.txt".to_string())));
+        assert!(log.contains(&LogLine::Stdout("hello".to_string())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn denied_verification_fails_the_run() {
+        let harness = Harness::new(AEyeConfig {
+            default_tier: 2,
+            action_tiers: [("tools.verify".to_string(), 3)].into_iter().collect(),
+            ..AEyeConfig::default()
+        });
+
+        let (_, patch, review) = harness.prepare();
+        assert!(review.verify.is_denied());
+        assert_eq!(review.commands_to_run().count(), 0);
+
+        let (success, log) = harness.apply_and_verify(patch, review);
+        assert!(!success);
+        assert!(!log.contains(&LogLine::Command("cat notes.txt".to_string())));
+    }
+
+    #[test]
+    fn local_workflow_without_patch_explains_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut workflow = LocalWorkflow::new(dir.path().to_path_buf(), None);
+
+        let err = workflow.plan("anything").unwrap_err();
+        assert!(err.to_string().contains("--patch"));
+    }
+}
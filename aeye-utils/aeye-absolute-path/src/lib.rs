@@ -6,11 +6,17 @@ use serde::Deserializer;
 use serde::Serialize;
 use serde::de::Error as SerdeError;
 use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::Component;
 use std::path::Display;
 use std::path::Path;
 use std::path::PathBuf;
 use ts_rs::TS;
 
+/// Symlinks followed before [`AbsolutePathBuf::resolve_symlinks_against_base`]
+/// gives up, matching Linux's `MAXSYMLINKS`.
+const MAX_SYMLINK_HOPS: usize = 40;
+
 /// A path that is guaranteed to be absolute and normalized (though it is not
 /// guaranteed to be canonicalized or exist on the filesystem).
 ///
@@ -52,6 +58,69 @@ impl AbsolutePathBuf {
         Ok(Self(absolute_path.into_owned()))
     }
 
+    /// Resolves `path` against `base_path` the way the OS would when opening
+    /// it: every symlink along the way is followed before the next component
+    /// (including `..`) is applied. Components that do not exist yet, such as
+    /// a file about to be created, are appended as-is, so the result is the
+    /// real location a write to `path` would land in.
+    pub fn resolve_symlinks_against_base<P: AsRef<Path>, B: AsRef<Path>>(
+        path: P,
+        base_path: B,
+    ) -> std::io::Result<Self> {
+        let expanded = Self::maybe_expand_home_directory(path.as_ref());
+        let full_path = Self::from_absolute_path(base_path)?.0.join(expanded);
+        let mut pending: VecDeque<PathBuf> = full_path
+            .components()
+            .map(|component| PathBuf::from(component.as_os_str()))
+            .collect();
+        let mut resolved = PathBuf::new();
+        let mut hops = 0;
+
+        while let Some(next) = pending.pop_front() {
+            match next.components().next() {
+                Some(Component::Normal(_)) => resolved.push(&next),
+                Some(Component::Prefix(_) | Component::RootDir) => {
+                    resolved.push(&next);
+                    continue;
+                }
+                Some(Component::ParentDir) => {
+                    resolved.pop();
+                    continue;
+                }
+                Some(Component::CurDir) | None => continue,
+            }
+
+            let is_symlink = match std::fs::symlink_metadata(&resolved) {
+                Ok(metadata) => metadata.file_type().is_symlink(),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+                Err(err) => return Err(err),
+            };
+            if !is_symlink {
+                continue;
+            }
+
+            hops += 1;
+            if hops > MAX_SYMLINK_HOPS {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "too many levels of symbolic links in {}",
+                        full_path.display()
+                    ),
+                ));
+            }
+            let target = std::fs::read_link(&resolved)?;
+            resolved.pop();
+            // An absolute target starts with a root component, which resets
+            // `resolved` when it is pushed.
+            for component in target.components().rev() {
+                pending.push_front(PathBuf::from(component.as_os_str()));
+            }
+        }
+
+        Ok(Self(resolved))
+    }
+
     pub fn from_absolute_path<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
         let expanded = Self::maybe_expand_home_directory(path.as_ref());
         let absolute_path = expanded.absolutize()?;
@@ -213,6 +282,44 @@ mod tests {
         assert_eq!(abs_path_buf.as_path(), base_dir.join("file.txt").as_path());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn resolve_symlinks_follows_links_before_parent_dirs() {
+        let temp_dir = tempdir().expect("temp dir");
+        let root = temp_dir.path().canonicalize().expect("canonical temp dir");
+        let repo = root.join("repo");
+        let outside = root.join("outside/nested");
+        std::fs::create_dir_all(&repo).expect("create repo");
+        std::fs::create_dir_all(&outside).expect("create outside");
+        std::os::unix::fs::symlink(&outside, repo.join("link")).expect("create symlink");
+
+        let resolved = AbsolutePathBuf::resolve_symlinks_against_base("link/new/file.txt", &repo)
+            .expect("resolve");
+        assert_eq!(resolved.as_path(), outside.join("new/file.txt").as_path());
+
+        // `..` applies to the link target, not to `repo`.
+        let resolved = AbsolutePathBuf::resolve_symlinks_against_base("link/../file.txt", &repo)
+            .expect("resolve");
+        assert_eq!(resolved.as_path(), root.join("outside/file.txt").as_path());
+
+        let resolved =
+            AbsolutePathBuf::resolve_symlinks_against_base("a/../b.txt", &repo).expect("resolve");
+        assert_eq!(resolved.as_path(), repo.join("b.txt").as_path());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_symlinks_rejects_loops() {
+        let temp_dir = tempdir().expect("temp dir");
+        let base_dir = temp_dir.path();
+        std::os::unix::fs::symlink(base_dir.join("b"), base_dir.join("a")).expect("symlink a");
+        std::os::unix::fs::symlink(base_dir.join("a"), base_dir.join("b")).expect("symlink b");
+
+        let err = AbsolutePathBuf::resolve_symlinks_against_base("a/file.txt", base_dir)
+            .expect_err("loop should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn guard_used_in_deserialization() {
         let temp_dir = tempdir().expect("base dir");
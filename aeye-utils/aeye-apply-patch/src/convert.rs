@@ -0,0 +1,812 @@
+//! Conversion between the `*** Begin Patch` format and git-style unified
+//! diffs, so that a patch in either format can be applied by either tool:
+//! `apply_patch` via [`hunks_from_unified_diff`], `git apply` via
+//! [`unified_diff_from_hunks`].
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use similar::Algorithm;
+use similar::ChangeTag;
+
+use crate::ApplyPatchError;
+use crate::ApplyPatchOptions;
+use crate::Hunk;
+use crate::ParseError;
+use crate::PreviewKind;
+use crate::parser::UpdateFileChunk;
+use crate::preview_hunks;
+
+/// Lines of context around each change, matching `git diff`.
+const GIT_CONTEXT_LINES: usize = 3;
+const REGULAR_FILE_MODE: &str = "100644";
+const EXECUTABLE_FILE_MODE: &str = "100755";
+const SYMLINK_MODE: &str = "120000";
+const DEV_NULL: &str = "/dev/null";
+/// Characters of base64 per `+` line of an `*** Add Binary File:` hunk.
+const BASE64_LINE_WIDTH: usize = 76;
+
+/// Renders `hunks` as a diff that `git apply` accepts, with paths relative to
+/// `cwd`. Update hunks are resolved against the files in `cwd`, so this fails
+/// wherever applying the hunks would. Binary files are summarised the way
+/// `git diff` does without `--binary`, which `git apply` cannot apply.
+pub fn unified_diff_from_hunks(hunks: &[Hunk], cwd: &Path) -> Result<String, ApplyPatchError> {
+    let preview = preview_hunks(hunks, cwd, GIT_CONTEXT_LINES, ApplyPatchOptions::default())?;
+    let mut diff = String::new();
+    for file in &preview.files {
+        let old_path = git_path(&file.path, cwd);
+        let new_path = git_path(file.move_path.as_ref().unwrap_or(&file.path), cwd);
+        diff.push_str(&format!("diff --git a/{old_path} b/{new_path}\n"));
+        let (old_header, new_header) = match file.kind {
+            PreviewKind::Add => {
+                diff.push_str(&format!("new file mode {REGULAR_FILE_MODE}\n"));
+                (DEV_NULL.to_string(), format!("b/{new_path}"))
+            }
+            PreviewKind::AddBinary => {
+                diff.push_str(&format!("new file mode {REGULAR_FILE_MODE}\n"));
+                diff.push_str(&format!(
+                    "Binary files {DEV_NULL} and b/{new_path} differ\n"
+                ));
+                continue;
+            }
+            PreviewKind::AddSymlink => {
+                diff.push_str(&format!("new file mode {SYMLINK_MODE}\n"));
+                (DEV_NULL.to_string(), format!("b/{new_path}"))
+            }
+            PreviewKind::Delete if file.old_content.is_none() => {
+                diff.push_str(&format!("deleted file mode {REGULAR_FILE_MODE}\n"));
+                diff.push_str(&format!(
+                    "Binary files a/{old_path} and {DEV_NULL} differ\n"
+                ));
+                continue;
+            }
+            PreviewKind::Delete => {
+                diff.push_str(&format!("deleted file mode {REGULAR_FILE_MODE}\n"));
+                (format!("a/{old_path}"), DEV_NULL.to_string())
+            }
+            PreviewKind::SetExecutable { executable } => {
+                let (old_mode, new_mode) = if executable {
+                    (REGULAR_FILE_MODE, EXECUTABLE_FILE_MODE)
+                } else {
+                    (EXECUTABLE_FILE_MODE, REGULAR_FILE_MODE)
+                };
+                diff.push_str(&format!("old mode {old_mode}\nnew mode {new_mode}\n"));
+                continue;
+            }
+            PreviewKind::Update => {
+                if old_path != new_path {
+                    if file.unified_diff.is_empty() {
+                        diff.push_str("similarity index 100%\n");
+                    }
+                    diff.push_str(&format!("rename from {old_path}\nrename to {new_path}\n"));
+                }
+                (format!("a/{old_path}"), format!("b/{new_path}"))
+            }
+        };
+        // Like git, omit the file headers when there are no content changes.
+        if !file.unified_diff.is_empty() {
+            diff.push_str(&format!("--- {old_header}\n+++ {new_header}\n"));
+            diff.push_str(&file.unified_diff);
+        }
+    }
+    Ok(diff)
+}
+
+/// `path` relative to `cwd` with `/` separators, quoted the way git quotes
+/// paths containing special characters.
+fn git_path(path: &Path, cwd: &Path) -> String {
+    let relative = path.strip_prefix(cwd).unwrap_or(path);
+    let path = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if !path.contains(['"', '\\', '\n', '\t']) {
+        return path;
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Renders `hunks` in the `*** Begin Patch` format accepted by
+/// [`crate::parse_patch`].
+pub fn patch_from_hunks(hunks: &[Hunk]) -> String {
+    let mut patch = String::from("*** Begin Patch\n");
+    for hunk in hunks {
+        match hunk {
+            Hunk::AddFile { path, contents } => {
+                patch.push_str(&format!("*** Add File: {}\n", path.display()));
+                for line in contents.lines() {
+                    patch.push_str(&format!("+{line}\n"));
+                }
+            }
+            Hunk::DeleteFile { path } => {
+                patch.push_str(&format!("*** Delete File: {}\n", path.display()));
+            }
+            Hunk::AddBinaryFile { path, contents } => {
+                patch.push_str(&format!("*** Add Binary File: {}\n", path.display()));
+                let encoded = BASE64_STANDARD.encode(contents);
+                // Base64 is ASCII, so every split falls on a char boundary.
+                for line in encoded.as_bytes().chunks(BASE64_LINE_WIDTH) {
+                    patch.push('+');
+                    patch.push_str(&String::from_utf8_lossy(line));
+                    patch.push('\n');
+                }
+            }
+            Hunk::SetExecutable { path, executable } => {
+                let marker = if *executable { "Set" } else { "Clear" };
+                patch.push_str(&format!("*** {marker} Executable: {}\n", path.display()));
+            }
+            Hunk::AddSymlink { path, target } => {
+                patch.push_str(&format!(
+                    "*** Add Symlink: {}\n+{}\n",
+                    path.display(),
+                    target.display()
+                ));
+            }
+            Hunk::UpdateFile {
+                path,
+                move_path,
+                chunks,
+            } => {
+                patch.push_str(&format!("*** Update File: {}\n", path.display()));
+                if let Some(move_path) = move_path {
+                    patch.push_str(&format!("*** Move to: {}\n", move_path.display()));
+                }
+                for chunk in chunks {
+                    match &chunk.change_context {
+                        Some(context) => patch.push_str(&format!("@@ {context}\n")),
+                        None => patch.push_str("@@\n"),
+                    }
+                    let ops = similar::capture_diff_slices(
+                        Algorithm::Myers,
+                        &chunk.old_lines,
+                        &chunk.new_lines,
+                    );
+                    for change in ops
+                        .iter()
+                        .flat_map(|op| op.iter_changes(&chunk.old_lines, &chunk.new_lines))
+                    {
+                        let sign = match change.tag() {
+                            ChangeTag::Equal => ' ',
+                            ChangeTag::Delete => '-',
+                            ChangeTag::Insert => '+',
+                        };
+                        patch.push_str(&format!("{sign}{}\n", change.value()));
+                    }
+                    if chunk.is_end_of_file {
+                        patch.push_str("*** End of File\n");
+                    }
+                }
+            }
+        }
+    }
+    patch.push_str("*** End Patch\n");
+    patch
+}
+
+/// Parses a unified diff, as produced by `git diff` or `diff -u`, into hunks
+/// that [`crate::apply_hunks`] can apply. Paths are returned as written in the
+/// diff, without the `a/` and `b/` prefixes.
+pub fn hunks_from_unified_diff(diff: &str) -> Result<Vec<Hunk>, ParseError> {
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut hunks = Vec::new();
+    let mut file: Option<FileDiff> = None;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let line_number = index + 1;
+        index += 1;
+
+        if let Some(rest) = line.strip_prefix("diff --git ") {
+            if let Some(done) = file.take() {
+                hunks.extend(done.into_hunks(line_number)?);
+            }
+            file = Some(FileDiff {
+                git_paths: parse_diff_git_paths(rest),
+                ..FileDiff::default()
+            });
+            continue;
+        }
+        if line.starts_with("--- ")
+            && lines
+                .get(index)
+                .is_some_and(|next| next.starts_with("+++ "))
+        {
+            // A `diff -u` section has no `diff --git` line; a git section has
+            // already been started by one.
+            let current = match file.take() {
+                Some(current) if current.old_path.is_none() && current.chunks.is_empty() => current,
+                previous => {
+                    if let Some(done) = previous {
+                        hunks.extend(done.into_hunks(line_number)?);
+                    }
+                    FileDiff::default()
+                }
+            };
+            file = Some(FileDiff {
+                old_path: Some(parse_header_path(&line[4..], "a/")),
+                new_path: Some(parse_header_path(&lines[index][4..], "b/")),
+                ..current
+            });
+            index += 1;
+            continue;
+        }
+
+        let Some(current) = file.as_mut() else {
+            // Anything before the first file, e.g. a commit message.
+            continue;
+        };
+        if line.starts_with("@@ ") {
+            let chunk;
+            (chunk, index) = parse_chunk(&lines, index - 1)?;
+            current.chunks.push(chunk);
+        } else if let Some(path) = line.strip_prefix("rename from ") {
+            current.rename_from = Some(unquote(path));
+        } else if let Some(path) = line.strip_prefix("rename to ") {
+            current.rename_to = Some(unquote(path));
+        } else if let Some(mode) = line.strip_prefix("new file mode ") {
+            current.is_new = true;
+            current.new_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            current.old_mode = Some(mode.to_string());
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            current.new_mode = Some(mode.to_string());
+        } else if line.starts_with("deleted file mode ") {
+            current.is_deleted = true;
+        } else if line.starts_with("copy from ") || line.starts_with("copy to ") {
+            return Err(ParseError::InvalidHunkError {
+                message: "copies are not supported".to_string(),
+                line_number,
+            });
+        } else if line.starts_with("GIT binary patch")
+            || (line.starts_with("Binary files ") && line.ends_with(" differ"))
+        {
+            return Err(ParseError::InvalidHunkError {
+                message: "binary diffs are not supported".to_string(),
+                line_number,
+            });
+        }
+        // Other extended headers (`index`, `similarity index`, ...)
+        // carry nothing an apply_patch hunk can express.
+    }
+    if let Some(done) = file {
+        hunks.extend(done.into_hunks(lines.len())?);
+    }
+    if hunks.is_empty() {
+        return Err(ParseError::InvalidPatchError(
+            "no file changes found in the diff".to_string(),
+        ));
+    }
+    Ok(hunks)
+}
+
+/// One file's section of a unified diff.
+#[derive(Debug, Default)]
+struct FileDiff {
+    /// The paths on the `diff --git` line, when they can be split unambiguously.
+    git_paths: Option<(String, String)>,
+    /// Paths from the `---`/`+++` headers; `None` inside means `/dev/null`.
+    old_path: Option<Option<String>>,
+    new_path: Option<Option<String>>,
+    rename_from: Option<String>,
+    rename_to: Option<String>,
+    /// Modes from `old mode`/`new mode`, or `new file mode` for `new_mode`.
+    old_mode: Option<String>,
+    new_mode: Option<String>,
+    is_new: bool,
+    is_deleted: bool,
+    chunks: Vec<DiffChunk>,
+}
+
+#[derive(Debug)]
+struct DiffChunk {
+    chunk: UpdateFileChunk,
+    /// The lines added by the chunk, with a trailing newline unless the diff
+    /// says there is none.
+    added: String,
+}
+
+impl FileDiff {
+    fn into_hunks(self, line_number: usize) -> Result<Vec<Hunk>, ParseError> {
+        let (git_old, git_new) = self.git_paths.unzip();
+        let old_path = self
+            .rename_from
+            .or_else(|| self.old_path.clone().flatten())
+            .or(git_old);
+        let new_path = self
+            .rename_to
+            .or_else(|| self.new_path.clone().flatten())
+            .or(git_new);
+        let is_new = self.is_new || matches!(self.old_path, Some(None));
+        let is_deleted = self.is_deleted || matches!(self.new_path, Some(None));
+        let missing_path = || ParseError::InvalidHunkError {
+            message: "could not determine the file path of a diff section".to_string(),
+            line_number,
+        };
+
+        if is_new {
+            let path = PathBuf::from(new_path.ok_or_else(missing_path)?);
+            let contents: String = self.chunks.into_iter().map(|chunk| chunk.added).collect();
+            return Ok(match self.new_mode.as_deref() {
+                Some(SYMLINK_MODE) => vec![Hunk::AddSymlink {
+                    path,
+                    target: PathBuf::from(contents),
+                }],
+                Some(EXECUTABLE_FILE_MODE) => vec![
+                    Hunk::AddFile {
+                        path: path.clone(),
+                        contents,
+                    },
+                    Hunk::SetExecutable {
+                        path,
+                        executable: true,
+                    },
+                ],
+                _ => vec![Hunk::AddFile { path, contents }],
+            });
+        }
+        let path = old_path.ok_or_else(missing_path)?;
+        if is_deleted {
+            return Ok(vec![Hunk::DeleteFile {
+                path: PathBuf::from(path),
+            }]);
+        }
+        let move_path = new_path.filter(|new_path| *new_path != path);
+        let final_path = PathBuf::from(move_path.as_ref().unwrap_or(&path));
+        let mut hunks = Vec::new();
+        if !self.chunks.is_empty() || move_path.is_some() {
+            hunks.push(Hunk::UpdateFile {
+                path: PathBuf::from(&path),
+                move_path: move_path.map(PathBuf::from),
+                chunks: self.chunks.into_iter().map(|chunk| chunk.chunk).collect(),
+            });
+        }
+        if let (Some(old_mode), Some(new_mode)) = (&self.old_mode, &self.new_mode) {
+            let executable = new_mode == EXECUTABLE_FILE_MODE;
+            if executable != (old_mode == EXECUTABLE_FILE_MODE) {
+                hunks.push(Hunk::SetExecutable {
+                    path: final_path,
+                    executable,
+                });
+            }
+        }
+        // Anything else, such as a mode change git tracks but apply_patch
+        // cannot express, has nothing to apply.
+        Ok(hunks)
+    }
+}
+
+/// Parses the `@@ -a,b +c,d @@` chunk starting at `lines[start]`, returning it
+/// and the index of the first line after it.
+fn parse_chunk(lines: &[&str], start: usize) -> Result<(DiffChunk, usize), ParseError> {
+    let invalid = |message: &str, index: usize| ParseError::InvalidHunkError {
+        message: message.to_string(),
+        line_number: index + 1,
+    };
+    let (old_start, mut old_remaining, mut new_remaining) =
+        parse_chunk_header(lines[start]).ok_or_else(|| invalid("invalid chunk header", start))?;
+
+    let mut old_lines = Vec::new();
+    let mut new_lines = Vec::new();
+    let mut added = String::new();
+    let mut is_end_of_file = false;
+    let mut last_sign = ' ';
+    let mut index = start + 1;
+    loop {
+        let line = lines.get(index);
+        if let Some(line) = line
+            && line.starts_with('\\')
+        {
+            // "\ No newline at end of file" refers to the line before it.
+            is_end_of_file = true;
+            if last_sign == '+' && added.ends_with('\n') {
+                added.pop();
+            }
+            index += 1;
+            continue;
+        }
+        if old_remaining == 0 && new_remaining == 0 {
+            break;
+        }
+        let Some(line) = line else {
+            return Err(invalid("chunk ends before its line counts are met", index));
+        };
+        // Some tools strip the single space from empty context lines.
+        let (sign, text) = match line.chars().next() {
+            Some(sign) => (sign, &line[sign.len_utf8()..]),
+            None => (' ', ""),
+        };
+        match sign {
+            ' ' if old_remaining > 0 && new_remaining > 0 => {
+                old_lines.push(text.to_string());
+                new_lines.push(text.to_string());
+                old_remaining -= 1;
+                new_remaining -= 1;
+            }
+            '-' if old_remaining > 0 => {
+                old_lines.push(text.to_string());
+                old_remaining -= 1;
+            }
+            '+' if new_remaining > 0 => {
+                new_lines.push(text.to_string());
+                added.push_str(text);
+                added.push('\n');
+                new_remaining -= 1;
+            }
+            _ => return Err(invalid("unexpected line in chunk", index)),
+        }
+        last_sign = sign;
+        index += 1;
+    }
+
+    Ok((
+        DiffChunk {
+            chunk: UpdateFileChunk {
+                change_context: None,
+                old_lines,
+                new_lines,
+                is_end_of_file,
+                old_start: Some(old_start),
+            },
+            added,
+        },
+        index,
+    ))
+}
+
+/// The 0-based old start index and the old and new line counts of a
+/// `@@ -a,b +c,d @@` header.
+fn parse_chunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    let ranges = line.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_count) = range(old.strip_prefix('-')?)?;
+    let (_, new_count) = range(new.strip_prefix('+')?)?;
+    // An empty range starts after line `a`; a non-empty one at line `a`.
+    let old_start = if old_count == 0 {
+        old_start
+    } else {
+        old_start.checked_sub(1)?
+    };
+    Some((old_start, old_count, new_count))
+}
+
+/// The path in a `---`/`+++` header, or `None` for `/dev/null`.
+fn parse_header_path(header: &str, prefix: &str) -> Option<String> {
+    // `diff -u` appends a tab and a timestamp.
+    let path = unquote(header.split('\t').next().unwrap_or(header));
+    if path == DEV_NULL {
+        return None;
+    }
+    Some(
+        path.strip_prefix(prefix)
+            .map(str::to_string)
+            .unwrap_or(path),
+    )
+}
+
+/// Splits `a/<path> b/<path>`. Only an unquoted, unrenamed path can be split
+/// unambiguously, which covers sections without `---`/`+++` headers such as an
+/// empty new file; renames carry `rename from`/`rename to` instead.
+fn parse_diff_git_paths(rest: &str) -> Option<(String, String)> {
+    let path = rest.strip_prefix("a/")?;
+    let len = path.len().checked_sub(3)? / 2;
+    let (old, new) = (path.get(..len)?, path.get(len..)?);
+    (new.strip_prefix(" b/") == Some(old)).then(|| (old.to_string(), old.to_string()))
+}
+
+/// Undoes git's C-style quoting of paths with special characters. Bytes
+/// outside ASCII are written as `\NNN` octal escapes of their UTF-8 encoding.
+fn unquote(path: &str) -> String {
+    let Some(inner) = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    else {
+        return path.to_string();
+    };
+    let mut out = Vec::with_capacity(inner.len());
+    let mut bytes = inner.bytes().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'a') => out.push(0x07),
+            Some(b'b') => out.push(0x08),
+            Some(b'f') => out.push(0x0c),
+            Some(b'n') => out.push(b'\n'),
+            Some(b'r') => out.push(b'\r'),
+            Some(b't') => out.push(b'\t'),
+            Some(b'v') => out.push(0x0b),
+            Some(digit @ b'0'..=b'7') => {
+                let mut value = u32::from(digit - b'0');
+                for _ in 0..2 {
+                    let Some(digit) = bytes.next_if(|next| (b'0'..=b'7').contains(next)) else {
+                        break;
+                    };
+                    value = value * 8 + u32::from(digit - b'0');
+                }
+                out.push(u8::try_from(value).unwrap_or(u8::MAX));
+            }
+            Some(other) => out.push(other),
+            None => out.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apply_hunks;
+    use crate::parse_patch;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const PATCH: &str = "*** Begin Patch
+*** Add File: docs/new.md
++# New
+*** Delete File: obsolete.txt
+*** Update File: src/lib.rs
+*** Move to: src/main.rs
+@@
+ fn main() {
+-    old();
++    new();
+ }
+*** End Patch
+";
+
+    #[test]
+    fn renders_git_diff_with_modes_and_renames() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("obsolete.txt"), "gone\n").unwrap();
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "fn main() {\n    old();\n}\n",
+        )
+        .unwrap();
+        let hunks = parse_patch(PATCH).unwrap().hunks;
+
+        let diff = unified_diff_from_hunks(&hunks, dir.path()).unwrap();
+
+        assert_eq!(
+            diff,
+            "diff --git a/docs/new.md b/docs/new.md
+new file mode 100644
+--- /dev/null
++++ b/docs/new.md
+@@ -0,0 +1 @@
++# New
+diff --git a/obsolete.txt b/obsolete.txt
+deleted file mode 100644
+--- a/obsolete.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-gone
+diff --git a/src/lib.rs b/src/main.rs
+rename from src/lib.rs
+rename to src/main.rs
+--- a/src/lib.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    old();
++    new();
+ }
+"
+        );
+    }
+
+    #[test]
+    fn git_diff_round_trips_through_hunks() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("obsolete.txt"), "gone\n").unwrap();
+        fs::write(
+            dir.path().join("src/lib.rs"),
+            "fn main() {\n    old();\n}\n",
+        )
+        .unwrap();
+        let hunks = parse_patch(PATCH).unwrap().hunks;
+        let diff = unified_diff_from_hunks(&hunks, dir.path()).unwrap();
+
+        let parsed = hunks_from_unified_diff(&diff).unwrap();
+        assert_eq!(parsed[0], hunks[0]);
+        assert_eq!(parsed[1], hunks[1]);
+
+        let resolved: Vec<Hunk> = parsed
+            .into_iter()
+            .map(|hunk| crate::preview::resolve_hunk(hunk, dir.path()))
+            .collect();
+        apply_hunks(&resolved, &mut Vec::new(), &mut Vec::new()).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("src/main.rs")).unwrap(),
+            "fn main() {\n    new();\n}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("docs/new.md")).unwrap(),
+            "# New\n"
+        );
+        assert!(!dir.path().join("src/lib.rs").exists());
+        assert!(!dir.path().join("obsolete.txt").exists());
+    }
+
+    #[test]
+    fn parses_plain_diff_u_output() {
+        let diff = "--- a.txt\t2024-01-01 00:00:00.000000000 +0000
++++ a.txt\t2024-01-02 00:00:00.000000000 +0000
+@@ -1,2 +1,2 @@
+ keep
+-old
+\\ No newline at end of file
++new
+\\ No newline at end of file
+";
+
+        assert_eq!(
+            hunks_from_unified_diff(diff).unwrap(),
+            vec![Hunk::UpdateFile {
+                path: PathBuf::from("a.txt"),
+                move_path: None,
+                chunks: vec![UpdateFileChunk {
+                    change_context: None,
+                    old_lines: vec!["keep".to_string(), "old".to_string()],
+                    new_lines: vec!["keep".to_string(), "new".to_string()],
+                    is_end_of_file: true,
+                    old_start: Some(0),
+                }],
+            }]
+        );
+        assert_eq!(
+            hunks_from_unified_diff("Binary files a/logo.png and b/logo.png differ\n").unwrap_err(),
+            ParseError::InvalidPatchError("no file changes found in the diff".to_string())
+        );
+    }
+
+    #[test]
+    fn zero_context_chunks_apply_at_their_header_lines() {
+        let dir = tempdir().unwrap();
+        let apply_diff = |contents: &str, chunk: &str| {
+            let path = dir.path().join("f.txt");
+            fs::write(&path, contents).unwrap();
+            let diff = format!("--- a/f.txt\n+++ b/f.txt\n{chunk}");
+            let hunks: Vec<Hunk> = hunks_from_unified_diff(&diff)
+                .unwrap()
+                .into_iter()
+                .map(|hunk| crate::preview::resolve_hunk(hunk, dir.path()))
+                .collect();
+            apply_hunks(&hunks, &mut Vec::new(), &mut Vec::new()).unwrap();
+            fs::read_to_string(&path).unwrap()
+        };
+
+        assert_eq!(
+            apply_diff("a\nb\n", "@@ -0,0 +1 @@\n+zero\n"),
+            "zero\na\nb\n"
+        );
+        assert_eq!(apply_diff("a\nb\n", "@@ -1,0 +2 @@\n+one\n"), "a\none\nb\n");
+        assert_eq!(apply_diff("x\ny\nx\n", "@@ -3 +2,0 @@\n-x\n"), "x\ny\n");
+        assert_eq!(
+            apply_diff("x\ny\nx\ny\n", "@@ -1 +1 @@\n-x\n+X\n@@ -3 +3 @@\n-x\n+Z\n"),
+            "X\ny\nZ\ny\n"
+        );
+    }
+
+    #[test]
+    fn decodes_git_quoted_paths() {
+        let diff = r#"diff --git "a/caf\303\251.txt" "b/caf\303\251 \"v2\"\t.txt"
+similarity index 50%
+rename from "caf\303\251.txt"
+rename to "caf\303\251 \"v2\"\t.txt"
+--- "a/caf\303\251.txt"
++++ "b/caf\303\251 \"v2\"\t.txt"
+@@ -1 +1 @@
+-old
++new
+"#;
+        let hunks = hunks_from_unified_diff(diff).unwrap();
+        assert_eq!(
+            hunks,
+            vec![Hunk::UpdateFile {
+                path: PathBuf::from("café.txt"),
+                move_path: Some(PathBuf::from("café \"v2\"\t.txt")),
+                chunks: vec![UpdateFileChunk {
+                    change_context: None,
+                    old_lines: vec!["old".to_string()],
+                    new_lines: vec!["new".to_string()],
+                    is_end_of_file: false,
+                    old_start: Some(0),
+                }],
+            }]
+        );
+        assert_eq!(
+            unquote(r#""\a\b\f\r\v\\\101""#),
+            "\u{7}\u{8}\u{c}\r\u{b}\\A"
+        );
+
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("café.txt"), "old\n").unwrap();
+        let rendered = unified_diff_from_hunks(&hunks, dir.path()).unwrap();
+        assert_eq!(hunks_from_unified_diff(&rendered).unwrap(), hunks);
+    }
+
+    #[test]
+    fn rejects_chunk_lines_with_an_unknown_prefix() {
+        assert_eq!(
+            hunks_from_unified_diff("--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n-a\néb\n+c\n").unwrap_err(),
+            ParseError::InvalidHunkError {
+                message: "unexpected line in chunk".to_string(),
+                line_number: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn renders_and_parses_modes_and_symlinks() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("run.sh"), "#!/bin/sh\n").unwrap();
+        let patch = "*** Begin Patch
+*** Set Executable: run.sh
+*** Add Symlink: latest
++releases/v2
+*** Add Binary File: logo.png
++iVBORw0KGgo=
+*** End Patch
+";
+        let hunks = parse_patch(patch).unwrap().hunks;
+        assert_eq!(patch_from_hunks(&hunks), patch);
+
+        let diff = unified_diff_from_hunks(&hunks, dir.path()).unwrap();
+
+        assert_eq!(
+            diff,
+            "diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/latest b/latest
+new file mode 120000
+--- /dev/null
++++ b/latest
+@@ -0,0 +1 @@
++releases/v2
+\\ No newline at end of file
+diff --git a/logo.png b/logo.png
+new file mode 100644
+Binary files /dev/null and b/logo.png differ
+"
+        );
+        let (text_diff, _binary) = diff.split_at(diff.find("diff --git a/logo.png").unwrap());
+        assert_eq!(
+            hunks_from_unified_diff(text_diff).unwrap(),
+            hunks[..2].to_vec()
+        );
+    }
+
+    #[test]
+    fn patch_round_trips_through_hunks() {
+        let hunks = parse_patch(PATCH).unwrap().hunks;
+
+        assert_eq!(patch_from_hunks(&hunks), PATCH);
+    }
+}
@@ -167,8 +167,17 @@ pub fn maybe_parse_apply_patch_verified(argv: &[String], cwd: &Path) -> MaybeApp
                     Hunk::AddFile { contents, .. } => {
                         changes.insert(path, ApplyPatchFileChange::Add { content: contents });
                     }
+                    Hunk::AddBinaryFile { contents, .. } => {
+                        changes.insert(path, ApplyPatchFileChange::AddBinary { content: contents });
+                    }
+                    Hunk::SetExecutable { executable, .. } => {
+                        changes.insert(path, ApplyPatchFileChange::SetExecutable { executable });
+                    }
+                    Hunk::AddSymlink { target, .. } => {
+                        changes.insert(path, ApplyPatchFileChange::AddSymlink { target });
+                    }
                     Hunk::DeleteFile { .. } => {
-                        let content = match std::fs::read_to_string(&path) {
+                        let content = match std::fs::read(&path) {
                             Ok(content) => content,
                             Err(e) => {
                                 return MaybeApplyPatchVerified::CorrectnessError(
@@ -179,7 +188,13 @@ pub fn maybe_parse_apply_patch_verified(argv: &[String], cwd: &Path) -> MaybeApp
                                 );
                             }
                         };
-                        changes.insert(path, ApplyPatchFileChange::Delete { content });
+                        let change = match String::from_utf8(content) {
+                            Ok(content) => ApplyPatchFileChange::Delete { content },
+                            Err(err) => ApplyPatchFileChange::DeleteBinary {
+                                content: err.into_bytes(),
+                            },
+                        };
+                        changes.insert(path, change);
                     }
                     Hunk::UpdateFile {
                         move_path, chunks, ..
@@ -1,19 +1,32 @@
+mod convert;
 mod invocation;
 mod parser;
+mod preview;
 mod seek_sequence;
 mod standalone_executable;
+mod transaction;
 
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
 
-use anyhow::Context;
 use anyhow::Result;
+pub use convert::hunks_from_unified_diff;
+pub use convert::patch_from_hunks;
+pub use convert::unified_diff_from_hunks;
 pub use parser::Hunk;
 pub use parser::ParseError;
 use parser::ParseError::*;
 use parser::UpdateFileChunk;
 pub use parser::parse_patch;
+pub use preview::FilePreview;
+pub use preview::LineStats;
+pub use preview::PatchPreview;
+pub use preview::PreviewKind;
+pub use preview::preview_hunks;
+pub use preview::preview_patch;
+pub use preview::preview_patch_with_context;
+pub use preview::resolve_hunk;
 use similar::TextDiff;
 use thiserror::Error;
 
@@ -21,6 +34,7 @@ pub use invocation::maybe_parse_apply_patch_verified;
 pub use standalone_executable::main;
 
 use crate::invocation::ExtractHeredocError;
+use crate::transaction::Transaction;
 
 /// Detailed instructions for gpt-4.1 on how to use the `apply_patch` tool.
 pub const APPLY_PATCH_TOOL_INSTRUCTIONS: &str = include_str!("../apply_patch_tool_instructions.md");
@@ -39,6 +53,14 @@ pub enum ApplyPatchError {
         "patch detected without explicit call to apply_patch. Rerun as [\"apply_patch\", \"<patch>\"]"
     )]
     ImplicitInvocation,
+    /// The patch could not be applied and the working tree was left exactly as
+    /// it was: either a hunk failed before anything was written, or every file
+    /// already written was restored (see [`AffectedPaths::rolled_back`]).
+    #[error("{message}")]
+    TreeUntouched {
+        message: String,
+        affected: AffectedPaths,
+    },
 }
 
 impl From<std::io::Error> for ApplyPatchError {
@@ -82,6 +104,32 @@ pub struct ApplyPatchArgs {
     pub workdir: Option<String>,
 }
 
+/// The default for [`ApplyPatchOptions::min_confidence`].
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.9;
+
+/// Closest matches scoring below this are not worth showing in an error.
+const MIN_REPORTED_CONFIDENCE: f64 = 0.5;
+
+/// Options that control how a patch is applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApplyPatchOptions {
+    /// When the lines of a chunk (or its `@@` context line) cannot be found
+    /// even after whitespace and punctuation normalisation, the most similar
+    /// region of the file is used instead if its confidence, from 0.0 to 1.0,
+    /// is at least this. That region keeps its own context lines; each line
+    /// the chunk removes must be at least this similar to the file's line.
+    /// Values above 1.0 disable fuzzy matching.
+    pub min_confidence: f64,
+}
+
+impl Default for ApplyPatchOptions {
+    fn default() -> Self {
+        Self {
+            min_confidence: DEFAULT_MIN_CONFIDENCE,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ApplyPatchFileChange {
     Add {
@@ -96,6 +144,22 @@ pub enum ApplyPatchFileChange {
         /// new_content that will result after the unified_diff is applied.
         new_content: String,
     },
+    AddBinary {
+        content: Vec<u8>,
+    },
+    /// The deletion of a file whose contents are not valid UTF-8.
+    DeleteBinary {
+        content: Vec<u8>,
+    },
+    /// Sets or clears the executable bits, leaving the contents as they are.
+    SetExecutable {
+        executable: bool,
+    },
+    AddSymlink {
+        /// The target as written in the patch, relative to the directory
+        /// containing the link.
+        target: PathBuf,
+    },
 }
 
 #[derive(Debug, PartialEq)]
@@ -208,15 +272,25 @@ pub fn apply_hunks(
     hunks: &[Hunk],
     stdout: &mut impl std::io::Write,
     stderr: &mut impl std::io::Write,
+) -> Result<(), ApplyPatchError> {
+    apply_hunks_with_options(hunks, ApplyPatchOptions::default(), stdout, stderr)
+}
+
+/// Like [`apply_hunks`], with explicit [`ApplyPatchOptions`].
+pub fn apply_hunks_with_options(
+    hunks: &[Hunk],
+    options: ApplyPatchOptions,
+    stdout: &mut impl std::io::Write,
+    stderr: &mut impl std::io::Write,
 ) -> Result<(), ApplyPatchError> {
     let _existing_paths: Vec<&Path> = hunks
         .iter()
         .filter_map(|hunk| match hunk {
-            Hunk::AddFile { .. } => {
+            Hunk::AddFile { .. } | Hunk::AddBinaryFile { .. } | Hunk::AddSymlink { .. } => {
                 // The file is being added, so it doesn't exist yet.
                 None
             }
-            Hunk::DeleteFile { path } => Some(path.as_path()),
+            Hunk::DeleteFile { path } | Hunk::SetExecutable { path, .. } => Some(path.as_path()),
             Hunk::UpdateFile {
                 path, move_path, ..
             } => match move_path {
@@ -236,97 +310,187 @@ pub fn apply_hunks(
         .collect::<Vec<&Path>>();
 
     // Delegate to a helper that applies each hunk to the filesystem.
-    match apply_hunks_to_files(hunks) {
+    match apply_hunks_to_files(hunks, options) {
         Ok(affected) => {
             print_summary(&affected, stdout).map_err(ApplyPatchError::from)?;
             Ok(())
         }
         Err(err) => {
-            let msg = err.to_string();
-            writeln!(stderr, "{msg}").map_err(ApplyPatchError::from)?;
-            if let Some(io) = err.downcast_ref::<std::io::Error>() {
-                Err(ApplyPatchError::from(io))
-            } else {
-                Err(ApplyPatchError::IoError(IoError {
-                    context: msg,
-                    source: std::io::Error::other(err),
-                }))
+            writeln!(stderr, "{err}").map_err(ApplyPatchError::from)?;
+            if let ApplyPatchError::TreeUntouched { affected, .. } = &err
+                && !affected.rolled_back.is_empty()
+            {
+                writeln!(stderr, "Rolled back the following files:")
+                    .map_err(ApplyPatchError::from)?;
+                for path in &affected.rolled_back {
+                    writeln!(stderr, "R {}", path.display()).map_err(ApplyPatchError::from)?;
+                }
             }
+            Err(err)
         }
     }
 }
 
-/// Applies each parsed patch hunk to the filesystem.
-/// Returns an error if any of the changes could not be applied.
 /// Tracks file paths affected by applying a patch.
+#[derive(Debug, Default, PartialEq)]
 pub struct AffectedPaths {
     pub added: Vec<PathBuf>,
     pub modified: Vec<PathBuf>,
     pub deleted: Vec<PathBuf>,
+    /// Files that had already been written when a later write failed, and
+    /// that were restored to their previous state.
+    pub rolled_back: Vec<PathBuf>,
 }
 
 /// Apply the hunks to the filesystem, returning which files were added, modified, or deleted.
-/// Returns an error if the patch could not be applied.
-fn apply_hunks_to_files(hunks: &[Hunk]) -> anyhow::Result<AffectedPaths> {
+///
+/// Every hunk is applied in memory and checked against the filesystem before
+/// anything is written, then all files are written at once. If the patch
+/// cannot be applied, any file already written is rolled back and the error is
+/// [`ApplyPatchError::TreeUntouched`].
+fn apply_hunks_to_files(
+    hunks: &[Hunk],
+    options: ApplyPatchOptions,
+) -> std::result::Result<AffectedPaths, ApplyPatchError> {
+    let untouched = |message: String| ApplyPatchError::TreeUntouched {
+        message,
+        affected: AffectedPaths::default(),
+    };
     if hunks.is_empty() {
-        anyhow::bail!("No files were modified.");
+        return Err(untouched("No files were modified.".to_string()));
     }
 
-    let mut added: Vec<PathBuf> = Vec::new();
-    let mut modified: Vec<PathBuf> = Vec::new();
-    let mut deleted: Vec<PathBuf> = Vec::new();
+    let mut transaction = Transaction::default();
+    let mut affected = AffectedPaths::default();
     for hunk in hunks {
-        match hunk {
-            Hunk::AddFile { path, contents } => {
-                if let Some(parent) = path.parent()
-                    && !parent.as_os_str().is_empty()
-                {
-                    std::fs::create_dir_all(parent).with_context(|| {
-                        format!("Failed to create parent directories for {}", path.display())
-                    })?;
+        stage_hunk(&mut transaction, hunk, options, &mut affected).map_err(untouched)?;
+    }
+
+    match transaction.commit() {
+        Ok(()) => Ok(affected),
+        Err(err) => {
+            let mut unrestored = err.unrestored.into_iter();
+            match unrestored.next() {
+                None => Err(ApplyPatchError::TreeUntouched {
+                    message: err.message,
+                    affected: AffectedPaths {
+                        rolled_back: err.rolled_back,
+                        ..AffectedPaths::default()
+                    },
+                }),
+                Some((path, source)) => {
+                    let paths = std::iter::once(path)
+                        .chain(unrestored.map(|(path, _)| path))
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    Err(ApplyPatchError::IoError(IoError {
+                        context: format!("{}; failed to roll back {paths}", err.message),
+                        source,
+                    }))
                 }
-                std::fs::write(path, contents)
-                    .with_context(|| format!("Failed to write file {}", path.display()))?;
-                added.push(path.clone());
             }
-            Hunk::DeleteFile { path } => {
-                std::fs::remove_file(path)
-                    .with_context(|| format!("Failed to delete file {}", path.display()))?;
-                deleted.push(path.clone());
+        }
+    }
+}
+
+/// Applies one hunk to the staged state of `transaction`, checking that the
+/// files it reads exist and the files it writes can be written.
+fn stage_hunk(
+    transaction: &mut Transaction,
+    hunk: &Hunk,
+    options: ApplyPatchOptions,
+    affected: &mut AffectedPaths,
+) -> std::result::Result<(), String> {
+    match hunk {
+        Hunk::AddFile { path, contents } => {
+            ensure_writable(path)?;
+            transaction.write(path, contents.clone().into_bytes());
+            affected.added.push(path.clone());
+        }
+        Hunk::AddBinaryFile { path, contents } => {
+            ensure_writable(path)?;
+            transaction.write(path, contents.clone());
+            affected.added.push(path.clone());
+        }
+        Hunk::AddSymlink { path, target } => {
+            ensure_writable(path)?;
+            transaction.symlink(path, target.clone());
+            affected.added.push(path.clone());
+        }
+        Hunk::SetExecutable { path, executable } => {
+            if !transaction.is_file(path) {
+                return Err(format!(
+                    "Failed to change the mode of file {}",
+                    path.display()
+                ));
             }
-            Hunk::UpdateFile {
-                path,
-                move_path,
-                chunks,
-            } => {
-                let AppliedPatch { new_contents, .. } =
-                    derive_new_contents_from_chunks(path, chunks)?;
-                if let Some(dest) = move_path {
-                    if let Some(parent) = dest.parent()
-                        && !parent.as_os_str().is_empty()
-                    {
-                        std::fs::create_dir_all(parent).with_context(|| {
-                            format!("Failed to create parent directories for {}", dest.display())
-                        })?;
-                    }
-                    std::fs::write(dest, new_contents)
-                        .with_context(|| format!("Failed to write file {}", dest.display()))?;
-                    std::fs::remove_file(path)
-                        .with_context(|| format!("Failed to remove original {}", path.display()))?;
-                    modified.push(dest.clone());
-                } else {
-                    std::fs::write(path, new_contents)
-                        .with_context(|| format!("Failed to write file {}", path.display()))?;
-                    modified.push(path.clone());
+            transaction.set_executable(path, *executable);
+            affected.modified.push(path.clone());
+        }
+        Hunk::DeleteFile { path } => {
+            if !transaction.is_file(path) {
+                return Err(format!("Failed to delete file {}", path.display()));
+            }
+            transaction.remove(path);
+            affected.deleted.push(path.clone());
+        }
+        Hunk::UpdateFile {
+            path,
+            move_path,
+            chunks,
+        } => {
+            let failed = |err: &dyn std::fmt::Display| {
+                format!("Failed to read file to update {}: {err}", path.display())
+            };
+            let original_contents = match transaction.read(path) {
+                Ok(Some(contents)) => {
+                    String::from_utf8(contents).map_err(|_| failed(&"file is not valid UTF-8"))?
                 }
+                Ok(None) => return Err(failed(&"it was deleted earlier in the patch")),
+                Err(err) => return Err(failed(&err)),
+            };
+            let AppliedPatch { new_contents, .. } =
+                apply_chunks_to_contents(path, original_contents, chunks, options)
+                    .map_err(|err| err.to_string())?;
+            let dest = move_path.as_ref().unwrap_or(path);
+            ensure_writable(dest)?;
+            transaction.write(dest, new_contents.into_bytes());
+            if dest != path {
+                transaction.remove(path);
+            }
+            affected.modified.push(dest.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Checks, before anything is written, that `path` can be created or replaced.
+fn ensure_writable(path: &Path) -> std::result::Result<(), String> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() || metadata.permissions().readonly() => {
+            Err(format!("Failed to write file {}", path.display()))
+        }
+        Ok(_) => Ok(()),
+        Err(_) => {
+            // The file will be created, so its nearest existing ancestor must
+            // be a directory.
+            let blocked = path
+                .ancestors()
+                .skip(1)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .find_map(|dir| std::fs::metadata(dir).ok())
+                .is_some_and(|metadata| !metadata.is_dir());
+            if blocked {
+                Err(format!(
+                    "Failed to create parent directories for {}",
+                    path.display()
+                ))
+            } else {
+                Ok(())
             }
         }
     }
-    Ok(AffectedPaths {
-        added,
-        modified,
-        deleted,
-    })
 }
 
 struct AppliedPatch {
@@ -349,7 +513,21 @@ fn derive_new_contents_from_chunks(
             }));
         }
     };
+    apply_chunks_to_contents(
+        path,
+        original_contents,
+        chunks,
+        ApplyPatchOptions::default(),
+    )
+}
 
+/// Apply the chunks to `original_contents`, the current contents of `path`.
+fn apply_chunks_to_contents(
+    path: &Path,
+    original_contents: String,
+    chunks: &[UpdateFileChunk],
+    options: ApplyPatchOptions,
+) -> std::result::Result<AppliedPatch, ApplyPatchError> {
     let mut original_lines: Vec<String> = original_contents.split('\n').map(String::from).collect();
 
     // Drop the trailing empty element that results from the final newline so
@@ -358,7 +536,7 @@ fn derive_new_contents_from_chunks(
         original_lines.pop();
     }
 
-    let replacements = compute_replacements(&original_lines, path, chunks)?;
+    let replacements = compute_replacements(&original_lines, path, chunks, options)?;
     let new_lines = apply_replacements(original_lines, &replacements);
     let mut new_lines = new_lines;
     if !new_lines.last().is_some_and(String::is_empty) {
@@ -373,11 +551,13 @@ fn derive_new_contents_from_chunks(
 
 /// Compute a list of replacements needed to transform `original_lines` into the
 /// new lines, given the patch `chunks`. Each replacement is returned as
-/// `(start_index, old_len, new_lines)`.
+/// `(start_index, old_len, new_lines)`. Lines that cannot be found exactly
+/// fall back to the closest fuzzy match allowed by `options`.
 fn compute_replacements(
     original_lines: &[String],
     path: &Path,
     chunks: &[UpdateFileChunk],
+    options: ApplyPatchOptions,
 ) -> std::result::Result<Vec<(usize, usize, Vec<String>)>, ApplyPatchError> {
     let mut replacements: Vec<(usize, usize, Vec<String>)> = Vec::new();
     let mut line_index: usize = 0;
@@ -394,22 +574,36 @@ fn compute_replacements(
             ) {
                 line_index = idx + 1;
             } else {
-                return Err(ApplyPatchError::ComputeReplacements(format!(
-                    "Failed to find context '{}' in {}",
-                    ctx_line,
-                    path.display()
-                )));
+                let context = std::slice::from_ref(ctx_line);
+                match seek_sequence::fuzzy_seek_sequence(original_lines, context, line_index, false)
+                {
+                    Some(anchor) if anchor.confidence >= options.min_confidence => {
+                        line_index = anchor.start + 1;
+                    }
+                    closest => {
+                        return Err(ApplyPatchError::ComputeReplacements(format!(
+                            "Failed to find context '{}' in {}{}",
+                            ctx_line,
+                            path.display(),
+                            describe_closest_match(original_lines, context, closest, options)
+                        )));
+                    }
+                }
             }
         }
 
         if chunk.old_lines.is_empty() {
-            // Pure addition (no old lines). We'll add them at the end or just
-            // before the final empty line if one exists.
-            let insertion_idx = if original_lines.last().is_some_and(String::is_empty) {
+            // Pure addition (no old lines). We'll add them where a unified
+            // diff placed them, else at the end or just before the final empty
+            // line if one exists.
+            let end_idx = if original_lines.last().is_some_and(String::is_empty) {
                 original_lines.len() - 1
             } else {
                 original_lines.len()
             };
+            let insertion_idx = chunk
+                .old_start
+                .map_or(end_idx, |old_start| old_start.min(end_idx));
             replacements.push((insertion_idx, 0, chunk.new_lines.clone()));
             continue;
         }
@@ -426,8 +620,7 @@ fn compute_replacements(
         // located reliably.
 
         let mut pattern: &[String] = &chunk.old_lines;
-        let mut found =
-            seek_sequence::seek_sequence(original_lines, pattern, line_index, chunk.is_end_of_file);
+        let mut found = seek_chunk(original_lines, pattern, line_index, chunk);
 
         let mut new_slice: &[String] = &chunk.new_lines;
 
@@ -439,29 +632,144 @@ fn compute_replacements(
                 new_slice = &new_slice[..new_slice.len() - 1];
             }
 
-            found = seek_sequence::seek_sequence(
+            found = seek_chunk(original_lines, pattern, line_index, chunk);
+        }
+
+        let (start_idx, new_region) = match found {
+            Some(start_idx) => (start_idx, new_slice.to_vec()),
+            None => match seek_sequence::fuzzy_seek_sequence(
                 original_lines,
                 pattern,
                 line_index,
                 chunk.is_end_of_file,
-            );
-        }
+            ) {
+                Some(closest) if closest.confidence >= options.min_confidence => (
+                    closest.start,
+                    rebase_fuzzy_region(
+                        original_lines,
+                        closest.start,
+                        pattern,
+                        new_slice,
+                        path,
+                        options,
+                    )?,
+                ),
+                closest => {
+                    return Err(ApplyPatchError::ComputeReplacements(format!(
+                        "Failed to find expected lines in {}:\n{}{}",
+                        path.display(),
+                        chunk.old_lines.join("\n"),
+                        describe_closest_match(original_lines, pattern, closest, options),
+                    )));
+                }
+            },
+        };
+        replacements.push((start_idx, pattern.len(), new_region));
+        line_index = start_idx + pattern.len();
+    }
+
+    replacements.sort_by_key(|(start_idx, _, _)| *start_idx);
 
-        if let Some(start_idx) = found {
-            replacements.push((start_idx, pattern.len(), new_slice.to_vec()));
-            line_index = start_idx + pattern.len();
-        } else {
-            return Err(ApplyPatchError::ComputeReplacements(format!(
-                "Failed to find expected lines in {}:\n{}",
-                path.display(),
-                chunk.old_lines.join("\n"),
-            )));
+    Ok(replacements)
+}
+
+/// The replacement for the `old.len()` lines at `start` that fuzzy matching
+/// found for a chunk: the file's own lines where the chunk has context, and
+/// the chunk's new lines where it changes something. Fails if a line the chunk
+/// removes does not closely match the file, since the chunk would then delete
+/// a line it never saw.
+fn rebase_fuzzy_region(
+    lines: &[String],
+    start: usize,
+    old: &[String],
+    new: &[String],
+    path: &Path,
+    options: ApplyPatchOptions,
+) -> std::result::Result<Vec<String>, ApplyPatchError> {
+    let actual = &lines[start..start + old.len()];
+    let mut region = Vec::with_capacity(new.len());
+    for op in similar::capture_diff_slices(similar::Algorithm::Myers, old, new) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        if tag == similar::DiffTag::Equal {
+            region.extend_from_slice(&actual[old_range]);
+            continue;
+        }
+        for index in old_range {
+            if seek_sequence::line_similarity(&actual[index], &old[index]) < options.min_confidence
+            {
+                return Err(ApplyPatchError::ComputeReplacements(format!(
+                    "Refusing to remove line {} of {}: expected\n-{}\nbut found\n+{}",
+                    start + index + 1,
+                    path.display(),
+                    old[index],
+                    actual[index],
+                )));
+            }
         }
+        region.extend_from_slice(&new[new_range]);
     }
+    Ok(region)
+}
 
-    replacements.sort_by(|(lhs_idx, _, _), (rhs_idx, _, _)| lhs_idx.cmp(rhs_idx));
+/// Finds `pattern`, the old lines of `chunk`, at or after `start`. When a
+/// unified diff gave the chunk's position, the occurrence nearest to it wins,
+/// so a chunk with little or no context is not applied to an earlier copy of
+/// its lines.
+fn seek_chunk(
+    lines: &[String],
+    pattern: &[String],
+    start: usize,
+    chunk: &UpdateFileChunk,
+) -> Option<usize> {
+    let Some(hint) = chunk.old_start.filter(|hint| *hint >= start) else {
+        return seek_sequence::seek_sequence(lines, pattern, start, chunk.is_end_of_file);
+    };
+    let mut nearest: Option<usize> = None;
+    let mut from = start;
+    while let Some(found) = seek_sequence::seek_sequence(lines, pattern, from, false) {
+        if nearest.is_none_or(|nearest| found.abs_diff(hint) < nearest.abs_diff(hint)) {
+            nearest = Some(found);
+        }
+        if found >= hint {
+            break;
+        }
+        from = found + 1;
+    }
+    nearest
+}
 
-    Ok(replacements)
+/// Explains why `closest` was not applied, with a line-by-line diff of the
+/// expected lines against the file, or nothing if no region came close.
+fn describe_closest_match(
+    lines: &[String],
+    pattern: &[String],
+    closest: Option<seek_sequence::FuzzyMatch>,
+    options: ApplyPatchOptions,
+) -> String {
+    let Some(closest) = closest.filter(|closest| closest.confidence >= MIN_REPORTED_CONFIDENCE)
+    else {
+        return String::new();
+    };
+    let actual = &lines[closest.start..closest.start + pattern.len()];
+    let location = match actual.len() {
+        1 => format!("line {}", closest.start + 1),
+        len => format!("lines {}-{}", closest.start + 1, closest.start + len),
+    };
+    let mut description = format!(
+        "\nClosest match is at {location} ({:.0}% similar, {:.0}% required; - expected, + actual):",
+        closest.confidence * 100.0,
+        options.min_confidence * 100.0,
+    );
+    let ops = similar::capture_diff_slices(similar::Algorithm::Myers, pattern, actual);
+    for change in ops.iter().flat_map(|op| op.iter_changes(pattern, actual)) {
+        let sign = match change.tag() {
+            similar::ChangeTag::Equal => ' ',
+            similar::ChangeTag::Delete => '-',
+            similar::ChangeTag::Insert => '+',
+        };
+        description.push_str(&format!("\n{sign}{}", change.value()));
+    }
+    description
 }
 
 /// Apply the `(start_index, old_len, new_lines)` replacements to `original_lines`,
@@ -516,13 +824,16 @@ pub fn unified_diff_from_chunks_with_context(
         new_contents,
     } = derive_new_contents_from_chunks(path, chunks)?;
     let text_diff = TextDiff::from_lines(&original_contents, &new_contents);
-    let unified_diff = text_diff.unified_diff().context_radius(context).to_string();
     Ok(ApplyPatchFileUpdate {
-        unified_diff,
+        unified_diff: unified_diff(&text_diff, context),
         content: new_contents,
     })
 }
 
+fn unified_diff<'a>(text_diff: &'a TextDiff<'a, 'a, 'a, str>, context: usize) -> String {
+    text_diff.unified_diff().context_radius(context).to_string()
+}
+
 /// Print the summary of changes in git-style format.
 /// Write a summary of changes to the given writer.
 pub fn print_summary(
@@ -1062,4 +1373,243 @@ g
         let result = apply_patch(&patch, &mut stdout, &mut stderr);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_failing_hunk_leaves_tree_untouched() {
+        let dir = tempdir().unwrap();
+        let modify = dir.path().join("modify.txt");
+        let delete = dir.path().join("delete.txt");
+        let added = dir.path().join("nested/added.txt");
+        fs::write(&modify, "one\ntwo\n").unwrap();
+        fs::write(&delete, "obsolete\n").unwrap();
+        let patch = wrap_patch(&format!(
+            "*** Update File: {}\n@@\n-two\n+TWO\n*** Delete File: {}\n*** Add File: {}\n+new\n*** Update File: {}\n@@\n-missing\n+present",
+            modify.display(),
+            delete.display(),
+            added.display(),
+            modify.display(),
+        ));
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let err = apply_patch(&patch, &mut stdout, &mut stderr).unwrap_err();
+
+        let message = format!(
+            "Failed to find expected lines in {}:\nmissing",
+            modify.display()
+        );
+        assert_eq!(
+            err,
+            ApplyPatchError::TreeUntouched {
+                message: message.clone(),
+                affected: AffectedPaths::default(),
+            }
+        );
+        assert_eq!(String::from_utf8(stderr).unwrap(), format!("{message}\n"));
+        assert_eq!(fs::read_to_string(&modify).unwrap(), "one\ntwo\n");
+        assert_eq!(fs::read_to_string(&delete).unwrap(), "obsolete\n");
+        assert!(!dir.path().join("nested").exists());
+    }
+
+    #[test]
+    fn test_failed_write_rolls_back_earlier_writes() {
+        let dir = tempdir().unwrap();
+        let modify = dir.path().join("modify.txt");
+        let blocker = dir.path().join("blocker");
+        fs::write(&modify, "before\n").unwrap();
+        // `blocker` only becomes a file when the patch is written, so creating
+        // `blocker/child.txt` fails after `modify.txt` has been replaced.
+        let patch = wrap_patch(&format!(
+            "*** Update File: {}\n@@\n-before\n+after\n*** Add File: {}\n+file\n*** Add File: {}\n+child",
+            modify.display(),
+            blocker.display(),
+            blocker.join("child.txt").display(),
+        ));
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let err = apply_patch(&patch, &mut stdout, &mut stderr).unwrap_err();
+
+        let ApplyPatchError::TreeUntouched { affected, .. } = err else {
+            panic!("expected the tree to be left untouched, got {err:?}");
+        };
+        assert_eq!(affected.rolled_back, vec![modify.clone(), blocker.clone()]);
+        assert_eq!(fs::read_to_string(&modify).unwrap(), "before\n");
+        assert!(!blocker.exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert!(
+            stderr.ends_with(&format!(
+                "Rolled back the following files:\nR {}\nR {}\n",
+                modify.display(),
+                blocker.display()
+            )),
+            "{stderr}"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match_applies_drifted_chunk() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("drift.rs");
+        fs::write(
+            &path,
+            "fn report(total: u32) {\n    let label = format!(\"{total}\");\n    println!(\"{label}\");\n    log(label);\n}\n",
+        )
+        .unwrap();
+        // The context renames `label` to `text` in one line.
+        let patch = wrap_patch(&format!(
+            r#"*** Update File: {}
+@@ fn report(total: u32) {{
+     let label = format!("{{total}}");
+     println!("{{text}}");
+-    log(label);
++    log_total(total);
+ }}"#,
+            path.display()
+        ));
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        apply_patch(&patch, &mut stdout, &mut stderr).unwrap();
+
+        // Only the removed line is replaced; the file keeps its own context.
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "fn report(total: u32) {\n    let label = format!(\"{total}\");\n    println!(\"{label}\");\n    log_total(total);\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_match_refuses_to_remove_unseen_line() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("drift.rs");
+        let original = "fn steps() {\n    one();\n    two();\n    three();\n    four();\n    remove_me();\n    six();\n    seven();\n    eight();\n    nine();\n}\n";
+        fs::write(&path, original).unwrap();
+        let patch = wrap_patch(&format!(
+            "*** Update File: {}\n@@\n fn steps() {{\n     one();\n     two();\n     three();\n     four();\n-    totally_different();\n     six();\n     seven();\n     eight();\n     nine();\n }}",
+            path.display()
+        ));
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let err = apply_patch(&patch, &mut stdout, &mut stderr).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Refusing to remove line 6 of {}: expected\n-    totally_different();\nbut found\n+    remove_me();",
+                path.display()
+            )
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_fuzzy_match_below_threshold_reports_closest_region() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("drift.txt");
+        fs::write(&path, "alpha\nbeta\ngamma\ndelta\n").unwrap();
+        let patch = wrap_patch(&format!(
+            "*** Update File: {}\n@@\n beta\n-gamma\n+GAMMA\n epsilon",
+            path.display()
+        ));
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let err = apply_patch(&patch, &mut stdout, &mut stderr).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Failed to find expected lines in {}:\nbeta\ngamma\nepsilon\nClosest match is at lines 2-4 (67% similar, 90% required; - expected, + actual):\n beta\n gamma\n-epsilon\n+delta",
+                path.display()
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "alpha\nbeta\ngamma\ndelta\n"
+        );
+
+        // The same chunk applies once the threshold is lowered, keeping the
+        // file's `delta` where the chunk expected `epsilon` as context.
+        let hunks = parse_patch(&patch).unwrap().hunks;
+        let options = ApplyPatchOptions {
+            min_confidence: 0.6,
+        };
+        apply_hunks_with_options(&hunks, options, &mut stdout, &mut stderr).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "alpha\nbeta\nGAMMA\ndelta\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_binary_executable_and_symlink_hunks() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("build.sh");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o640)).unwrap();
+        let image = dir.path().join("assets/logo.png");
+        let link = dir.path().join("assets/current.png");
+        let patch = wrap_patch(&format!(
+            "*** Add Binary File: {}\n+iVBORw0K\n+Ggo=\n*** Set Executable: {}\n*** Add Symlink: {}\n+logo.png",
+            image.display(),
+            script.display(),
+            link.display(),
+        ));
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        apply_patch(&patch, &mut stdout, &mut stderr).unwrap();
+
+        assert_eq!(fs::read(&image).unwrap(), b"\x89PNG\r\n\x1a\n");
+        assert_eq!(
+            fs::metadata(&script).unwrap().permissions().mode() & 0o777,
+            0o750
+        );
+        assert_eq!(fs::read_link(&link).unwrap(), PathBuf::from("logo.png"));
+        assert_eq!(fs::read(&link).unwrap(), b"\x89PNG\r\n\x1a\n");
+        assert_eq!(
+            String::from_utf8(stdout).unwrap(),
+            format!(
+                "Success. Updated the following files:\nA {}\nA {}\nM {}\n",
+                image.display(),
+                link.display(),
+                script.display()
+            )
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_write_rolls_back_mode_change() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("run.sh");
+        let blocker = dir.path().join("blocker");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let patch = wrap_patch(&format!(
+            "*** Clear Executable: {}\n*** Add File: {}\n+file\n*** Add File: {}\n+child",
+            script.display(),
+            blocker.display(),
+            blocker.join("child.txt").display(),
+        ));
+
+        let err = apply_patch(&patch, &mut Vec::new(), &mut Vec::new()).unwrap_err();
+
+        let ApplyPatchError::TreeUntouched { affected, .. } = err else {
+            panic!("expected the tree to be left untouched, got {err:?}");
+        };
+        assert_eq!(affected.rolled_back, vec![script.clone(), blocker]);
+        assert_eq!(
+            fs::metadata(&script).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+    }
 }
@@ -7,12 +7,16 @@
 //! begin_patch: "*** Begin Patch" LF
 //! end_patch: "*** End Patch" LF?
 //!
-//! hunk: add_hunk | delete_hunk | update_hunk
+//! hunk: add_hunk | delete_hunk | update_hunk | add_binary_hunk | mode_hunk | symlink_hunk
 //! add_hunk: "*** Add File: " filename LF add_line+
 //! delete_hunk: "*** Delete File: " filename LF
 //! update_hunk: "*** Update File: " filename LF change_move? change?
+//! add_binary_hunk: "*** Add Binary File: " filename LF base64_line*
+//! mode_hunk: ("*** Set Executable: " | "*** Clear Executable: ") filename LF
+//! symlink_hunk: "*** Add Symlink: " filename LF add_line
 //! filename: /(.+)/
 //! add_line: "+" /(.+)/ LF -> line
+//! base64_line: "+" /[A-Za-z0-9+\/=]*/ LF
 //!
 //! change_move: "*** Move to: " filename LF
 //! change: (change_context | change_line)+ eof_line?
@@ -26,6 +30,8 @@ use crate::ApplyPatchArgs;
 use std::path::Path;
 use std::path::PathBuf;
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
 use thiserror::Error;
 
 const BEGIN_PATCH_MARKER: &str = "*** Begin Patch";
@@ -33,6 +39,10 @@ const END_PATCH_MARKER: &str = "*** End Patch";
 const ADD_FILE_MARKER: &str = "*** Add File: ";
 const DELETE_FILE_MARKER: &str = "*** Delete File: ";
 const UPDATE_FILE_MARKER: &str = "*** Update File: ";
+const ADD_BINARY_FILE_MARKER: &str = "*** Add Binary File: ";
+const SET_EXECUTABLE_MARKER: &str = "*** Set Executable: ";
+const CLEAR_EXECUTABLE_MARKER: &str = "*** Clear Executable: ";
+const ADD_SYMLINK_MARKER: &str = "*** Add Symlink: ";
 const MOVE_TO_MARKER: &str = "*** Move to: ";
 const EOF_MARKER: &str = "*** End of File";
 const CHANGE_CONTEXT_MARKER: &str = "@@ ";
@@ -73,6 +83,24 @@ pub enum Hunk {
         /// should occur later in the file than the previous chunk.
         chunks: Vec<UpdateFileChunk>,
     },
+    /// Creates (or overwrites) a file with arbitrary bytes, written in the
+    /// patch as base64.
+    AddBinaryFile {
+        path: PathBuf,
+        contents: Vec<u8>,
+    },
+    /// Sets or clears the executable bits of an existing file without
+    /// changing its contents.
+    SetExecutable {
+        path: PathBuf,
+        executable: bool,
+    },
+    /// Creates a symlink at `path` pointing at `target`, which is stored as
+    /// written and so is relative to the directory containing `path`.
+    AddSymlink {
+        path: PathBuf,
+        target: PathBuf,
+    },
 }
 
 impl Hunk {
@@ -81,6 +109,9 @@ impl Hunk {
             Hunk::AddFile { path, .. } => cwd.join(path),
             Hunk::DeleteFile { path } => cwd.join(path),
             Hunk::UpdateFile { path, .. } => cwd.join(path),
+            Hunk::AddBinaryFile { path, .. } => cwd.join(path),
+            Hunk::SetExecutable { path, .. } => cwd.join(path),
+            Hunk::AddSymlink { path, .. } => cwd.join(path),
         }
     }
 }
@@ -101,6 +132,11 @@ pub struct UpdateFileChunk {
     /// If set to true, `old_lines` must occur at the end of the source file.
     /// (Tolerance around trailing newlines should be encouraged.)
     pub is_end_of_file: bool,
+
+    /// 0-based index in the original file of the first of `old_lines`, or of
+    /// the line a pure insertion goes before. Only unified diffs carry it (in
+    /// their `@@ -a,b` header); it places chunks with little or no context.
+    pub old_start: Option<usize>,
 }
 
 pub fn parse_patch(patch: &str) -> Result<ApplyPatchArgs, ParseError> {
@@ -330,11 +366,75 @@ fn parse_one_hunk(lines: &[&str], line_number: usize) -> Result<(Hunk, usize), P
             },
             parsed_lines,
         ));
+    } else if let Some(path) = first_line.strip_prefix(ADD_BINARY_FILE_MARKER) {
+        // Add Binary File: the contents are base64, split over any number of
+        // `+` lines.
+        let encoded: Vec<&str> = lines[1..]
+            .iter()
+            .map_while(|line| line.strip_prefix('+'))
+            .collect();
+        let contents = BASE64_STANDARD
+            .decode(encoded.concat().trim())
+            .map_err(|err| InvalidHunkError {
+                message: format!("Invalid base64 contents for binary file '{path}': {err}"),
+                line_number,
+            })?;
+        return Ok((
+            AddBinaryFile {
+                path: PathBuf::from(path),
+                contents,
+            },
+            encoded.len() + 1,
+        ));
+    } else if let Some(path) = first_line.strip_prefix(SET_EXECUTABLE_MARKER) {
+        return Ok((
+            SetExecutable {
+                path: PathBuf::from(path),
+                executable: true,
+            },
+            1,
+        ));
+    } else if let Some(path) = first_line.strip_prefix(CLEAR_EXECUTABLE_MARKER) {
+        return Ok((
+            SetExecutable {
+                path: PathBuf::from(path),
+                executable: false,
+            },
+            1,
+        ));
+    } else if let Some(path) = first_line.strip_prefix(ADD_SYMLINK_MARKER) {
+        // Add Symlink: exactly one `+` line holding the target.
+        let targets: Vec<&str> = lines[1..]
+            .iter()
+            .map_while(|line| line.strip_prefix('+'))
+            .collect();
+        let [target] = targets[..] else {
+            return Err(InvalidHunkError {
+                message: format!(
+                    "Symlink hunk for path '{path}' must have exactly one '+' line with its target, found {}",
+                    targets.len()
+                ),
+                line_number,
+            });
+        };
+        if target.is_empty() {
+            return Err(InvalidHunkError {
+                message: format!("Symlink hunk for path '{path}' has an empty target"),
+                line_number,
+            });
+        }
+        return Ok((
+            AddSymlink {
+                path: PathBuf::from(path),
+                target: PathBuf::from(target),
+            },
+            2,
+        ));
     }
 
     Err(InvalidHunkError {
         message: format!(
-            "'{first_line}' is not a valid hunk header. Valid hunk headers: '*** Add File: {{path}}', '*** Delete File: {{path}}', '*** Update File: {{path}}'"
+            "'{first_line}' is not a valid hunk header. Valid hunk headers: '*** Add File: {{path}}', '*** Delete File: {{path}}', '*** Update File: {{path}}', '*** Add Binary File: {{path}}', '*** Set Executable: {{path}}', '*** Clear Executable: {{path}}', '*** Add Symlink: {{path}}'"
         ),
         line_number,
     })
@@ -380,6 +480,7 @@ fn parse_update_file_chunk(
         old_lines: Vec::new(),
         new_lines: Vec::new(),
         is_end_of_file: false,
+        old_start: None,
     };
     let mut parsed_lines = 0;
     for line in &lines[start_index..] {
@@ -520,7 +621,8 @@ fn test_parse_patch() {
                     change_context: Some("def f():".to_string()),
                     old_lines: vec!["    pass".to_string()],
                     new_lines: vec!["    return 123".to_string()],
-                    is_end_of_file: false
+                    is_end_of_file: false,
+                    old_start: None
                 }]
             }
         ]
@@ -547,7 +649,8 @@ fn test_parse_patch() {
                     change_context: None,
                     old_lines: vec![],
                     new_lines: vec!["line".to_string()],
-                    is_end_of_file: false
+                    is_end_of_file: false,
+                    old_start: None
                 }],
             },
             AddFile {
@@ -578,6 +681,7 @@ fn test_parse_patch() {
                 old_lines: vec!["import foo".to_string()],
                 new_lines: vec!["import foo".to_string(), "bar".to_string()],
                 is_end_of_file: false,
+                old_start: None,
             }],
         }]
     );
@@ -598,6 +702,7 @@ fn test_parse_patch_lenient() {
             old_lines: vec!["import foo".to_string()],
             new_lines: vec!["import foo".to_string(), "bar".to_string()],
             is_end_of_file: false,
+            old_start: None,
         }],
     }];
     let expected_error =
@@ -675,13 +780,67 @@ fn test_parse_one_hunk() {
         parse_one_hunk(&["bad"], 234),
         Err(InvalidHunkError {
             message: "'bad' is not a valid hunk header. \
-            Valid hunk headers: '*** Add File: {path}', '*** Delete File: {path}', '*** Update File: {path}'".to_string(),
+            Valid hunk headers: '*** Add File: {path}', '*** Delete File: {path}', '*** Update File: {path}', \
+            '*** Add Binary File: {path}', '*** Set Executable: {path}', '*** Clear Executable: {path}', \
+            '*** Add Symlink: {path}'".to_string(),
             line_number: 234
         })
     );
     // Other edge cases are already covered by tests above/below.
 }
 
+#[test]
+fn test_parse_binary_mode_and_symlink_hunks() {
+    let patch = "*** Begin Patch\n\
+        *** Add Binary File: logo.png\n\
+        +iVBORw0K\n\
+        +Ggo=\n\
+        *** Set Executable: build.sh\n\
+        *** Clear Executable: notes.txt\n\
+        *** Add Symlink: current\n\
+        +releases/v2\n\
+        *** End Patch";
+    assert_eq!(
+        parse_patch_text(patch, ParseMode::Strict).map(|args| args.hunks),
+        Ok(vec![
+            AddBinaryFile {
+                path: PathBuf::from("logo.png"),
+                contents: b"\x89PNG\r\n\x1a\n".to_vec(),
+            },
+            SetExecutable {
+                path: PathBuf::from("build.sh"),
+                executable: true,
+            },
+            SetExecutable {
+                path: PathBuf::from("notes.txt"),
+                executable: false,
+            },
+            AddSymlink {
+                path: PathBuf::from("current"),
+                target: PathBuf::from("releases/v2"),
+            },
+        ])
+    );
+
+    assert_eq!(
+        parse_one_hunk(&["*** Add Binary File: logo.png", "+not base64!"], 4),
+        Err(InvalidHunkError {
+            message:
+                "Invalid base64 contents for binary file 'logo.png': Invalid symbol 32, offset 3."
+                    .to_string(),
+            line_number: 4
+        })
+    );
+    assert_eq!(
+        parse_one_hunk(&["*** Add Symlink: current", "+a", "+b"], 7),
+        Err(InvalidHunkError {
+            message: "Symlink hunk for path 'current' must have exactly one '+' line with its target, found 2"
+                .to_string(),
+            line_number: 7
+        })
+    );
+}
+
 #[test]
 fn test_update_file_chunk() {
     assert_eq!(
@@ -743,7 +902,8 @@ fn test_update_file_chunk() {
                     "add".to_string(),
                     "context2".to_string()
                 ],
-                is_end_of_file: false
+                is_end_of_file: false,
+                old_start: None
             }),
             6
         ))
@@ -755,7 +915,8 @@ fn test_update_file_chunk() {
                 change_context: None,
                 old_lines: vec![],
                 new_lines: vec!["line".to_string()],
-                is_end_of_file: true
+                is_end_of_file: true,
+                old_start: None
             }),
             3
         ))
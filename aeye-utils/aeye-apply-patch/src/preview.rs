@@ -0,0 +1,306 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use similar::ChangeTag;
+use similar::TextDiff;
+
+use crate::AffectedPaths;
+use crate::ApplyPatchError;
+use crate::ApplyPatchOptions;
+use crate::Hunk;
+use crate::parse_patch;
+use crate::stage_hunk;
+use crate::transaction::Transaction;
+
+/// What applying a patch would do, computed without writing anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchPreview {
+    /// One entry per hunk, in patch order.
+    pub files: Vec<FilePreview>,
+}
+
+impl PatchPreview {
+    /// Line counts summed over every file.
+    pub fn stats(&self) -> LineStats {
+        self.files
+            .iter()
+            .fold(LineStats::default(), |total, file| LineStats {
+                added: total.added + file.stats.added,
+                removed: total.removed + file.stats.removed,
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewKind {
+    Add,
+    Delete,
+    Update,
+    /// An `Add Binary File` hunk, whose contents are not shown.
+    AddBinary,
+    SetExecutable {
+        executable: bool,
+    },
+    AddSymlink,
+}
+
+/// The effect of a single hunk on the file it targets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePreview {
+    pub kind: PreviewKind,
+    /// The file the hunk targets, resolved against `cwd`.
+    pub path: PathBuf,
+    /// Where an `Update File` hunk with `*** Move to:` moves the file.
+    pub move_path: Option<PathBuf>,
+    /// Contents before the hunk, or `None` if the file does not exist yet or
+    /// is not valid UTF-8.
+    pub old_content: Option<String>,
+    /// Contents after the hunk, or `None` if the file is deleted or is not
+    /// valid UTF-8. For a symlink, this is its target.
+    pub new_content: Option<String>,
+    pub unified_diff: String,
+    pub stats: LineStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineStats {
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Computes what applying `patch` in `cwd` would do, with one line of diff
+/// context, without touching disk. Fails exactly when applying the patch
+/// would fail before writing anything.
+pub fn preview_patch(patch: &str, cwd: &Path) -> Result<PatchPreview, ApplyPatchError> {
+    preview_patch_with_context(patch, cwd, 1)
+}
+
+pub fn preview_patch_with_context(
+    patch: &str,
+    cwd: &Path,
+    context: usize,
+) -> Result<PatchPreview, ApplyPatchError> {
+    let hunks = parse_patch(patch)?.hunks;
+    preview_hunks(&hunks, cwd, context, ApplyPatchOptions::default())
+}
+
+/// Like [`preview_patch_with_context`], for hunks that have already been
+/// parsed and with explicit [`ApplyPatchOptions`].
+pub fn preview_hunks(
+    hunks: &[Hunk],
+    cwd: &Path,
+    context: usize,
+    options: ApplyPatchOptions,
+) -> Result<PatchPreview, ApplyPatchError> {
+    let untouched = |message: String| ApplyPatchError::TreeUntouched {
+        message,
+        affected: AffectedPaths::default(),
+    };
+    if hunks.is_empty() {
+        return Err(untouched("No files were modified.".to_string()));
+    }
+
+    // Stage the hunks exactly as `apply_patch` would, so that a file touched
+    // by several hunks is previewed against its contents after the earlier
+    // ones.
+    let mut transaction = Transaction::default();
+    let mut affected = AffectedPaths::default();
+    let mut files = Vec::with_capacity(hunks.len());
+    for hunk in hunks {
+        let hunk = resolve_hunk(hunk.clone(), cwd);
+        let (kind, path, move_path) = match &hunk {
+            Hunk::AddFile { path, .. } => (PreviewKind::Add, path.clone(), None),
+            Hunk::DeleteFile { path } => (PreviewKind::Delete, path.clone(), None),
+            Hunk::UpdateFile {
+                path, move_path, ..
+            } => (PreviewKind::Update, path.clone(), move_path.clone()),
+            Hunk::AddBinaryFile { path, .. } => (PreviewKind::AddBinary, path.clone(), None),
+            Hunk::SetExecutable { path, executable } => (
+                PreviewKind::SetExecutable {
+                    executable: *executable,
+                },
+                path.clone(),
+                None,
+            ),
+            Hunk::AddSymlink { path, .. } => (PreviewKind::AddSymlink, path.clone(), None),
+        };
+        let old_content = current_contents(&transaction, &path);
+        stage_hunk(&mut transaction, &hunk, options, &mut affected).map_err(untouched)?;
+        let new_content = match &hunk {
+            Hunk::DeleteFile { .. } => None,
+            // Like git, show a symlink as its target rather than the contents
+            // of the file it points to.
+            Hunk::AddSymlink { target, .. } => Some(target.to_string_lossy().into_owned()),
+            _ => current_contents(&transaction, move_path.as_ref().unwrap_or(&path)),
+        };
+
+        let old = old_content.as_deref().unwrap_or_default();
+        let new = new_content.as_deref().unwrap_or_default();
+        let text_diff = TextDiff::from_lines(old, new);
+        let stats = text_diff
+            .iter_all_changes()
+            .fold(LineStats::default(), |stats, change| match change.tag() {
+                ChangeTag::Insert => LineStats {
+                    added: stats.added + 1,
+                    ..stats
+                },
+                ChangeTag::Delete => LineStats {
+                    removed: stats.removed + 1,
+                    ..stats
+                },
+                ChangeTag::Equal => stats,
+            });
+        files.push(FilePreview {
+            kind,
+            path,
+            move_path,
+            unified_diff: crate::unified_diff(&text_diff, context),
+            old_content,
+            new_content,
+            stats,
+        });
+    }
+    Ok(PatchPreview { files })
+}
+
+/// Resolves the paths in `hunk` against `cwd`. Symlink targets stay as
+/// written, relative to the link.
+pub fn resolve_hunk(hunk: Hunk, cwd: &Path) -> Hunk {
+    match hunk {
+        Hunk::AddFile { path, contents } => Hunk::AddFile {
+            path: cwd.join(path),
+            contents,
+        },
+        Hunk::DeleteFile { path } => Hunk::DeleteFile {
+            path: cwd.join(path),
+        },
+        Hunk::UpdateFile {
+            path,
+            move_path,
+            chunks,
+        } => Hunk::UpdateFile {
+            path: cwd.join(path),
+            move_path: move_path.map(|move_path| cwd.join(move_path)),
+            chunks,
+        },
+        Hunk::AddBinaryFile { path, contents } => Hunk::AddBinaryFile {
+            path: cwd.join(path),
+            contents,
+        },
+        Hunk::SetExecutable { path, executable } => Hunk::SetExecutable {
+            path: cwd.join(path),
+            executable,
+        },
+        Hunk::AddSymlink { path, target } => Hunk::AddSymlink {
+            path: cwd.join(path),
+            target,
+        },
+    }
+}
+
+/// The contents `path` would have at this point in the patch.
+fn current_contents(transaction: &Transaction, path: &Path) -> Option<String> {
+    let contents = transaction.read(path).ok().flatten()?;
+    String::from_utf8(contents).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn previews_every_hunk_without_touching_disk() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("modify.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.path().join("delete.txt"), "obsolete\n").unwrap();
+        let patch = "*** Begin Patch\n*** Add File: nested/new.txt\n+created\n*** Delete File: delete.txt\n*** Update File: modify.txt\n*** Move to: moved.txt\n@@\n one\n-two\n+TWO\n+2\n*** End Patch";
+
+        let preview = preview_patch(patch, dir.path()).unwrap();
+
+        assert_eq!(
+            preview.files,
+            vec![
+                FilePreview {
+                    kind: PreviewKind::Add,
+                    path: dir.path().join("nested/new.txt"),
+                    move_path: None,
+                    old_content: None,
+                    new_content: Some("created\n".to_string()),
+                    unified_diff: "@@ -0,0 +1 @@\n+created\n".to_string(),
+                    stats: LineStats {
+                        added: 1,
+                        removed: 0
+                    },
+                },
+                FilePreview {
+                    kind: PreviewKind::Delete,
+                    path: dir.path().join("delete.txt"),
+                    move_path: None,
+                    old_content: Some("obsolete\n".to_string()),
+                    new_content: None,
+                    unified_diff: "@@ -1 +0,0 @@\n-obsolete\n".to_string(),
+                    stats: LineStats {
+                        added: 0,
+                        removed: 1
+                    },
+                },
+                FilePreview {
+                    kind: PreviewKind::Update,
+                    path: dir.path().join("modify.txt"),
+                    move_path: Some(dir.path().join("moved.txt")),
+                    old_content: Some("one\ntwo\nthree\n".to_string()),
+                    new_content: Some("one\nTWO\n2\nthree\n".to_string()),
+                    unified_diff: "@@ -1,3 +1,4 @@\n one\n-two\n+TWO\n+2\n three\n".to_string(),
+                    stats: LineStats {
+                        added: 2,
+                        removed: 1
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            preview.stats(),
+            LineStats {
+                added: 3,
+                removed: 2
+            }
+        );
+        let mut entries: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec!["delete.txt", "modify.txt"]);
+    }
+
+    #[test]
+    fn later_hunks_see_earlier_ones() {
+        let dir = tempdir().unwrap();
+        let patch = "*** Begin Patch\n*** Add File: notes.txt\n+draft\n*** Update File: notes.txt\n@@\n-draft\n+final\n*** End Patch";
+
+        let preview = preview_patch(patch, dir.path()).unwrap();
+
+        assert_eq!(preview.files[1].old_content.as_deref(), Some("draft\n"));
+        assert_eq!(preview.files[1].new_content.as_deref(), Some("final\n"));
+        assert!(!dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn reports_the_error_apply_would_hit() {
+        let dir = tempdir().unwrap();
+        let patch = "*** Begin Patch\n*** Add File: created.txt\n+hello\n*** Delete File: missing.txt\n*** End Patch";
+
+        let err = preview_patch(patch, dir.path()).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "Failed to delete file {}",
+                dir.path().join("missing.txt").display()
+            )
+        );
+    }
+}
@@ -72,27 +72,6 @@ pub(crate) fn seek_sequence(
     // fuzzy behaviour of `git apply` which ignores minor byte-level
     // differences when locating context lines.
     // ------------------------------------------------------------------
-
-    fn normalise(s: &str) -> String {
-        s.trim()
-            .chars()
-            .map(|c| match c {
-                // Various dash / hyphen code-points → ASCII '-'
-                '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
-                | '\u{2212}' => '-',
-                // Fancy single quotes → '\''
-                '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
-                // Fancy double quotes → '"'
-                '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
-                // Non-breaking space and other odd spaces → normal space
-                '\u{00A0}' | '\u{2002}' | '\u{2003}' | '\u{2004}' | '\u{2005}' | '\u{2006}'
-                | '\u{2007}' | '\u{2008}' | '\u{2009}' | '\u{200A}' | '\u{202F}' | '\u{205F}'
-                | '\u{3000}' => ' ',
-                other => other,
-            })
-            .collect::<String>()
-    }
-
     for i in search_start..=lines.len().saturating_sub(pattern.len()) {
         let mut ok = true;
         for (p_idx, pat) in pattern.iter().enumerate() {
@@ -109,8 +88,145 @@ pub(crate) fn seek_sequence(
     None
 }
 
+fn normalise(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| match c {
+            // Various dash / hyphen code-points → ASCII '-'
+            '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
+            | '\u{2212}' => '-',
+            // Fancy single quotes → '\''
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+            // Fancy double quotes → '"'
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => '"',
+            // Non-breaking space and other odd spaces → normal space
+            '\u{00A0}' | '\u{2002}' | '\u{2003}' | '\u{2004}' | '\u{2005}' | '\u{2006}'
+            | '\u{2007}' | '\u{2008}' | '\u{2009}' | '\u{200A}' | '\u{202F}' | '\u{205F}'
+            | '\u{3000}' => ' ',
+            other => other,
+        })
+        .collect::<String>()
+}
+
+/// The region of a file that best matches a pattern which could not be found
+/// by [`seek_sequence`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct FuzzyMatch {
+    pub(crate) start: usize,
+    /// How closely the region matches, from 0.0 (nothing in common) to 1.0
+    /// (equal after normalisation): one minus the line-level edit distance
+    /// between the pattern and the region, divided by the pattern length.
+    pub(crate) confidence: f64,
+}
+
+/// Scores every `pattern.len()`-line region at or after `start` and returns
+/// the best one, preferring the end of the file when `eof` is set and the
+/// earliest region otherwise. The edit distance is computed over lines, where
+/// inserting or deleting a line costs 1 and substituting one costs how
+/// dissimilar the two lines are, so drift such as a renamed identifier in one
+/// context line or two swapped lines only lowers the score.
+pub(crate) fn fuzzy_seek_sequence(
+    lines: &[String],
+    pattern: &[String],
+    start: usize,
+    eof: bool,
+) -> Option<FuzzyMatch> {
+    if pattern.is_empty() || pattern.len() > lines.len() || start > lines.len() - pattern.len() {
+        return None;
+    }
+    let last = lines.len() - pattern.len();
+    let pattern: Vec<LineShape> = pattern.iter().map(|line| LineShape::new(line)).collect();
+    let candidates: Vec<LineShape> = lines[start..]
+        .iter()
+        .map(|line| LineShape::new(line))
+        .collect();
+    let similarity: Vec<Vec<f64>> = candidates
+        .iter()
+        .map(|line| pattern.iter().map(|pat| line.similarity(pat)).collect())
+        .collect();
+
+    let order = eof.then_some(last).into_iter().chain(start..=last);
+    let mut best: Option<FuzzyMatch> = None;
+    for window_start in order {
+        let window = &similarity[window_start - start..window_start - start + pattern.len()];
+        let confidence = 1.0 - line_edit_distance(window) / pattern.len() as f64;
+        if best.is_none_or(|best| confidence > best.confidence) {
+            best = Some(FuzzyMatch {
+                start: window_start,
+                confidence,
+            });
+        }
+    }
+    best
+}
+
+/// How similar two lines are, from 0.0 to 1.0, as scored by
+/// [`fuzzy_seek_sequence`].
+pub(crate) fn line_similarity(a: &str, b: &str) -> f64 {
+    LineShape::new(a).similarity(&LineShape::new(b))
+}
+
+/// Edit distance between a window of file lines and the pattern, given the
+/// similarity of each window line (rows) to each pattern line (columns).
+fn line_edit_distance(window: &[Vec<f64>]) -> f64 {
+    let pattern_len = window.first().map_or(0, Vec::len);
+    let mut previous: Vec<f64> = (0..=pattern_len).map(|j| j as f64).collect();
+    for (i, row) in window.iter().enumerate() {
+        let mut current = vec![(i + 1) as f64; pattern_len + 1];
+        for (j, similarity) in row.iter().enumerate() {
+            current[j + 1] = (previous[j] + 1.0 - similarity)
+                .min(previous[j + 1] + 1.0)
+                .min(current[j] + 1.0);
+        }
+        previous = current;
+    }
+    previous[pattern_len]
+}
+
+/// A normalised line and its sorted character bigrams, for comparing lines.
+struct LineShape {
+    text: String,
+    bigrams: Vec<(char, char)>,
+}
+
+impl LineShape {
+    fn new(line: &str) -> Self {
+        let text = normalise(line);
+        let chars: Vec<char> = text.chars().collect();
+        let mut bigrams: Vec<(char, char)> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+        bigrams.sort_unstable();
+        Self { text, bigrams }
+    }
+
+    /// Sørensen–Dice coefficient of the two lines' character bigrams.
+    fn similarity(&self, other: &LineShape) -> f64 {
+        if self.text == other.text {
+            return 1.0;
+        }
+        let total = self.bigrams.len() + other.bigrams.len();
+        if total == 0 {
+            return 0.0;
+        }
+        let (mut i, mut j, mut shared) = (0, 0, 0);
+        while i < self.bigrams.len() && j < other.bigrams.len() {
+            match self.bigrams[i].cmp(&other.bigrams[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        (2 * shared) as f64 / total as f64
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::FuzzyMatch;
+    use super::fuzzy_seek_sequence;
     use super::seek_sequence;
     use std::string::ToString;
 
@@ -148,4 +264,46 @@ mod tests {
         // Should not panic – must return None when pattern cannot possibly fit.
         assert_eq!(seek_sequence(&lines, &pattern, 0, false), None);
     }
+
+    #[test]
+    fn test_fuzzy_match_tolerates_a_renamed_identifier() {
+        let lines = to_vec(&[
+            "fn main() {",
+            "    let total = compute(input);",
+            "    println!(\"{total}\");",
+            "}",
+        ]);
+        let pattern = to_vec(&[
+            "fn main() {",
+            "    let sum = compute(input);",
+            "    println!(\"{total}\");",
+        ]);
+        assert_eq!(seek_sequence(&lines, &pattern, 0, false), None);
+        let found = fuzzy_seek_sequence(&lines, &pattern, 0, false).unwrap();
+        assert_eq!(found.start, 0);
+        assert!(found.confidence > 0.9, "{found:?}");
+    }
+
+    #[test]
+    fn test_fuzzy_match_scores_swapped_lines_lower() {
+        let lines = to_vec(&["alpha()", "beta()", "gamma()", "delta()"]);
+        let pattern = to_vec(&["beta()", "alpha()", "gamma()", "delta()"]);
+        let found = fuzzy_seek_sequence(&lines, &pattern, 0, false).unwrap();
+        assert_eq!(found.start, 0);
+        assert!((0.4..0.9).contains(&found.confidence), "{found:?}");
+    }
+
+    #[test]
+    fn test_fuzzy_match_respects_start() {
+        let lines = to_vec(&["same", "other", "same"]);
+        let pattern = to_vec(&["same"]);
+        assert_eq!(
+            fuzzy_seek_sequence(&lines, &pattern, 1, false),
+            Some(FuzzyMatch {
+                start: 2,
+                confidence: 1.0
+            })
+        );
+        assert_eq!(fuzzy_seek_sequence(&lines, &pattern, 3, false), None);
+    }
 }
@@ -1,6 +1,8 @@
 use std::io::Read;
 use std::io::Write;
 
+use crate::PreviewKind;
+
 pub fn main() -> ! {
     let exit_code = run_main();
     std::process::exit(exit_code);
@@ -10,9 +12,15 @@ pub fn main() -> ! {
 /// method is still a nightly API and we want main() to return !.
 pub fn run_main() -> i32 {
     // Expect either one argument (the full apply_patch payload) or read it from stdin.
-    let mut args = std::env::args_os();
+    let mut args = std::env::args_os().peekable();
     let _argv0 = args.next();
 
+    // `--dry-run` (or its alias `--check`) reports what the patch would do
+    // without writing anything.
+    let dry_run = args
+        .next_if(|arg| arg == "--dry-run" || arg == "--check")
+        .is_some();
+
     let patch_arg = match args.next() {
         Some(arg) => match arg.into_string() {
             Ok(s) => s,
@@ -27,7 +35,9 @@ pub fn run_main() -> i32 {
             match std::io::stdin().read_to_string(&mut buf) {
                 Ok(_) => {
                     if buf.is_empty() {
-                        eprintln!("Usage: apply_patch 'PATCH'\n       echo 'PATCH' | apply_patch");
+                        eprintln!(
+                            "Usage: apply_patch [--dry-run] 'PATCH'\n       echo 'PATCH' | apply_patch [--dry-run]"
+                        );
                         return 2;
                     }
                     buf
@@ -48,6 +58,15 @@ pub fn run_main() -> i32 {
 
     let mut stdout = std::io::stdout();
     let mut stderr = std::io::stderr();
+    if dry_run {
+        return match print_preview(&patch_arg, &mut stdout) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("{err}");
+                1
+            }
+        };
+    }
     match crate::apply_patch(&patch_arg, &mut stdout, &mut stderr) {
         Ok(()) => {
             // Flush to ensure output ordering when used in pipelines.
@@ -57,3 +76,44 @@ pub fn run_main() -> i32 {
         Err(_) => 1,
     }
 }
+
+/// Prints the summary `apply_patch` would print, with line counts, followed by
+/// the diff of each file.
+fn print_preview(patch: &str, out: &mut impl Write) -> anyhow::Result<()> {
+    // Resolving against an empty path keeps paths relative to the current
+    // directory, as `apply_patch` reports them.
+    let preview = crate::preview_patch_with_context(patch, std::path::Path::new(""), 3)?;
+
+    writeln!(out, "Dry run. The patch would update the following files:")?;
+    for file in &preview.files {
+        let (status, path) = match file.kind {
+            PreviewKind::Add | PreviewKind::AddBinary | PreviewKind::AddSymlink => {
+                ("A", &file.path)
+            }
+            PreviewKind::Delete => ("D", &file.path),
+            PreviewKind::Update | PreviewKind::SetExecutable { .. } => {
+                ("M", file.move_path.as_ref().unwrap_or(&file.path))
+            }
+        };
+        if file.kind == PreviewKind::AddBinary {
+            writeln!(out, "{status} {} (binary)", path.display())?;
+            continue;
+        }
+        writeln!(
+            out,
+            "{status} {} (+{} -{})",
+            path.display(),
+            file.stats.added,
+            file.stats.removed
+        )?;
+    }
+    for file in &preview.files {
+        let new_path = file.move_path.as_ref().unwrap_or(&file.path);
+        writeln!(out)?;
+        writeln!(out, "--- {}", file.path.display())?;
+        writeln!(out, "+++ {}", new_path.display())?;
+        write!(out, "{}", file.unified_diff)?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,373 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use tempfile::NamedTempFile;
+use tempfile::TempPath;
+
+/// The final state of every file a patch touches, staged in memory so that
+/// nothing is written until every hunk has been applied successfully.
+#[derive(Debug, Default)]
+pub(crate) struct Transaction {
+    /// Each touched path in the order it was first touched, with its staged
+    /// state.
+    changes: Vec<(PathBuf, Staged)>,
+}
+
+#[derive(Debug)]
+enum Staged {
+    Removed,
+    /// A regular file. `contents` is `None` when only the executable bits
+    /// change, and `executable` is `None` when they are left as they are.
+    File {
+        contents: Option<Vec<u8>>,
+        executable: Option<bool>,
+    },
+    Symlink(PathBuf),
+}
+
+/// A failed [`Transaction::commit`], after rollback has been attempted.
+#[derive(Debug)]
+pub(crate) struct CommitError {
+    pub(crate) message: String,
+    /// Paths that had already been written and were restored.
+    pub(crate) rolled_back: Vec<PathBuf>,
+    /// Paths whose previous state could not be restored.
+    pub(crate) unrestored: Vec<(PathBuf, io::Error)>,
+}
+
+impl Transaction {
+    fn staged(&self, path: &Path) -> Option<&Staged> {
+        self.changes
+            .iter()
+            .find(|(staged, _)| staged == path)
+            .map(|(_, state)| state)
+    }
+
+    /// The contents of `path`, taking earlier staged changes into account:
+    /// `Ok(None)` if it has been removed.
+    pub(crate) fn read(&self, path: &Path) -> io::Result<Option<Vec<u8>>> {
+        match self.staged(path) {
+            Some(Staged::Removed) => Ok(None),
+            Some(Staged::File {
+                contents: Some(contents),
+                ..
+            }) => Ok(Some(contents.clone())),
+            Some(Staged::Symlink(target)) => fs::read(parent_dir(path).join(target)).map(Some),
+            Some(Staged::File { contents: None, .. }) | None => fs::read(path).map(Some),
+        }
+    }
+
+    /// Whether `path` is a file, taking earlier staged changes into account.
+    pub(crate) fn is_file(&self, path: &Path) -> bool {
+        match self.staged(path) {
+            Some(Staged::Removed) => false,
+            Some(Staged::File { .. } | Staged::Symlink(_)) => true,
+            None => fs::symlink_metadata(path).is_ok_and(|metadata| !metadata.is_dir()),
+        }
+    }
+
+    /// Stages new contents for `path`, keeping any executable bit change
+    /// staged for it earlier.
+    pub(crate) fn write(&mut self, path: &Path, contents: Vec<u8>) {
+        let executable = match self.staged(path) {
+            Some(Staged::File { executable, .. }) => *executable,
+            _ => None,
+        };
+        self.stage(
+            path,
+            Staged::File {
+                contents: Some(contents),
+                executable,
+            },
+        );
+    }
+
+    pub(crate) fn set_executable(&mut self, path: &Path, executable: bool) {
+        let contents = match self.changes.iter_mut().find(|(staged, _)| staged == path) {
+            Some((_, Staged::File { contents, .. })) => contents.take(),
+            _ => None,
+        };
+        self.stage(
+            path,
+            Staged::File {
+                contents,
+                executable: Some(executable),
+            },
+        );
+    }
+
+    pub(crate) fn symlink(&mut self, path: &Path, target: PathBuf) {
+        self.stage(path, Staged::Symlink(target));
+    }
+
+    pub(crate) fn remove(&mut self, path: &Path) {
+        self.stage(path, Staged::Removed);
+    }
+
+    fn stage(&mut self, path: &Path, state: Staged) {
+        match self.changes.iter_mut().find(|(staged, _)| staged == path) {
+            Some((_, staged)) => *staged = state,
+            None => self.changes.push((path.to_path_buf(), state)),
+        }
+    }
+
+    /// Writes every staged change to disk. Each file is written to a temporary
+    /// file next to it and renamed into place, and each file it replaces or
+    /// removes is first moved aside, so that on failure every change made so
+    /// far can be undone.
+    pub(crate) fn commit(self) -> Result<(), CommitError> {
+        let mut journal = Journal::default();
+        for (path, state) in &self.changes {
+            let result = match state {
+                Staged::Removed => journal.remove(path),
+                Staged::File {
+                    contents: Some(contents),
+                    executable,
+                } => journal.write(path, contents, *executable),
+                Staged::File {
+                    contents: None,
+                    executable: Some(executable),
+                } => journal.set_executable(path, *executable),
+                Staged::File {
+                    contents: None,
+                    executable: None,
+                } => Ok(()),
+                Staged::Symlink(target) => journal.symlink(path, target),
+            };
+            if let Err(message) = result {
+                return Err(journal.roll_back(message));
+            }
+        }
+        // Dropping the journal deletes the files that were moved aside.
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Journal {
+    undo: Vec<Undo>,
+    created_dirs: Vec<PathBuf>,
+}
+
+enum Undo {
+    /// `path` did not exist before the commit.
+    Remove(PathBuf),
+    /// `path` was moved aside to `backup`.
+    Restore { path: PathBuf, backup: TempPath },
+    /// `path` had `permissions` before they were changed in place.
+    Permissions {
+        path: PathBuf,
+        permissions: fs::Permissions,
+    },
+}
+
+impl Journal {
+    fn write(
+        &mut self,
+        path: &Path,
+        contents: &[u8],
+        executable: Option<bool>,
+    ) -> Result<(), String> {
+        let failed = |err: io::Error| format!("Failed to write file {}: {err}", path.display());
+        // Writing through a symlink updates its target, as `fs::write` would.
+        let path = match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_symlink() => fs::canonicalize(path).map_err(failed)?,
+            _ => path.to_path_buf(),
+        };
+        self.create_parent_dirs(&path).map_err(|err| {
+            format!(
+                "Failed to create parent directories for {}: {err}",
+                path.display()
+            )
+        })?;
+
+        let mut temp = new_temp_file(parent_dir(&path)).map_err(failed)?;
+        temp.write_all(contents).map_err(failed)?;
+        let existing = fs::metadata(&path).ok();
+        let permissions = match &existing {
+            Some(metadata) => metadata.permissions(),
+            None => temp.as_file().metadata().map_err(failed)?.permissions(),
+        };
+        let permissions = match executable {
+            Some(executable) => with_executable(permissions, executable),
+            None => permissions,
+        };
+        temp.as_file()
+            .set_permissions(permissions)
+            .map_err(failed)?;
+        match existing {
+            Some(_) => self.move_aside(&path).map_err(failed)?,
+            None => self.undo.push(Undo::Remove(path.clone())),
+        }
+        temp.persist(&path).map_err(|err| failed(err.error))?;
+        Ok(())
+    }
+
+    fn set_executable(&mut self, path: &Path, executable: bool) -> Result<(), String> {
+        let failed = |err: io::Error| {
+            format!(
+                "Failed to change the mode of file {}: {err}",
+                path.display()
+            )
+        };
+        let permissions = fs::metadata(path).map_err(failed)?.permissions();
+        let updated = with_executable(permissions.clone(), executable);
+        if updated == permissions {
+            return Ok(());
+        }
+        fs::set_permissions(path, updated).map_err(failed)?;
+        self.undo.push(Undo::Permissions {
+            path: path.to_path_buf(),
+            permissions,
+        });
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path, target: &Path) -> Result<(), String> {
+        let failed = |err: &dyn std::fmt::Display| {
+            format!("Failed to create symlink {}: {err}", path.display())
+        };
+        self.create_parent_dirs(path).map_err(|err| {
+            format!(
+                "Failed to create parent directories for {}: {err}",
+                path.display()
+            )
+        })?;
+        match fs::symlink_metadata(path) {
+            Ok(_) => self.move_aside(path).map_err(|err| failed(&err))?,
+            Err(_) => self.undo.push(Undo::Remove(path.to_path_buf())),
+        }
+        // Windows needs to know whether the target is a directory.
+        let resolved_target = parent_dir(path).join(target);
+        aeye_git::create_symlink(&resolved_target, target, path).map_err(|err| failed(&err))
+    }
+
+    fn remove(&mut self, path: &Path) -> Result<(), String> {
+        match fs::symlink_metadata(path) {
+            Ok(_) => self
+                .move_aside(path)
+                .map_err(|err| format!("Failed to delete file {}: {err}", path.display())),
+            // Added and deleted again within the same patch.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("Failed to delete file {}: {err}", path.display())),
+        }
+    }
+
+    fn move_aside(&mut self, path: &Path) -> io::Result<()> {
+        let backup = tempfile::Builder::new()
+            .prefix(".apply-patch-")
+            .suffix(".orig")
+            .tempfile_in(parent_dir(path))?
+            .into_temp_path();
+        fs::rename(path, &backup)?;
+        self.undo.push(Undo::Restore {
+            path: path.to_path_buf(),
+            backup,
+        });
+        Ok(())
+    }
+
+    fn create_parent_dirs(&mut self, path: &Path) -> io::Result<()> {
+        let missing: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_os_str().is_empty() && fs::symlink_metadata(dir).is_err())
+            .collect();
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(dir)?;
+            self.created_dirs.push(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Undoes every change in reverse order.
+    fn roll_back(self, message: String) -> CommitError {
+        let mut rolled_back = Vec::new();
+        let mut unrestored = Vec::new();
+        for undo in self.undo.into_iter().rev() {
+            let (path, result) = match undo {
+                Undo::Remove(path) => {
+                    let result = match fs::remove_file(&path) {
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+                        result => result,
+                    };
+                    (path, result)
+                }
+                Undo::Restore { path, backup } => {
+                    let result = fs::rename(&backup, &path).map_err(|err| {
+                        let kept = backup.to_path_buf();
+                        // Never delete the only copy of the previous contents.
+                        let _ = backup.keep();
+                        io::Error::new(
+                            err.kind(),
+                            format!("{err}; previous contents kept at {}", kept.display()),
+                        )
+                    });
+                    (path, result)
+                }
+                Undo::Permissions { path, permissions } => {
+                    let result = fs::set_permissions(&path, permissions);
+                    (path, result)
+                }
+            };
+            match result {
+                Ok(()) if !rolled_back.contains(&path) => rolled_back.push(path),
+                Ok(()) => {}
+                Err(err) => unrestored.push((path, err)),
+            }
+        }
+        for dir in self.created_dirs.into_iter().rev() {
+            if let Err(err) = fs::remove_dir(&dir) {
+                unrestored.push((dir, err));
+            }
+        }
+        rolled_back.reverse();
+        CommitError {
+            message,
+            rolled_back,
+            unrestored,
+        }
+    }
+}
+
+/// A temporary file for new contents. Unlike [`NamedTempFile::new_in`], on
+/// Unix it is created with the permissions `fs::write` would give a new file.
+fn new_temp_file(dir: &Path) -> io::Result<NamedTempFile> {
+    let mut builder = tempfile::Builder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o666));
+    }
+    builder.tempfile_in(dir)
+}
+
+/// `permissions` with the executable bits set for everyone who may read the
+/// file, or cleared for everyone. Windows has no executable bits, so there
+/// this is a no-op.
+#[cfg(unix)]
+fn with_executable(permissions: fs::Permissions, executable: bool) -> fs::Permissions {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = permissions.mode();
+    let mode = if executable {
+        mode | (mode & 0o444) >> 2
+    } else {
+        mode & !0o111
+    };
+    fs::Permissions::from_mode(mode)
+}
+
+#[cfg(not(unix))]
+fn with_executable(permissions: fs::Permissions, _executable: bool) -> fs::Permissions {
+    permissions
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
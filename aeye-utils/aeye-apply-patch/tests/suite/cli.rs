@@ -89,3 +89,23 @@ fn test_apply_patch_cli_stdin_add_and_update() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_apply_patch_cli_dry_run_leaves_files_untouched() -> anyhow::Result<()> {
+    let tmp = tempdir()?;
+    let path = tmp.path().join("dry_run.txt");
+    fs::write(&path, "hello\n")?;
+
+    apply_patch_command()?
+        .arg("--dry-run")
+        .arg("*** Begin Patch\n*** Update File: dry_run.txt\n@@\n-hello\n+world\n*** End Patch")
+        .current_dir(tmp.path())
+        .assert()
+        .success()
+        .stdout(
+            "Dry run. The patch would update the following files:\nM dry_run.txt (+1 -1)\n\n--- dry_run.txt\n+++ dry_run.txt\n@@ -1 +1 @@\n-hello\n+world\n",
+        );
+    assert_eq!(fs::read_to_string(&path)?, "hello\n");
+
+    Ok(())
+}
@@ -9,6 +9,11 @@ use std::path::PathBuf;
 use serde_json;
 use thiserror::Error;
 
+use crate::decision::Decision;
+use crate::parser::PolicyParser;
+use crate::rule::PatternToken;
+use crate::rule::PrefixRule;
+
 #[derive(Debug, Error)]
 pub enum AmendError {
     #[error("prefix rule requires at least one token")]
@@ -52,6 +57,16 @@ pub enum AmendError {
         path: PathBuf,
         source: std::io::Error,
     },
+    #[error("justification cannot be empty")]
+    EmptyJustification,
+    #[error("failed to parse policy file {path}: {source}")]
+    ParsePolicyFile { path: PathBuf, source: crate::Error },
+    #[error("cannot safely edit policy file {path} at line {line}: {reason}")]
+    UnsafeEdit {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
 }
 
 /// Note this thread uses advisory file locking and performs blocking I/O, so it should be used with
@@ -60,6 +75,81 @@ pub fn blocking_append_allow_prefix_rule(
     policy_path: &Path,
     prefix: &[String],
 ) -> Result<(), AmendError> {
+    blocking_append_prefix_rule(policy_path, prefix, Decision::Allow, None)
+}
+
+/// Appends `prefix_rule(pattern=[...], decision="...")` to `policy_path`,
+/// creating the file if needed. An identical line already in the file is not
+/// appended again.
+pub fn blocking_append_prefix_rule(
+    policy_path: &Path,
+    prefix: &[String],
+    decision: Decision,
+    justification: Option<&str>,
+) -> Result<(), AmendError> {
+    let rule = format_prefix_rule(prefix, decision, justification)?;
+    create_policy_dir(policy_path)?;
+    append_locked_line(policy_path, &rule)
+}
+
+/// Removes every `prefix_rule` whose pattern is exactly the literal `prefix`
+/// and that has no flag, context or profile constraints, optionally only those with
+/// `decision`. Returns how many rules were removed; a missing file counts as
+/// zero.
+///
+/// Only the lines spanned by each removed call are dropped, so comments and
+/// other rules are left as written. The edit is refused with
+/// [`AmendError::UnsafeEdit`] when a matching call does not start its own line
+/// or shares it with other rules.
+pub fn blocking_remove_prefix_rule(
+    policy_path: &Path,
+    prefix: &[String],
+    decision: Option<Decision>,
+) -> Result<usize, AmendError> {
+    if prefix.is_empty() {
+        return Err(AmendError::EmptyPrefix);
+    }
+    if !policy_path.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    rewrite_locked(policy_path, |contents| {
+        let (contents, count) = remove_prefix_rules(policy_path, contents, prefix, decision)?;
+        removed = count;
+        Ok(contents)
+    })?;
+    Ok(removed)
+}
+
+/// Replaces the rules [`blocking_remove_prefix_rule`] would remove for
+/// `prefix`, whatever their decision, with a single rule for `decision`.
+/// Both steps happen under one lock, e.g. to turn an earlier allow into a
+/// forbid.
+pub fn blocking_set_prefix_rule(
+    policy_path: &Path,
+    prefix: &[String],
+    decision: Decision,
+    justification: Option<&str>,
+) -> Result<(), AmendError> {
+    let rule = format_prefix_rule(prefix, decision, justification)?;
+    create_policy_dir(policy_path)?;
+    rewrite_locked(policy_path, |contents| {
+        let (mut contents, _) = remove_prefix_rules(policy_path, contents, prefix, None)?;
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(&rule);
+        contents.push('\n');
+        Ok(contents)
+    })
+}
+
+fn format_prefix_rule(
+    prefix: &[String],
+    decision: Decision,
+    justification: Option<&str>,
+) -> Result<String, AmendError> {
     if prefix.is_empty() {
         return Err(AmendError::EmptyPrefix);
     }
@@ -70,27 +160,37 @@ pub fn blocking_append_allow_prefix_rule(
         .collect::<Result<Vec<_>, _>>()
         .map_err(|source| AmendError::SerializePrefix { source })?;
     let pattern = format!("[{}]", tokens.join(", "));
-    let rule = format!(r#"prefix_rule(pattern={pattern}, decision="allow")"#);
+    let decision = decision.as_str();
+    let mut rule = format!(r#"prefix_rule(pattern={pattern}, decision="{decision}""#);
+    if let Some(justification) = justification {
+        if justification.trim().is_empty() {
+            return Err(AmendError::EmptyJustification);
+        }
+        let justification = serde_json::to_string(justification)
+            .map_err(|source| AmendError::SerializePrefix { source })?;
+        rule.push_str(&format!(", justification={justification}"));
+    }
+    rule.push(')');
+    Ok(rule)
+}
 
+fn create_policy_dir(policy_path: &Path) -> Result<(), AmendError> {
     let dir = policy_path
         .parent()
         .ok_or_else(|| AmendError::MissingParent {
             path: policy_path.to_path_buf(),
         })?;
     match std::fs::create_dir(dir) {
-        Ok(()) => {}
-        Err(ref source) if source.kind() == std::io::ErrorKind::AlreadyExists => {}
-        Err(source) => {
-            return Err(AmendError::CreatePolicyDir {
-                dir: dir.to_path_buf(),
-                source,
-            });
-        }
+        Ok(()) => Ok(()),
+        Err(ref source) if source.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(source) => Err(AmendError::CreatePolicyDir {
+            dir: dir.to_path_buf(),
+            source,
+        }),
     }
-    append_locked_line(policy_path, &rule)
 }
 
-fn append_locked_line(policy_path: &Path, line: &str) -> Result<(), AmendError> {
+fn open_locked(policy_path: &Path) -> Result<(std::fs::File, String), AmendError> {
     let mut file = OpenOptions::new()
         .create(true)
         .read(true)
@@ -116,6 +216,11 @@ fn append_locked_line(policy_path: &Path, line: &str) -> Result<(), AmendError>
             path: policy_path.to_path_buf(),
             source,
         })?;
+    Ok((file, contents))
+}
+
+fn append_locked_line(policy_path: &Path, line: &str) -> Result<(), AmendError> {
+    let (mut file, contents) = open_locked(policy_path)?;
 
     if contents.lines().any(|existing| existing == line) {
         return Ok(());
@@ -138,6 +243,140 @@ fn append_locked_line(policy_path: &Path, line: &str) -> Result<(), AmendError>
     Ok(())
 }
 
+/// Replaces the file's contents with `edit(contents)` while holding the lock.
+/// The file is left untouched if `edit` fails or changes nothing.
+fn rewrite_locked(
+    policy_path: &Path,
+    edit: impl FnOnce(&str) -> Result<String, AmendError>,
+) -> Result<(), AmendError> {
+    let (mut file, contents) = open_locked(policy_path)?;
+    let updated = edit(&contents)?;
+    if updated == contents {
+        return Ok(());
+    }
+
+    // The file is opened in append mode, so after truncation every write lands
+    // at the (new) end.
+    file.set_len(0)
+        .and_then(|()| file.write_all(updated.as_bytes()))
+        .map_err(|source| AmendError::WritePolicyFile {
+            path: policy_path.to_path_buf(),
+            source,
+        })
+}
+
+fn remove_prefix_rules(
+    policy_path: &Path,
+    contents: &str,
+    prefix: &[String],
+    decision: Option<Decision>,
+) -> Result<(String, usize), AmendError> {
+    let before = parse_prefix_rules(policy_path, contents)?;
+    let mut spans: Vec<(usize, usize)> = before
+        .iter()
+        .filter(|rule| is_exact_prefix_rule(rule, prefix))
+        .filter(|rule| decision.is_none_or(|decision| rule.decision == decision))
+        .filter_map(|rule| rule.source.as_ref())
+        .map(|source| (source.line, source.end_line))
+        .collect();
+    spans.sort_unstable();
+    spans.dedup();
+    if spans.is_empty() {
+        return Ok((contents.to_string(), 0));
+    }
+
+    let lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    for &(line, _) in &spans {
+        let starts_line = lines
+            .get(line - 1)
+            .is_some_and(|text| text.starts_with("prefix_rule("));
+        if !starts_line {
+            return Err(AmendError::UnsafeEdit {
+                path: policy_path.to_path_buf(),
+                line,
+                reason: "the rule is not a top-level `prefix_rule(` call at the start of its line"
+                    .to_string(),
+            });
+        }
+    }
+    let updated: String = lines
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            let line = index + 1;
+            !spans
+                .iter()
+                .any(|&(start, end)| (start..=end).contains(&line))
+        })
+        .map(|(_, text)| *text)
+        .collect();
+
+    // Dropping whole lines also drops anything sharing them, such as a second
+    // call after a `;` or the other alternatives of `["git", "jj"]`.
+    let removed = before.len() - parse_prefix_rules(policy_path, &updated)?.len();
+    let expected = before
+        .iter()
+        .filter(|rule| {
+            rule.source
+                .as_ref()
+                .is_some_and(|source| spans.contains(&(source.line, source.end_line)))
+        })
+        .count();
+    let matched = before
+        .iter()
+        .filter(|rule| is_exact_prefix_rule(rule, prefix))
+        .filter(|rule| decision.is_none_or(|decision| rule.decision == decision))
+        .count();
+    if removed != expected || expected != matched {
+        return Err(AmendError::UnsafeEdit {
+            path: policy_path.to_path_buf(),
+            line: spans[0].0,
+            reason: "the lines holding the rule also define other rules".to_string(),
+        });
+    }
+    Ok((updated, matched))
+}
+
+/// The prefix rules defined in `policy_path` itself, leaving out any it
+/// pulls in with `load()` or `include()`.
+fn parse_prefix_rules(policy_path: &Path, contents: &str) -> Result<Vec<PrefixRule>, AmendError> {
+    let identifier = policy_path.display().to_string();
+    let mut parser = PolicyParser::new();
+    parser
+        .parse(&identifier, contents)
+        .map_err(|source| AmendError::ParsePolicyFile {
+            path: policy_path.to_path_buf(),
+            source,
+        })?;
+    let policy = parser.build();
+    Ok(policy
+        .rules()
+        .iter_all()
+        .flat_map(|(_, rules)| rules)
+        .filter_map(|rule| rule.as_any().downcast_ref::<PrefixRule>())
+        .filter(|rule| {
+            rule.source
+                .as_ref()
+                .is_some_and(|source| source.path == identifier)
+        })
+        .cloned()
+        .collect())
+}
+
+fn is_exact_prefix_rule(rule: &PrefixRule, prefix: &[String]) -> bool {
+    rule.constraints.is_empty()
+        && rule.context.is_empty()
+        && rule.profile.is_none()
+        && rule.pattern.first.as_ref() == prefix[0]
+        && rule.pattern.rest.len() == prefix.len() - 1
+        && rule
+            .pattern
+            .rest
+            .iter()
+            .zip(&prefix[1..])
+            .all(|(token, expected)| matches!(token, PatternToken::Single(literal) if literal == expected))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,4 +454,118 @@ prefix_rule(pattern=["echo", "Hello, world!"], decision="allow")
 "#
         );
     }
+
+    #[test]
+    fn appends_any_decision_with_justification() {
+        let tmp = tempdir().expect("create temp dir");
+        let policy_path = tmp.path().join("rules").join("default.rules");
+
+        blocking_append_prefix_rule(
+            &policy_path,
+            &[String::from("rm"), String::from("-rf")],
+            Decision::Forbidden,
+            Some(r#"use "trash" instead"#),
+        )
+        .expect("append rule");
+
+        let contents = std::fs::read_to_string(&policy_path).expect("read policy");
+        assert_eq!(
+            contents,
+            r#"prefix_rule(pattern=["rm", "-rf"], decision="forbidden", justification="use \"trash\" instead")
+"#
+        );
+        assert!(matches!(
+            blocking_append_prefix_rule(
+                &policy_path,
+                &[String::from("ls")],
+                Decision::Prompt,
+                Some(" ")
+            ),
+            Err(AmendError::EmptyJustification)
+        ));
+    }
+
+    #[test]
+    fn removes_matching_rules_and_keeps_comments() {
+        let tmp = tempdir().expect("create temp dir");
+        let policy_path = tmp.path().join("default.rules");
+        std::fs::write(
+            &policy_path,
+            r#"# Reviewed by the platform team.
+prefix_rule(pattern=["git", "status"], decision="allow")
+prefix_rule(
+    pattern = ["git", "push"],
+    decision = "allow",
+)
+# Pushes with --force stay blocked.
+prefix_rule(pattern=["git", "push"], decision="forbidden", flags_present=["--force"])
+"#,
+        )
+        .expect("write seed rules");
+
+        let removed = blocking_remove_prefix_rule(
+            &policy_path,
+            &[String::from("git"), String::from("push")],
+            Some(Decision::Allow),
+        )
+        .expect("remove rule");
+
+        assert_eq!(removed, 1);
+        let contents = std::fs::read_to_string(&policy_path).expect("read policy");
+        assert_eq!(
+            contents,
+            r#"# Reviewed by the platform team.
+prefix_rule(pattern=["git", "status"], decision="allow")
+# Pushes with --force stay blocked.
+prefix_rule(pattern=["git", "push"], decision="forbidden", flags_present=["--force"])
+"#
+        );
+    }
+
+    #[test]
+    fn set_replaces_earlier_decision() {
+        let tmp = tempdir().expect("create temp dir");
+        let policy_path = tmp.path().join("default.rules");
+        std::fs::write(
+            &policy_path,
+            r#"prefix_rule(pattern=["curl"], decision="allow")
+prefix_rule(pattern=["ls"], decision="allow")"#,
+        )
+        .expect("write seed rules");
+
+        blocking_set_prefix_rule(
+            &policy_path,
+            &[String::from("curl")],
+            Decision::Prompt,
+            Some("network access"),
+        )
+        .expect("set rule");
+
+        let contents = std::fs::read_to_string(&policy_path).expect("read policy");
+        assert_eq!(
+            contents,
+            r#"prefix_rule(pattern=["ls"], decision="allow")
+prefix_rule(pattern=["curl"], decision="prompt", justification="network access")
+"#
+        );
+    }
+
+    #[test]
+    fn refuses_to_remove_rules_sharing_a_line() {
+        let tmp = tempdir().expect("create temp dir");
+        let policy_path = tmp.path().join("default.rules");
+        let seed = r#"prefix_rule(pattern=[["git", "jj"], "status"], decision="allow")
+prefix_rule(pattern=["ls"]); prefix_rule(pattern=["pwd"])
+"#;
+        std::fs::write(&policy_path, seed).expect("write seed rules");
+
+        for prefix in [["git", "status"].as_slice(), ["ls"].as_slice()] {
+            let prefix: Vec<String> = prefix.iter().map(ToString::to_string).collect();
+            let err = blocking_remove_prefix_rule(&policy_path, &prefix, None)
+                .expect_err("removal should be refused");
+            assert!(matches!(err, AmendError::UnsafeEdit { .. }), "{err}");
+        }
+        let contents = std::fs::read_to_string(&policy_path).expect("read policy");
+        assert_eq!(contents, seed);
+    }
 }
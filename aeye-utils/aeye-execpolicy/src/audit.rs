@@ -0,0 +1,590 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use clap::Parser;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::context::EvaluationContext;
+use crate::decision::Decision;
+use crate::execpolicycheck::load_policies;
+use crate::policy::Evaluation;
+use crate::policy::Policy;
+use crate::rule::RuleMatch;
+
+/// Arguments for replaying audit logs against a new version of a policy.
+#[derive(Debug, Parser, Clone)]
+pub struct ExecPolicyReplayCommand {
+    /// Paths to the execpolicy rule files to replay against (repeatable).
+    #[arg(short = 'r', long = "rules", value_name = "PATH", required = true)]
+    pub rules: Vec<PathBuf>,
+
+    /// Exit non-zero if any decision would change or could not be checked.
+    #[arg(long)]
+    pub fail_on_change: bool,
+
+    /// JSONL audit logs to replay (repeatable).
+    #[arg(value_name = "LOG", required = true)]
+    pub logs: Vec<PathBuf>,
+}
+
+impl ExecPolicyReplayCommand {
+    /// Print one line per changed decision, then a summary.
+    pub fn run(&self) -> Result<()> {
+        let policy = load_policies(&self.rules)?;
+
+        let mut total = 0;
+        let mut changes = Vec::new();
+        for log_path in &self.logs {
+            let entries = AuditLog::new(log_path).read()?;
+            total += entries.len();
+            for change in replay_audit_log(&policy, &entries) {
+                println!("{}: {change}", log_path.display());
+                changes.push(change);
+            }
+        }
+        let changed = changes.iter().filter(|change| change.is_change()).count();
+        let stricter = changes.iter().filter(|change| change.is_stricter()).count();
+        let unchecked = changes
+            .iter()
+            .filter(|change| change.is_unchecked())
+            .count();
+        println!(
+            "{changed} of {total} decisions would change; {stricter} stricter, {} more permissive; {unchecked} lack variables the policy tests",
+            changed - stricter
+        );
+
+        if self.fail_on_change && !changes.is_empty() {
+            anyhow::bail!("{changed} decisions would change and {unchecked} could not be checked")
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("failed to create audit log directory {dir}: {source}")]
+    CreateLogDir {
+        dir: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to open audit log {path}: {source}")]
+    OpenLog {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to lock audit log {path}: {source}")]
+    LockLog {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to write to audit log {path}: {source}")]
+    WriteLog {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to read audit log {path}: {source}")]
+    ReadLog {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to serialize audit record: {source}")]
+    SerializeRecord { source: serde_json::Error },
+    #[error("invalid audit record at {path}:{line}: {source}")]
+    ParseRecord {
+        path: PathBuf,
+        line: usize,
+        source: serde_json::Error,
+    },
+}
+
+/// What was checked: a command's argv, or a bash script passed to
+/// [`Policy::check_shell`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditedCommand {
+    Argv(Vec<String>),
+    Shell(String),
+}
+
+impl fmt::Display for AuditedCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Argv(argv) => f.write_str(
+                &shlex::try_join(argv.iter().map(String::as_str))
+                    .unwrap_or_else(|_| format!("{argv:?}")),
+            ),
+            Self::Shell(script) => f.write_str(script),
+        }
+    }
+}
+
+/// How the user answered the approval prompt for a command. Mirrors the
+/// protocol's `ReviewDecision`, which converts into it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalOutcome {
+    Approved,
+    /// Approved, adding an allow rule for `prefix` to the policy.
+    ApprovedExecpolicyAmendment {
+        prefix: Vec<String>,
+    },
+    ApprovedForSession,
+    Denied,
+    Abort,
+}
+
+impl ApprovalOutcome {
+    pub fn is_approved(&self) -> bool {
+        matches!(
+            self,
+            Self::Approved | Self::ApprovedExecpolicyAmendment { .. } | Self::ApprovedForSession
+        )
+    }
+}
+
+/// One policy evaluation, as stored on one line of an audit log.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub command: AuditedCommand,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Only `PATH` and the variables named by the `env` conditions of the
+    /// policy in force when the record was made, so no other secrets are
+    /// logged. A newer policy may test variables that were not kept.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// The names `env` was filtered to, including variables that were unset.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub captured_env: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub matched_rules: Vec<RuleMatch>,
+    pub decision: Decision,
+    /// `None` when the user was not asked, or has not answered yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<ApprovalOutcome>,
+}
+
+impl AuditRecord {
+    pub fn new(
+        policy: &Policy,
+        command: AuditedCommand,
+        context: &EvaluationContext,
+        evaluation: &Evaluation,
+    ) -> Self {
+        let mut captured_env: BTreeSet<String> =
+            policy.env_names().into_iter().map(str::to_string).collect();
+        captured_env.insert("PATH".to_string());
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX))
+            .unwrap_or_default();
+        Self {
+            timestamp_ms,
+            command,
+            cwd: context.cwd.clone(),
+            env: context
+                .env
+                .iter()
+                .filter(|(name, _)| captured_env.contains(name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            captured_env,
+            profile: context.profile.clone(),
+            matched_rules: evaluation.matched_rules.clone(),
+            decision: evaluation.decision,
+            outcome: None,
+        }
+    }
+
+    pub fn with_outcome(mut self, outcome: ApprovalOutcome) -> Self {
+        self.outcome = Some(outcome);
+        self
+    }
+
+    pub fn context(&self) -> EvaluationContext {
+        EvaluationContext {
+            cwd: self.cwd.clone(),
+            env: self.env.clone().into_iter().collect(),
+            profile: self.profile.clone(),
+        }
+    }
+
+    /// The decision reached by policy rules alone, leaving out any
+    /// heuristics fallback; `None` if no rule matched.
+    pub fn rule_decision(&self) -> Option<Decision> {
+        rule_decision(&self.matched_rules)
+    }
+
+    /// The variables that `policy`'s rules for this command test but whose
+    /// value this record did not keep. Records written before `captured_env`
+    /// existed only vouch for the variables they hold.
+    pub fn uncaptured_env(&self, policy: &Policy) -> Vec<String> {
+        let context = self.context();
+        let env_names = match &self.command {
+            AuditedCommand::Argv(argv) => policy.env_names_for_command(argv, &context),
+            AuditedCommand::Shell(script) => policy.env_names_for_shell(script, &context),
+        };
+        env_names
+            .into_iter()
+            .filter(|name| !self.captured_env.contains(*name) && !self.env.contains_key(*name))
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+fn rule_decision(matched_rules: &[RuleMatch]) -> Option<Decision> {
+    matched_rules
+        .iter()
+        .filter(|rule_match| !matches!(rule_match, RuleMatch::HeuristicsRuleMatch { .. }))
+        .map(RuleMatch::decision)
+        .max()
+}
+
+/// An append-only JSONL file of [`AuditRecord`]s.
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `record` as one line under an advisory file lock, creating the
+    /// log and its directory if needed. Performs blocking I/O; use
+    /// [`tokio::task::spawn_blocking`] from async code.
+    pub fn blocking_append(&self, record: &AuditRecord) -> Result<(), AuditError> {
+        let mut line = serde_json::to_string(record)
+            .map_err(|source| AuditError::SerializeRecord { source })?;
+        line.push('\n');
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|source| AuditError::CreateLogDir {
+                dir: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|source| AuditError::OpenLog {
+                path: self.path.clone(),
+                source,
+            })?;
+        file.lock().map_err(|source| AuditError::LockLog {
+            path: self.path.clone(),
+            source,
+        })?;
+        // One write per record, so concurrent writers never interleave lines.
+        file.write_all(line.as_bytes())
+            .map_err(|source| AuditError::WriteLog {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    /// Reads every record, in the order they were appended. Blank lines are
+    /// skipped.
+    pub fn read(&self) -> Result<Vec<AuditEntry>, AuditError> {
+        let contents = fs::read_to_string(&self.path).map_err(|source| AuditError::ReadLog {
+            path: self.path.clone(),
+            source,
+        })?;
+        contents
+            .lines()
+            .enumerate()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(index, text)| {
+                let record =
+                    serde_json::from_str(text).map_err(|source| AuditError::ParseRecord {
+                        path: self.path.clone(),
+                        line: index + 1,
+                        source,
+                    })?;
+                Ok(AuditEntry {
+                    line: index + 1,
+                    record,
+                })
+            })
+            .collect()
+    }
+}
+
+/// A record read back from an audit log.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuditEntry {
+    /// 1-based.
+    pub line: usize,
+    pub record: AuditRecord,
+}
+
+/// A recorded evaluation whose decision differs under another policy, or
+/// cannot be checked because the policy tests variables the record lacks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecisionChange {
+    pub entry: AuditEntry,
+    /// `None` means no rule matched.
+    pub before: Option<Decision>,
+    /// Computed as if every variable in `uncaptured_env` were unset.
+    pub after: Option<Decision>,
+    /// The rules that matched under the new policy.
+    pub matched_rules: Vec<RuleMatch>,
+    /// Variables the new policy tests that were not recorded.
+    pub uncaptured_env: Vec<String>,
+}
+
+impl DecisionChange {
+    /// Whether the decision differs; `false` for a record that is only
+    /// reported because of `uncaptured_env`.
+    pub fn is_change(&self) -> bool {
+        self.before != self.after
+    }
+
+    /// Whether the new decision is uncertain because of `uncaptured_env`.
+    pub fn is_unchecked(&self) -> bool {
+        !self.uncaptured_env.is_empty()
+    }
+
+    /// Whether the new policy is stricter for this command. No match counts as
+    /// more permissive than any decision.
+    pub fn is_stricter(&self) -> bool {
+        self.after > self.before
+    }
+}
+
+impl fmt::Display for DecisionChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let render = |decision: Option<Decision>| decision.map_or("no match", Decision::as_str);
+        write!(
+            f,
+            "line {}: `{}`: {} -> {}",
+            self.entry.line,
+            self.entry.record.command,
+            render(self.before),
+            render(self.after)
+        )?;
+        if let Some(outcome) = &self.entry.record.outcome {
+            let answer = if outcome.is_approved() {
+                "approved"
+            } else {
+                "denied"
+            };
+            write!(f, " (user {answer})")?;
+        }
+        if self.is_unchecked() {
+            write!(
+                f,
+                " (not recorded: {}; replayed as unset)",
+                self.uncaptured_env.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Re-evaluates each recorded command against `policy` in its recorded
+/// context and returns those whose decision would change, along with those
+/// that cannot be checked because `policy` tests variables the record did not
+/// keep. Decisions are compared on policy rules alone, since the heuristics
+/// fallback is not part of the policy.
+pub fn replay_audit_log(policy: &Policy, entries: &[AuditEntry]) -> Vec<DecisionChange> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let context = entry.record.context();
+            let matched_rules = match &entry.record.command {
+                AuditedCommand::Argv(argv) => policy.matches_for_command(argv, &context, None),
+                AuditedCommand::Shell(script) => policy.matches_for_shell(script, &context, None),
+            };
+            let change = DecisionChange {
+                entry: entry.clone(),
+                before: entry.record.rule_decision(),
+                after: rule_decision(&matched_rules),
+                matched_rules,
+                uncaptured_env: entry.record.uncaptured_env(policy),
+            };
+            (change.is_change() || change.is_unchecked()).then_some(change)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PolicyParser;
+    use pretty_assertions::assert_eq;
+
+    fn policy(src: &str) -> Policy {
+        let mut parser = PolicyParser::new();
+        parser.parse("test.rules", src).unwrap();
+        parser.build()
+    }
+
+    fn tokens(cmd: &str) -> Vec<String> {
+        cmd.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn records_round_trip_through_the_log() {
+        let policy = policy(
+            r#"prefix_rule(pattern = ["aws"], decision = "prompt", env = {"AWS_PROFILE": "prod"})"#,
+        );
+        let context = EvaluationContext {
+            cwd: Some(".".into()),
+            env: [
+                ("AWS_PROFILE".to_string(), "prod".to_string()),
+                ("AWS_SECRET_ACCESS_KEY".to_string(), "secret".to_string()),
+            ]
+            .into(),
+            profile: None,
+        };
+        let command = tokens("aws s3 ls");
+        let evaluation = policy.check(&command, &context, &|_: &[String]| Decision::Allow);
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(dir.path().join("audit/policy.jsonl"));
+        let record = AuditRecord::new(
+            &policy,
+            AuditedCommand::Argv(command),
+            &context,
+            &evaluation,
+        )
+        .with_outcome(ApprovalOutcome::Approved);
+        log.blocking_append(&record).unwrap();
+        log.blocking_append(&record).unwrap();
+
+        let entries = log.read().unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.line).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(entries[0].record, record);
+        assert_eq!(
+            record.env,
+            BTreeMap::from([("AWS_PROFILE".to_string(), "prod".to_string())])
+        );
+        assert_eq!(record.rule_decision(), Some(Decision::Prompt));
+    }
+
+    #[test]
+    fn replay_reports_changed_decisions() {
+        let old = policy(
+            r#"
+prefix_rule(pattern = ["git", "push"], decision = "prompt")
+prefix_rule(pattern = ["git", "status"])
+"#,
+        );
+        let new = policy(
+            r#"
+prefix_rule(pattern = ["git", "push"], decision = "forbidden")
+prefix_rule(pattern = ["git", "status"])
+prefix_rule(pattern = ["rm"], decision = "prompt")
+"#,
+        );
+        let context = EvaluationContext::default();
+        let record = |command: AuditedCommand| {
+            let evaluation = match &command {
+                AuditedCommand::Argv(argv) => {
+                    old.check(argv, &context, &|_: &[String]| Decision::Allow)
+                }
+                AuditedCommand::Shell(script) => {
+                    old.check_shell(script, &context, &|_: &[String]| Decision::Allow)
+                }
+            };
+            AuditRecord::new(&old, command, &context, &evaluation)
+        };
+        let entries: Vec<AuditEntry> = [
+            record(AuditedCommand::Argv(tokens("git push origin")))
+                .with_outcome(ApprovalOutcome::Approved),
+            record(AuditedCommand::Argv(tokens("git status"))),
+            record(AuditedCommand::Shell("ls && rm -rf target".to_string())),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| AuditEntry {
+            line: index + 1,
+            record,
+        })
+        .collect();
+
+        let changes = replay_audit_log(&new, &entries);
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "line 1: `git push origin`: prompt -> forbidden (user approved)",
+                "line 3: `ls && rm -rf target`: no match -> prompt",
+            ]
+        );
+        assert!(changes.iter().all(DecisionChange::is_stricter));
+    }
+
+    #[test]
+    fn replay_reports_records_missing_variables_the_policy_tests() {
+        let old = policy(
+            r#"
+prefix_rule(pattern = ["aws"], decision = "prompt")
+prefix_rule(pattern = ["gcloud"], decision = "prompt", env = {"CLOUDSDK_CONFIG": "prod"})
+"#,
+        );
+        let new = policy(
+            r#"
+prefix_rule(pattern = ["aws"], decision = "prompt")
+prefix_rule(pattern = ["aws"], decision = "forbidden", env = {"AWS_PROFILE": "prod"})
+prefix_rule(pattern = ["gcloud"], decision = "prompt", env = {"CLOUDSDK_CONFIG": "prod"})
+"#,
+        );
+        let context = EvaluationContext {
+            env: [("AWS_PROFILE".to_string(), "prod".to_string())].into(),
+            ..EvaluationContext::default()
+        };
+        let entries: Vec<AuditEntry> = ["aws s3 ls", "gcloud auth list"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, command)| {
+                let evaluation =
+                    old.check(&tokens(command), &context, &|_: &[String]| Decision::Allow);
+                AuditEntry {
+                    line: index + 1,
+                    record: AuditRecord::new(
+                        &old,
+                        AuditedCommand::Argv(tokens(command)),
+                        &context,
+                        &evaluation,
+                    ),
+                }
+            })
+            .collect();
+        assert!(entries[0].record.env.is_empty());
+
+        let changes = replay_audit_log(&new, &entries);
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "line 1: `aws s3 ls`: prompt -> prompt (not recorded: AWS_PROFILE; replayed as unset)"
+            ]
+        );
+        assert!(!changes[0].is_change());
+        assert!(changes[0].is_unchecked());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::error::Error;
+use crate::error::Result;
+use crate::matcher::GlobPattern;
+use crate::matcher::normalize_relative;
+use crate::policy::render_pattern_token;
+use crate::rule::PatternToken;
+
+/// Where and how a command is about to run.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct EvaluationContext {
+    /// The working directory, relative to the workspace root (`.` for the
+    /// root itself). `None` when unknown, in which case no `cwd` condition
+    /// is satisfied.
+    pub cwd: Option<PathBuf>,
+    /// The environment the command inherits.
+    pub env: HashMap<String, String>,
+    /// The profile whose rules apply on top of the base rules; see
+    /// [`crate::Policy::resolution_order`].
+    pub profile: Option<String>,
+}
+
+impl EvaluationContext {
+    /// The context for a command run from the workspace root with the current
+    /// process environment.
+    pub fn from_process_env() -> Self {
+        Self {
+            cwd: Some(PathBuf::from(".")),
+            env: std::env::vars().collect(),
+            profile: None,
+        }
+    }
+
+    /// A command run from the workspace root with an empty environment.
+    pub(crate) fn workspace_root() -> Self {
+        Self {
+            cwd: Some(PathBuf::from(".")),
+            ..Self::default()
+        }
+    }
+
+    /// This context with `assignments` (e.g. `AWS_PROFILE=prod cmd`) applied on
+    /// top of the inherited environment.
+    pub fn with_env_overrides(&self, assignments: &[(String, String)]) -> Self {
+        let mut context = self.clone();
+        context.env.extend(assignments.iter().cloned());
+        context
+    }
+
+    /// The working directory as matched by `cwd` globs: lexically normalised,
+    /// with the workspace root rendered as `.`. `None` for absolute paths and
+    /// paths outside the workspace.
+    fn relative_cwd(&self) -> Option<String> {
+        let cwd = normalize_relative(self.cwd.as_deref()?)?;
+        if cwd == Path::new("") {
+            Some(".".to_string())
+        } else {
+            Some(cwd.to_string_lossy().into_owned())
+        }
+    }
+}
+
+/// Conditions on where and how a command runs, checked alongside its argv.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContextConstraints {
+    /// The working directory must match at least one of these globs.
+    pub cwd: Vec<GlobPattern>,
+    /// Each variable must be set, to a value accepted by its token: a literal,
+    /// a list of alternatives, `regex()` or `glob()`.
+    pub env: Vec<(String, PatternToken)>,
+}
+
+impl ContextConstraints {
+    pub fn new(cwd: Vec<GlobPattern>, env: Vec<(String, PatternToken)>) -> Result<Self> {
+        if let Some((name, _)) = env
+            .iter()
+            .find(|(_, value)| matches!(value, PatternToken::AnyArgs(_)))
+        {
+            return Err(Error::InvalidRule(format!(
+                "env value for `{name}` must be a string, list of strings, regex() or glob()"
+            )));
+        }
+        Ok(Self { cwd, env })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cwd.is_empty() && self.env.is_empty()
+    }
+
+    /// Whether every context satisfying `self` also satisfies `other`.
+    /// Conservative: only identical or absent conditions are compared.
+    pub fn implies(&self, other: &Self) -> bool {
+        (other.cwd.is_empty() || self.cwd == other.cwd)
+            && other
+                .env
+                .iter()
+                .all(|condition| self.env.contains(condition))
+    }
+
+    pub fn matches(&self, context: &EvaluationContext) -> bool {
+        let cwd = self.cwd.is_empty()
+            || context
+                .relative_cwd()
+                .is_some_and(|cwd| self.cwd.iter().any(|glob| glob.matches(&cwd)));
+        cwd && self.env.iter().all(|(name, value)| {
+            context
+                .env
+                .get(name)
+                .is_some_and(|actual| value.matches(actual, context))
+        })
+    }
+}
+
+impl fmt::Display for ContextConstraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut conditions: Vec<String> = self
+            .cwd
+            .iter()
+            .map(|glob| format!("cwd={:?}", glob.source()))
+            .collect();
+        conditions.extend(
+            self.env
+                .iter()
+                .map(|(name, value)| format!("{name}={}", render_pattern_token(value))),
+        );
+        f.write_str(&conditions.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::ArgMatcher;
+    use crate::matcher::RegexMatcher;
+
+    fn context(cwd: &str, env: &[(&str, &str)]) -> EvaluationContext {
+        EvaluationContext {
+            cwd: Some(PathBuf::from(cwd)),
+            env: env
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            profile: None,
+        }
+    }
+
+    #[test]
+    fn cwd_globs_match_normalised_relative_paths() {
+        let infra = ContextConstraints::new(
+            vec![
+                GlobPattern::new("infra").unwrap(),
+                GlobPattern::new("infra/**").unwrap(),
+            ],
+            Vec::new(),
+        )
+        .unwrap();
+        assert!(infra.matches(&context("infra", &[])));
+        assert!(infra.matches(&context("./infra/prod/", &[])));
+        assert!(!infra.matches(&context("src/../infra-old", &[])));
+        assert!(!infra.matches(&context("/infra", &[])));
+        assert!(!infra.matches(&EvaluationContext::default()));
+
+        let root =
+            ContextConstraints::new(vec![GlobPattern::new(".").unwrap()], Vec::new()).unwrap();
+        assert!(root.matches(&context("", &[])));
+        assert!(root.matches(&context("src/..", &[])));
+    }
+
+    #[test]
+    fn env_conditions_require_a_matching_value() {
+        let prod = ContextConstraints::new(
+            Vec::new(),
+            vec![(
+                "AWS_PROFILE".to_string(),
+                PatternToken::Matcher(ArgMatcher::Regex(RegexMatcher::new("prod.*").unwrap())),
+            )],
+        )
+        .unwrap();
+        assert!(prod.matches(&context(".", &[("AWS_PROFILE", "prod-eu")])));
+        assert!(!prod.matches(&context(".", &[("AWS_PROFILE", "staging")])));
+        assert!(!prod.matches(&context(".", &[])));
+
+        let overridden = context(".", &[("AWS_PROFILE", "staging")])
+            .with_env_overrides(&[("AWS_PROFILE".to_string(), "prod".to_string())]);
+        assert!(prod.matches(&overridden));
+        assert_eq!(prod.to_string(), r#"AWS_PROFILE=regex("prod.*")"#);
+    }
+}
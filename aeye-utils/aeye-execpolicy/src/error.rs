@@ -43,6 +43,15 @@ pub enum Error {
     ExampleDidMatch { rule: String, example: String },
     #[error("starlark error: {0}")]
     Starlark(StarlarkError),
+    #[error("failed to read policy {path}: {source}")]
+    ReadPolicy {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("policy files load each other in a cycle: {}", .0.join(" -> "))]
+    LoadCycle(Vec<String>),
+    #[error("unknown profile `{0}`")]
+    UnknownProfile(String),
 }
 
 impl Error {
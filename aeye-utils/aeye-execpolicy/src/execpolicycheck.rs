@@ -7,6 +7,8 @@ use clap::Parser;
 use serde::Serialize;
 
 use crate::Decision;
+use crate::EvaluationContext;
+use crate::Explanation;
 use crate::Policy;
 use crate::PolicyParser;
 use crate::RuleMatch;
@@ -22,6 +24,30 @@ pub struct ExecPolicyCheckCommand {
     #[arg(long)]
     pub pretty: bool,
 
+    /// Include an `explanation` listing every candidate rule for the program, where it was
+    /// defined, and which token or flag check made it match or not.
+    #[arg(long, conflicts_with = "shell")]
+    pub explain: bool,
+
+    /// Treat COMMAND as a bash script and check every simple command it would run. Constructs
+    /// that cannot be analysed, such as `eval`, are reported with a `prompt` decision.
+    #[arg(long)]
+    pub shell: bool,
+
+    /// Working directory of the command, relative to the workspace root. Rules with a `cwd`
+    /// condition or a `path_under` argument never match when this is omitted.
+    #[arg(long, value_name = "DIR")]
+    pub cwd: Option<PathBuf>,
+
+    /// Environment variable the command runs with, for rules with `env` conditions
+    /// (repeatable). The CLI's own environment is not used.
+    #[arg(long = "env", value_name = "NAME=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+
+    /// Profile declared with `profile()` whose rules apply on top of the base rules.
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
     /// Command tokens to check against the policy.
     #[arg(
         value_name = "COMMAND",
@@ -36,25 +62,58 @@ impl ExecPolicyCheckCommand {
     /// Load the policies for this command, evaluate the command, and render JSON output.
     pub fn run(&self) -> Result<()> {
         let policy = load_policies(&self.rules)?;
-        let matched_rules = policy.matches_for_command(&self.command, None);
+        policy.resolution_order(self.profile.as_deref())?;
+        let context = EvaluationContext {
+            cwd: self.cwd.clone(),
+            env: self.env.iter().cloned().collect(),
+            profile: self.profile.clone(),
+        };
+        let matched_rules = if self.shell {
+            policy.matches_for_shell(&self.command.join(" "), &context, None)
+        } else {
+            policy.matches_for_command(&self.command, &context, None)
+        };
 
-        let json = format_matches_json(&matched_rules, self.pretty)?;
+        let json = if self.explain {
+            let explanation = policy.explain(&self.command, &context, None);
+            to_json(
+                &ExecPolicyCheckOutput {
+                    matched_rules: &matched_rules,
+                    decision: explanation.decision,
+                    explanation: Some(&explanation),
+                },
+                self.pretty,
+            )?
+        } else {
+            format_matches_json(&matched_rules, self.pretty)?
+        };
         println!("{json}");
 
         Ok(())
     }
 }
 
+fn parse_env_var(raw: &str) -> Result<(String, String)> {
+    let (name, value) = raw
+        .split_once('=')
+        .with_context(|| format!("expected NAME=VALUE, got `{raw}`"))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
 pub fn format_matches_json(matched_rules: &[RuleMatch], pretty: bool) -> Result<String> {
     let output = ExecPolicyCheckOutput {
         matched_rules,
         decision: matched_rules.iter().map(RuleMatch::decision).max(),
+        explanation: None,
     };
+    to_json(&output, pretty)
+}
 
+fn to_json(output: &ExecPolicyCheckOutput<'_>, pretty: bool) -> Result<String> {
     if pretty {
-        serde_json::to_string_pretty(&output).map_err(Into::into)
+        serde_json::to_string_pretty(output).map_err(Into::into)
     } else {
-        serde_json::to_string(&output).map_err(Into::into)
+        serde_json::to_string(output).map_err(Into::into)
     }
 }
 
@@ -80,4 +139,6 @@ struct ExecPolicyCheckOutput<'a> {
     matched_rules: &'a [RuleMatch],
     #[serde(skip_serializing_if = "Option::is_none")]
     decision: Option<Decision>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    explanation: Option<&'a Explanation>,
 }
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use anyhow::Result;
+use clap::Parser;
+use serde::Deserialize;
+
+use crate::Decision;
+use crate::EvaluationContext;
+use crate::Policy;
+use crate::RuleMatch;
+use crate::execpolicycheck::load_policies;
+
+/// Arguments for running a suite of expected decisions against execpolicy files.
+#[derive(Debug, Parser, Clone)]
+pub struct ExecPolicyTestCommand {
+    /// Paths to execpolicy rule files to evaluate (repeatable).
+    #[arg(short = 'r', long = "rules", value_name = "PATH", required = true)]
+    pub rules: Vec<PathBuf>,
+
+    /// Test suites to run, as `.toml` or `.json` files (repeatable).
+    #[arg(value_name = "SUITE", required = true)]
+    pub suites: Vec<PathBuf>,
+}
+
+impl ExecPolicyTestCommand {
+    /// Run every suite, print one line per failing case, and fail if any case failed.
+    pub fn run(&self) -> Result<()> {
+        let policy = load_policies(&self.rules)?;
+
+        let mut total = 0;
+        let mut failures = Vec::new();
+        for suite_path in &self.suites {
+            let suite = load_test_suite(suite_path)?;
+            for profile in suite
+                .cases
+                .iter()
+                .filter_map(|case| case.profile.as_deref())
+            {
+                policy
+                    .resolution_order(Some(profile))
+                    .with_context(|| format!("invalid test suite at {}", suite_path.display()))?;
+            }
+            total += suite.cases.len();
+            failures.extend(run_policy_tests(&policy, &suite.cases));
+        }
+
+        for failure in &failures {
+            println!("FAIL {failure}");
+        }
+        let passed = total - failures.len();
+        println!("{passed} passed; {} failed", failures.len());
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("{} of {total} policy tests failed", failures.len())
+        }
+    }
+}
+
+/// A set of commands with the decision the policy is expected to reach for each.
+///
+/// ```toml
+/// [[cases]]
+/// command = "git push --force"
+/// decision = "forbidden"
+///
+/// [[cases]]
+/// command = "terraform apply"
+/// cwd = "infra/prod"
+/// env = { AWS_PROFILE = "prod" }
+/// decision = "forbidden"
+///
+/// [[cases]]
+/// command = "git push"
+/// profile = "ci"
+/// decision = "allow"
+///
+/// [[cases]]
+/// name = "unknown commands fall through"
+/// command = ["make", "deploy"]
+/// decision = "none"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyTestSuite {
+    pub cases: Vec<PolicyTestCase>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyTestCase {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub command: TestCommand,
+    /// Working directory relative to the workspace root; unknown when omitted.
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Profile to evaluate under; base rules only when omitted.
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub decision: ExpectedDecision,
+}
+
+/// A command given as a token list, or as a string that is split with `shlex`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum TestCommand {
+    Tokens(Vec<String>),
+    Shell(String),
+}
+
+impl TestCommand {
+    fn tokens(&self) -> Option<Vec<String>> {
+        match self {
+            Self::Tokens(tokens) => Some(tokens.clone()),
+            Self::Shell(raw) => shlex::split(raw),
+        }
+    }
+}
+
+/// The expected outcome of a case; `none` means no rule should match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExpectedDecision {
+    Allow,
+    Prompt,
+    Forbidden,
+    None,
+}
+
+impl ExpectedDecision {
+    fn as_decision(self) -> Option<Decision> {
+        match self {
+            Self::Allow => Some(Decision::Allow),
+            Self::Prompt => Some(Decision::Prompt),
+            Self::Forbidden => Some(Decision::Forbidden),
+            Self::None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyTestFailure {
+    pub case: PolicyTestCase,
+    pub actual: Option<Decision>,
+    /// The strictest rule that matched, which decided `actual`.
+    pub rule: Option<RuleMatch>,
+}
+
+impl std::fmt::Display for PolicyTestFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let command = match &self.case.command {
+            TestCommand::Tokens(tokens) => shlex::try_join(tokens.iter().map(String::as_str))
+                .unwrap_or_else(|_| format!("{tokens:?}")),
+            TestCommand::Shell(raw) => raw.clone(),
+        };
+        if let Some(name) = &self.case.name {
+            write!(f, "{name}: ")?;
+        }
+        write!(
+            f,
+            "`{command}`: expected {}, got {}",
+            render_decision(self.case.decision.as_decision()),
+            render_decision(self.actual)
+        )?;
+        match &self.rule {
+            Some(RuleMatch::PrefixRuleMatch {
+                matched_prefix,
+                justification,
+                ..
+            }) => {
+                write!(f, " (matched prefix_rule `{}`", matched_prefix.join(" "))?;
+                if let Some(justification) = justification {
+                    write!(f, ": {justification}")?;
+                }
+                write!(f, ")")
+            }
+            Some(RuleMatch::HeuristicsRuleMatch { .. })
+            | Some(RuleMatch::UnanalyzableShellMatch { .. })
+            | None => Ok(()),
+        }
+    }
+}
+
+fn render_decision(decision: Option<Decision>) -> &'static str {
+    decision.map_or("no match", Decision::as_str)
+}
+
+pub fn load_test_suite(path: &Path) -> Result<PolicyTestSuite> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("failed to read test suite at {}", path.display()))?;
+    let suite = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&contents).map_err(anyhow::Error::from)
+    } else {
+        toml::from_str(&contents).map_err(anyhow::Error::from)
+    };
+    let suite: PolicyTestSuite =
+        suite.with_context(|| format!("failed to parse test suite at {}", path.display()))?;
+    if let Some(case) = suite
+        .cases
+        .iter()
+        .find(|case| case.command.tokens().is_none_or(|tokens| tokens.is_empty()))
+    {
+        anyhow::bail!(
+            "invalid command {:?} in test suite at {}",
+            case.command,
+            path.display()
+        );
+    }
+    Ok(suite)
+}
+
+/// Evaluates each case against `policy` and returns the ones whose decision differs.
+pub fn run_policy_tests(policy: &Policy, cases: &[PolicyTestCase]) -> Vec<PolicyTestFailure> {
+    cases
+        .iter()
+        .filter_map(|case| {
+            let matched_rules = case
+                .command
+                .tokens()
+                .map(|command| {
+                    let context = EvaluationContext {
+                        cwd: case.cwd.clone(),
+                        env: case.env.clone(),
+                        profile: case.profile.clone(),
+                    };
+                    policy.matches_for_command(&command, &context, None)
+                })
+                .unwrap_or_default();
+            let rule = matched_rules.into_iter().max_by_key(RuleMatch::decision);
+            let actual = rule.as_ref().map(RuleMatch::decision);
+            (actual != case.decision.as_decision()).then(|| PolicyTestFailure {
+                case: case.clone(),
+                actual,
+                rule,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PolicyParser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn reports_mismatches_with_the_deciding_rule() {
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                "test.rules",
+                r#"
+prefix_rule(pattern = ["git", "status"])
+prefix_rule(pattern = ["git", "push"], decision = "prompt", justification = "pushes are reviewed")
+"#,
+            )
+            .unwrap();
+        let policy = parser.build();
+
+        let suite: PolicyTestSuite = toml::from_str(
+            r#"
+[[cases]]
+command = "git status"
+decision = "allow"
+
+[[cases]]
+name = "force push"
+command = ["git", "push", "--force"]
+decision = "forbidden"
+
+[[cases]]
+command = "make deploy"
+decision = "none"
+"#,
+        )
+        .unwrap();
+
+        let failures = run_policy_tests(&policy, &suite.cases);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].to_string(),
+            "force push: `git push --force`: expected forbidden, got prompt (matched prefix_rule \
+             `git push`: pushes are reviewed)"
+        );
+    }
+
+    #[test]
+    fn json_suites_are_supported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("suite.json");
+        fs::write(
+            &path,
+            r#"{"cases": [{"command": "ls -l", "decision": "none"}]}"#,
+        )
+        .unwrap();
+
+        let suite = load_test_suite(&path).unwrap();
+        assert_eq!(
+            suite.cases,
+            vec![PolicyTestCase {
+                name: None,
+                command: TestCommand::Shell("ls -l".to_string()),
+                cwd: None,
+                env: HashMap::new(),
+                profile: None,
+                decision: ExpectedDecision::None,
+            }]
+        );
+    }
+}
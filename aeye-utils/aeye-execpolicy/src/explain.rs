@@ -0,0 +1,449 @@
+use serde::Serialize;
+
+use crate::context::EvaluationContext;
+use crate::decision::Decision;
+use crate::matcher::operands;
+use crate::normalize::CommandRewrite;
+use crate::policy::HeuristicsFallback;
+use crate::policy::Policy;
+use crate::policy::layer_index;
+use crate::policy::render_pattern_token;
+use crate::rule::PatternToken;
+use crate::rule::PrefixRule;
+use crate::rule::Rule;
+use crate::rule::RuleSource;
+
+/// A step-by-step account of how a policy evaluated one command.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explanation {
+    /// The layers of rules that were consulted, most specific first; see
+    /// [`Policy::resolution_order`].
+    pub resolution_order: Vec<String>,
+    /// Every rule in one of those layers registered for the program of the
+    /// command or one of its normalised forms (e.g. `git` for `sudo git`),
+    /// form by form and then in definition order.
+    pub candidates: Vec<RuleTrace>,
+    /// Set when no rule matched and the heuristics fallback decided instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heuristics_decision: Option<Decision>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decision: Option<Decision>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleTrace {
+    /// The rule's pattern, rendered like `git [push|fetch] regex("...")`.
+    pub pattern: String,
+    pub decision: Decision,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<RuleSource>,
+    /// `None` for base rules.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// How the command was rewritten into the form this rule was checked
+    /// against; empty for the command as written.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rewrites: Vec<CommandRewrite>,
+    /// Checks in evaluation order; evaluation stops at the first failed step.
+    pub steps: Vec<TraceStep>,
+    pub matched: bool,
+    /// The rule matched, but a rule from a more specific layer also matched
+    /// and decided instead.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub overridden: bool,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum TraceStep {
+    /// One pattern element compared with the command token at the same
+    /// position. `actual` is `None` when the command was too short; for
+    /// `any_args` it holds every remaining token.
+    Token {
+        expected: String,
+        actual: Option<String>,
+        matched: bool,
+    },
+    /// The rule's `flags_present` / `flags_absent` checks.
+    #[serde(rename_all = "camelCase")]
+    Flags {
+        flags_present: Vec<String>,
+        flags_absent: Vec<String>,
+        matched: bool,
+    },
+    /// The rule's `cwd` / `env` conditions, rendered like
+    /// `cwd="infra/**" AWS_PROFILE=prod`.
+    Context { conditions: String, matched: bool },
+    /// The form gets more arguments at run time (e.g. from `xargs`), which
+    /// an allow rule constraining arguments cannot check.
+    UnknownArgs { matched: bool },
+}
+
+impl TraceStep {
+    fn matched(&self) -> bool {
+        match self {
+            Self::Token { matched, .. }
+            | Self::Flags { matched, .. }
+            | Self::Context { matched, .. }
+            | Self::UnknownArgs { matched } => *matched,
+        }
+    }
+}
+
+impl PrefixRule {
+    /// Traces how this rule evaluates `cmd`. Agrees with [`crate::Rule::matches`].
+    pub fn explain(&self, cmd: &[String], context: &EvaluationContext) -> RuleTrace {
+        let mut steps = vec![TraceStep::Token {
+            expected: self.pattern.first.to_string(),
+            actual: cmd.first().cloned(),
+            matched: cmd.first().map(String::as_str) == Some(self.pattern.first.as_ref()),
+        }];
+        let args = cmd.get(1..).unwrap_or_default();
+
+        for (index, pattern_token) in self.pattern.rest.iter().enumerate() {
+            if !steps.iter().all(TraceStep::matched) {
+                break;
+            }
+            let expected = render_pattern_token(pattern_token);
+            let step = match pattern_token {
+                PatternToken::AnyArgs(each) => {
+                    let remaining = args.get(index..).unwrap_or_default();
+                    TraceStep::Token {
+                        expected,
+                        actual: Some(remaining.join(" ")),
+                        matched: each.as_ref().is_none_or(|each| {
+                            operands(remaining).all(|operand| each.matches(operand, context))
+                        }),
+                    }
+                }
+                _ => {
+                    let actual = args.get(index);
+                    TraceStep::Token {
+                        expected,
+                        actual: actual.cloned(),
+                        matched: actual
+                            .is_some_and(|actual| pattern_token.matches(actual, context)),
+                    }
+                }
+            };
+            steps.push(step);
+        }
+
+        if !self.constraints.is_empty() && steps.iter().all(TraceStep::matched) {
+            steps.push(TraceStep::Flags {
+                flags_present: self.constraints.flags_present.clone(),
+                flags_absent: self.constraints.flags_absent.clone(),
+                matched: self.constraints.matches(args),
+            });
+        }
+        if !self.context.is_empty() && steps.iter().all(TraceStep::matched) {
+            steps.push(TraceStep::Context {
+                conditions: self.context.to_string(),
+                matched: self.context.matches(context),
+            });
+        }
+
+        let mut pattern = vec![self.pattern.first.to_string()];
+        pattern.extend(self.pattern.rest.iter().map(render_pattern_token));
+        RuleTrace {
+            pattern: pattern.join(" "),
+            decision: self.decision,
+            justification: self.justification.clone(),
+            source: self.source.clone(),
+            profile: self.profile.clone(),
+            rewrites: Vec::new(),
+            matched: steps.iter().all(TraceStep::matched),
+            steps,
+            overridden: false,
+        }
+    }
+}
+
+impl Policy {
+    /// Like [`Policy::matches_for_command`], but records why each candidate
+    /// rule did or did not match.
+    pub fn explain(
+        &self,
+        cmd: &[String],
+        context: &EvaluationContext,
+        heuristics_fallback: HeuristicsFallback<'_>,
+    ) -> Explanation {
+        let resolution_order = self.active_layers(context);
+        let mut layered: Vec<(usize, RuleTrace)> = Vec::new();
+        for form in self.normalize(cmd, context) {
+            for rule in self
+                .rules_for(&form)
+                .filter_map(|rule| rule.as_any().downcast_ref::<PrefixRule>())
+            {
+                let Some(layer) = layer_index(&resolution_order, rule.profile.as_deref()) else {
+                    continue;
+                };
+                let mut trace = rule.explain(&form.argv, &form.context);
+                trace.rewrites = form.rewrites.clone();
+                if trace.matched && form.unknown_args && !rule.matches_unknown_args() {
+                    trace.steps.push(TraceStep::UnknownArgs { matched: false });
+                    trace.matched = false;
+                }
+                layered.push((layer, trace));
+            }
+        }
+
+        let deciding_layer = layered
+            .iter()
+            .filter(|(_, candidate)| candidate.matched)
+            .map(|(layer, _)| *layer)
+            .min();
+        for (layer, candidate) in &mut layered {
+            candidate.overridden = candidate.matched && Some(*layer) != deciding_layer;
+        }
+        let candidates: Vec<RuleTrace> = layered
+            .into_iter()
+            .map(|(_, candidate)| candidate)
+            .collect();
+
+        let decision = candidates
+            .iter()
+            .filter(|candidate| candidate.matched && !candidate.overridden)
+            .map(|candidate| candidate.decision)
+            .max();
+        let heuristics_decision = match (decision, heuristics_fallback) {
+            (None, Some(fallback)) => Some(fallback(cmd)),
+            _ => None,
+        };
+
+        Explanation {
+            resolution_order,
+            candidates,
+            heuristics_decision,
+            decision: decision.or(heuristics_decision),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PolicyParser;
+    use pretty_assertions::assert_eq;
+
+    fn tokens(cmd: &str) -> Vec<String> {
+        cmd.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn policy() -> Policy {
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                "team.rules",
+                r#"
+prefix_rule(pattern = ["git", "status"])
+prefix_rule(pattern = ["git", "push"], flags_absent = ["--force", "-f"])
+prefix_rule(
+    pattern = ["git", "push"],
+    decision = "forbidden",
+    flags_present = ["--force", "-f"],
+    justification = "force pushes rewrite shared history",
+)
+prefix_rule(pattern = ["rm", any_args(each = path_under("target"))])
+prefix_rule(pattern = ["cargo", "publish"], decision = "forbidden", env = {"CI": "true"})
+"#,
+            )
+            .unwrap();
+        parser.build()
+    }
+
+    fn source(line: usize, end_line: usize) -> Option<RuleSource> {
+        Some(RuleSource {
+            path: "team.rules".to_string(),
+            line,
+            end_line,
+        })
+    }
+
+    fn token(expected: &str, actual: Option<&str>, matched: bool) -> TraceStep {
+        TraceStep::Token {
+            expected: expected.to_string(),
+            actual: actual.map(str::to_string),
+            matched,
+        }
+    }
+
+    #[test]
+    fn traces_every_candidate_rule() {
+        let explanation = policy().explain(
+            &tokens("git push -f origin"),
+            &EvaluationContext::default(),
+            None,
+        );
+        let force_flags = vec!["--force".to_string(), "-f".to_string()];
+        assert_eq!(
+            explanation,
+            Explanation {
+                resolution_order: vec!["base".to_string()],
+                candidates: vec![
+                    RuleTrace {
+                        pattern: "git status".to_string(),
+                        decision: Decision::Allow,
+                        justification: None,
+                        source: source(2, 2),
+                        profile: None,
+                        rewrites: Vec::new(),
+                        steps: vec![
+                            token("git", Some("git"), true),
+                            token("status", Some("push"), false),
+                        ],
+                        matched: false,
+                        overridden: false,
+                    },
+                    RuleTrace {
+                        pattern: "git push".to_string(),
+                        decision: Decision::Allow,
+                        justification: None,
+                        source: source(3, 3),
+                        profile: None,
+                        rewrites: Vec::new(),
+                        steps: vec![
+                            token("git", Some("git"), true),
+                            token("push", Some("push"), true),
+                            TraceStep::Flags {
+                                flags_present: Vec::new(),
+                                flags_absent: force_flags.clone(),
+                                matched: false,
+                            },
+                        ],
+                        matched: false,
+                        overridden: false,
+                    },
+                    RuleTrace {
+                        pattern: "git push".to_string(),
+                        decision: Decision::Forbidden,
+                        justification: Some("force pushes rewrite shared history".to_string()),
+                        source: source(4, 9),
+                        profile: None,
+                        rewrites: Vec::new(),
+                        steps: vec![
+                            token("git", Some("git"), true),
+                            token("push", Some("push"), true),
+                            TraceStep::Flags {
+                                flags_present: force_flags,
+                                flags_absent: Vec::new(),
+                                matched: true,
+                            },
+                        ],
+                        matched: true,
+                        overridden: false,
+                    },
+                ],
+                heuristics_decision: None,
+                decision: Some(Decision::Forbidden),
+            }
+        );
+    }
+
+    #[test]
+    fn reports_heuristics_fallback() {
+        let explanation = policy().explain(
+            &tokens("rm -rf src"),
+            &EvaluationContext::default(),
+            Some(&|_: &[String]| Decision::Prompt),
+        );
+        assert_eq!(
+            explanation.candidates[0].steps,
+            vec![
+                token("rm", Some("rm"), true),
+                token(r#"path_under("target")..."#, Some("-rf src"), false),
+            ]
+        );
+        assert_eq!(explanation.heuristics_decision, Some(Decision::Prompt));
+        assert_eq!(explanation.decision, Some(Decision::Prompt));
+    }
+
+    #[test]
+    fn agrees_with_matches() {
+        let policy = policy();
+        let context = EvaluationContext {
+            cwd: Some(".".into()),
+            env: [("CI".to_string(), "true".to_string())].into(),
+            profile: None,
+        };
+        for cmd in [
+            "git",
+            "git status -s",
+            "git push origin",
+            "git push --force=yes",
+            "rm target/a -r",
+            "rm target/a ../b",
+            "cargo publish",
+        ] {
+            let cmd = tokens(cmd);
+            let explanation = policy.explain(&cmd, &context, None);
+            let matched_rules = policy.matches_for_command(&cmd, &context, None);
+            assert_eq!(
+                explanation
+                    .candidates
+                    .iter()
+                    .filter(|candidate| candidate.matched && !candidate.overridden)
+                    .count(),
+                matched_rules.len(),
+                "{cmd:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_resolution_order_and_overridden_rules() {
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                "org.rules",
+                r#"
+profile("ci")
+prefix_rule(pattern = ["git", "push"], decision = "forbidden")
+prefix_rule(pattern = ["git", "push"], profile = "ci")
+"#,
+            )
+            .unwrap();
+        let policy = parser.build();
+        let context = EvaluationContext {
+            profile: Some("ci".to_string()),
+            ..EvaluationContext::default()
+        };
+
+        let explanation = policy.explain(&tokens("git push"), &context, None);
+        assert_eq!(explanation.resolution_order, vec!["ci", "base"]);
+        assert_eq!(
+            explanation
+                .candidates
+                .iter()
+                .map(|candidate| (candidate.profile.as_deref(), candidate.overridden))
+                .collect::<Vec<_>>(),
+            vec![(None, true), (Some("ci"), false)]
+        );
+        assert_eq!(explanation.decision, Some(Decision::Allow));
+    }
+
+    #[test]
+    fn traces_rules_for_normalised_forms() {
+        let explanation = policy().explain(
+            &tokens("sudo git status"),
+            &EvaluationContext::default(),
+            None,
+        );
+        let status = &explanation.candidates[0];
+        assert_eq!(
+            (status.pattern.as_str(), status.matched),
+            ("git status", true)
+        );
+        assert_eq!(
+            status.rewrites,
+            vec![CommandRewrite::StripWrapper {
+                wrapper: vec!["sudo".to_string()],
+            }]
+        );
+        assert_eq!(explanation.decision, Some(Decision::Allow));
+    }
+}
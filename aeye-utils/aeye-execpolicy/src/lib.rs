@@ -1,13 +1,35 @@
 pub mod amend;
+pub mod audit;
+pub mod context;
 pub mod decision;
 pub mod error;
 pub mod execpolicycheck;
+pub mod execpolicytest;
+pub mod explain;
+pub mod lint;
+pub mod matcher;
+pub mod normalize;
 pub mod parser;
 pub mod policy;
+pub mod profile;
 pub mod rule;
+pub mod shell;
 
 pub use amend::AmendError;
 pub use amend::blocking_append_allow_prefix_rule;
+pub use amend::blocking_append_prefix_rule;
+pub use amend::blocking_remove_prefix_rule;
+pub use amend::blocking_set_prefix_rule;
+pub use audit::ApprovalOutcome;
+pub use audit::AuditEntry;
+pub use audit::AuditError;
+pub use audit::AuditLog;
+pub use audit::AuditRecord;
+pub use audit::AuditedCommand;
+pub use audit::DecisionChange;
+pub use audit::replay_audit_log;
+pub use context::ContextConstraints;
+pub use context::EvaluationContext;
 pub use decision::Decision;
 pub use error::Error;
 pub use error::ErrorLocation;
@@ -15,9 +37,24 @@ pub use error::Result;
 pub use error::TextPosition;
 pub use error::TextRange;
 pub use execpolicycheck::ExecPolicyCheckCommand;
+pub use execpolicytest::ExecPolicyTestCommand;
+pub use explain::Explanation;
+pub use lint::LintFinding;
+pub use lint::lint_policy;
+pub use matcher::ArgConstraints;
+pub use matcher::ArgMatcher;
+pub use normalize::CommandRewrite;
+pub use normalize::NormalizedCommand;
+pub use normalize::normalize_command;
 pub use parser::PolicyParser;
 pub use policy::Evaluation;
 pub use policy::Policy;
+pub use profile::BASE_PROFILE;
+pub use profile::Profile;
 pub use rule::Rule;
 pub use rule::RuleMatch;
 pub use rule::RuleRef;
+pub use rule::RuleSource;
+pub use shell::ShellCommands;
+pub use shell::UnanalyzableReason;
+pub use shell::parse_shell_script;
@@ -0,0 +1,270 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+
+use crate::EvaluationContext;
+use crate::Policy;
+use crate::execpolicycheck::load_policies;
+use crate::policy::render_pattern_token;
+use crate::rule::PatternToken;
+use crate::rule::PrefixRule;
+
+/// Arguments for linting one or more execpolicy files.
+#[derive(Debug, Parser, Clone)]
+pub struct ExecPolicyLintCommand {
+    /// Paths to execpolicy rule files to lint (repeatable).
+    #[arg(short = 'r', long = "rules", value_name = "PATH", required = true)]
+    pub rules: Vec<PathBuf>,
+}
+
+impl ExecPolicyLintCommand {
+    /// Print every finding and fail if there are any.
+    pub fn run(&self) -> Result<()> {
+        let policy = load_policies(&self.rules)?;
+        let findings = lint_policy(&policy);
+        for finding in &findings {
+            println!("{finding}");
+        }
+        if findings.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("found {} policy lint issue(s)", findings.len())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LintKind {
+    /// Matches exactly the same commands as an earlier rule, with the same decision.
+    Duplicate,
+    /// Matches exactly the same commands as an earlier rule, with a different decision.
+    Contradictory,
+    /// Every command it matches is also matched by a stricter rule, so its decision never wins.
+    Shadowed,
+    /// Every command it matches is also matched by a broader rule with the same decision.
+    Redundant,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LintFinding {
+    pub kind: LintKind,
+    /// The rule the finding is about.
+    pub rule: PrefixRule,
+    /// The rule that makes `rule` a duplicate, contradictory, shadowed or redundant.
+    pub other: PrefixRule,
+}
+
+impl fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = match self.kind {
+            LintKind::Duplicate => "duplicates",
+            LintKind::Contradictory => "contradicts",
+            LintKind::Shadowed => "is shadowed by",
+            LintKind::Redundant => "is made redundant by",
+        };
+        if let Some(source) = &self.rule.source {
+            write!(f, "{source}: ")?;
+        }
+        write!(
+            f,
+            "{} {relation} {}",
+            describe_rule(&self.rule),
+            describe_rule(&self.other)
+        )?;
+        if let Some(source) = &self.other.source {
+            write!(f, " at {source}")?;
+        }
+        Ok(())
+    }
+}
+
+fn describe_rule(rule: &PrefixRule) -> String {
+    let mut tokens = vec![rule.pattern.first.to_string()];
+    tokens.extend(rule.pattern.rest.iter().map(render_pattern_token));
+    format!("`{}` ({})", tokens.join(" "), rule.decision.as_str())
+}
+
+/// Reports prefix rules whose decision can never affect an evaluation. Each
+/// rule is reported at most once, against the first rule that explains it;
+/// rules are compared only with others for the same program and profile, since
+/// a profile is meant to override base rules.
+pub fn lint_policy(policy: &Policy) -> Vec<LintFinding> {
+    let mut programs: Vec<_> = policy.rules().iter_all().collect();
+    programs.sort_by_key(|(program, _)| *program);
+
+    let mut findings = Vec::new();
+    for (_program, rules) in programs {
+        let prefix_rules: Vec<&PrefixRule> = rules
+            .iter()
+            .filter_map(|rule| rule.as_any().downcast_ref::<PrefixRule>())
+            .collect();
+        for (index, rule) in prefix_rules.iter().enumerate() {
+            let finding = prefix_rules
+                .iter()
+                .enumerate()
+                .filter(|(other_index, _)| *other_index != index)
+                .find_map(|(other_index, other)| classify(rule, other, other_index < index));
+            if let Some((kind, other)) = finding {
+                findings.push(LintFinding {
+                    kind,
+                    rule: (*rule).clone(),
+                    other: other.clone(),
+                });
+            }
+        }
+    }
+    findings
+}
+
+fn classify<'a>(
+    rule: &PrefixRule,
+    other: &'a PrefixRule,
+    other_is_earlier: bool,
+) -> Option<(LintKind, &'a PrefixRule)> {
+    if !covers(other, rule) {
+        return None;
+    }
+    if covers(rule, other) {
+        // Equivalent rules are reported on the later one only.
+        if !other_is_earlier {
+            return None;
+        }
+        let kind = if rule.decision == other.decision {
+            LintKind::Duplicate
+        } else {
+            LintKind::Contradictory
+        };
+        return Some((kind, other));
+    }
+    match rule.decision.cmp(&other.decision) {
+        std::cmp::Ordering::Less => Some((LintKind::Shadowed, other)),
+        std::cmp::Ordering::Equal => Some((LintKind::Redundant, other)),
+        std::cmp::Ordering::Greater => None,
+    }
+}
+
+/// Whether every command matched by `inner` is also matched by `outer`.
+/// Conservative: returns `false` when coverage cannot be shown, e.g. for two
+/// different regexes.
+fn covers(outer: &PrefixRule, inner: &PrefixRule) -> bool {
+    if outer.pattern.first != inner.pattern.first
+        || outer.profile != inner.profile
+        || !inner.constraints.implies(&outer.constraints)
+        || !inner.context.implies(&outer.context)
+    {
+        return false;
+    }
+    for (index, outer_token) in outer.pattern.rest.iter().enumerate() {
+        if outer_token == &PatternToken::AnyArgs(None) {
+            return true;
+        }
+        let Some(inner_token) = inner.pattern.rest.get(index) else {
+            return false;
+        };
+        if !token_covers(outer_token, inner_token) {
+            return false;
+        }
+    }
+    true
+}
+
+fn token_covers(outer: &PatternToken, inner: &PatternToken) -> bool {
+    if outer == inner {
+        return true;
+    }
+    let literals = inner.alternatives();
+    if literals.is_empty() {
+        // Only identical matchers are known to accept the same arguments.
+        return false;
+    }
+    match outer {
+        PatternToken::Single(_) | PatternToken::Alts(_) => literals
+            .iter()
+            .all(|literal| outer.alternatives().contains(literal)),
+        // With no working directory `path_under` accepts nothing, so it is
+        // never taken to cover a literal whose meaning depends on the cwd.
+        PatternToken::Matcher(matcher) => literals
+            .iter()
+            .all(|literal| matcher.matches(literal, &EvaluationContext::default())),
+        PatternToken::AnyArgs(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PolicyParser;
+    use pretty_assertions::assert_eq;
+
+    fn lint(source: &str) -> Vec<String> {
+        let mut parser = PolicyParser::new();
+        parser.parse("team.rules", source).unwrap();
+        lint_policy(&parser.build())
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn reports_shadowed_redundant_and_duplicate_rules() {
+        let findings = lint(
+            r#"
+prefix_rule(pattern = ["git"], decision = "prompt")
+prefix_rule(pattern = ["git", "status"])
+prefix_rule(pattern = ["git", ["push", "fetch"]], decision = "prompt")
+prefix_rule(pattern = ["ls"])
+prefix_rule(pattern = ["ls"])
+prefix_rule(pattern = ["rm", "-rf"], decision = "forbidden")
+prefix_rule(pattern = ["rm", "-rf"])
+"#,
+        );
+        assert_eq!(
+            findings,
+            vec![
+                "team.rules:3: `git status` (allow) is shadowed by `git` (prompt) at team.rules:2",
+                "team.rules:4: `git [push|fetch]` (prompt) is made redundant by `git` (prompt) at \
+                 team.rules:2",
+                "team.rules:6: `ls` (allow) duplicates `ls` (allow) at team.rules:5",
+                "team.rules:8: `rm -rf` (allow) contradicts `rm -rf` (forbidden) at team.rules:7",
+            ]
+        );
+    }
+
+    #[test]
+    fn narrower_stricter_rules_and_constraints_are_not_reported() {
+        let findings = lint(
+            r#"
+prefix_rule(pattern = ["git", "push"], flags_absent = ["--force"])
+prefix_rule(pattern = ["git", "push"], decision = "forbidden", flags_present = ["--force"])
+prefix_rule(pattern = ["cargo"], decision = "prompt")
+prefix_rule(pattern = ["cargo", "publish"], decision = "forbidden")
+prefix_rule(pattern = ["cat", any_args(each = path_under("src"))])
+prefix_rule(pattern = ["cat", regex(".*")], decision = "prompt")
+"#,
+        );
+        assert_eq!(findings, Vec::<String>::new());
+    }
+
+    #[test]
+    fn matchers_shadow_literals_they_accept() {
+        let findings = lint(
+            r#"
+prefix_rule(pattern = ["git", "checkout", "release/v1"])
+prefix_rule(pattern = ["git", "checkout", regex("release/.*")], decision = "forbidden")
+prefix_rule(pattern = ["git", "log", any_args()], decision = "prompt")
+prefix_rule(pattern = ["git", "log", "-p"])
+"#,
+        );
+        assert_eq!(
+            findings,
+            vec![
+                "team.rules:2: `git checkout release/v1` (allow) is shadowed by `git checkout \
+                 regex(\"release/.*\")` (forbidden) at team.rules:3",
+                "team.rules:5: `git log -p` (allow) is shadowed by `git log ...` (prompt) at \
+                 team.rules:4",
+            ]
+        );
+    }
+}
@@ -1,4 +1,7 @@
+use aeye_execpolicy::audit::ExecPolicyReplayCommand;
 use aeye_execpolicy::execpolicycheck::ExecPolicyCheckCommand;
+use aeye_execpolicy::execpolicytest::ExecPolicyTestCommand;
+use aeye_execpolicy::lint::ExecPolicyLintCommand;
 use anyhow::Result;
 use clap::Parser;
 
@@ -8,11 +11,20 @@ use clap::Parser;
 enum Cli {
     /// Evaluate a command against a policy.
     Check(ExecPolicyCheckCommand),
+    /// Check a suite of commands against their expected decisions.
+    Test(ExecPolicyTestCommand),
+    /// Report duplicate, contradictory, shadowed and redundant rules.
+    Lint(ExecPolicyLintCommand),
+    /// Re-evaluate audit logs against a policy and report decisions that would change.
+    Replay(ExecPolicyReplayCommand),
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
     match cli {
         Cli::Check(cmd) => cmd.run(),
+        Cli::Test(cmd) => cmd.run(),
+        Cli::Lint(cmd) => cmd.run(),
+        Cli::Replay(cmd) => cmd.run(),
     }
 }
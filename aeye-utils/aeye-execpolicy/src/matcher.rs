@@ -0,0 +1,350 @@
+use globset::GlobBuilder;
+use globset::GlobMatcher;
+use regex::Regex;
+use std::fmt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::context::EvaluationContext;
+use crate::error::Error;
+use crate::error::Result;
+
+/// Matches a single command argument by shape rather than by exact value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ArgMatcher {
+    Regex(RegexMatcher),
+    Glob(GlobPattern),
+    PathUnder(PathUnder),
+}
+
+impl ArgMatcher {
+    /// `context` locates relative paths for [`ArgMatcher::PathUnder`].
+    pub fn matches(&self, arg: &str, context: &EvaluationContext) -> bool {
+        match self {
+            Self::Regex(regex) => regex.regex.is_match(arg),
+            Self::Glob(glob) => glob.matches(arg),
+            Self::PathUnder(path_under) => path_under.matches(arg, context.cwd.as_deref()),
+        }
+    }
+}
+
+impl fmt::Display for ArgMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Regex(regex) => write!(f, "regex({:?})", regex.source),
+            Self::Glob(glob) => write!(f, "glob({:?})", glob.source),
+            Self::PathUnder(path_under) => {
+                let dirs: Vec<String> = path_under
+                    .dirs
+                    .iter()
+                    .map(|dir| format!("{:?}", dir.display().to_string()))
+                    .collect();
+                write!(f, "path_under({})", dirs.join(", "))
+            }
+        }
+    }
+}
+
+/// A regex that must match the whole argument.
+#[derive(Clone, Debug)]
+pub struct RegexMatcher {
+    source: String,
+    regex: Regex,
+}
+
+impl RegexMatcher {
+    pub fn new(source: &str) -> Result<Self> {
+        let regex = Regex::new(&format!("^(?:{source})$"))
+            .map_err(|err| Error::InvalidPattern(format!("invalid regex `{source}`: {err}")))?;
+        Ok(Self {
+            source: source.to_string(),
+            regex,
+        })
+    }
+}
+
+impl PartialEq for RegexMatcher {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for RegexMatcher {}
+
+/// A glob over the argument text; `*` does not cross `/`.
+#[derive(Clone, Debug)]
+pub struct GlobPattern {
+    source: String,
+    matcher: GlobMatcher,
+}
+
+impl GlobPattern {
+    pub fn new(source: &str) -> Result<Self> {
+        let glob = GlobBuilder::new(source)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| Error::InvalidPattern(format!("invalid glob `{source}`: {err}")))?;
+        Ok(Self {
+            source: source.to_string(),
+            matcher: glob.compile_matcher(),
+        })
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        self.matcher.is_match(text)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl PartialEq for GlobPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for GlobPattern {}
+
+/// Requires the argument to be a path inside one of `dirs`, relative to the
+/// workspace root. Arguments are resolved against the command's working
+/// directory; nothing matches when that is unknown or outside the workspace,
+/// and absolute paths and paths that climb out with `..` never match.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PathUnder {
+    dirs: Vec<PathBuf>,
+}
+
+impl PathUnder {
+    pub fn new<I, S>(dirs: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let dirs = dirs
+            .into_iter()
+            .map(|dir| {
+                let dir = dir.as_ref();
+                normalize_relative(Path::new(dir)).ok_or_else(|| {
+                    Error::InvalidPattern(format!(
+                        "path_under directory must be relative to the workspace root (got `{dir}`)"
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if dirs.is_empty() {
+            return Err(Error::InvalidPattern(
+                "path_under requires at least one directory".to_string(),
+            ));
+        }
+        Ok(Self { dirs })
+    }
+
+    fn matches(&self, arg: &str, cwd: Option<&Path>) -> bool {
+        let Some(cwd) = cwd.and_then(normalize_relative) else {
+            return false;
+        };
+        normalize_relative(&cwd.join(arg))
+            .is_some_and(|path| self.dirs.iter().any(|dir| path.starts_with(dir)))
+    }
+}
+
+/// Lexically resolves `.` and `..`. Returns `None` for absolute paths and
+/// paths that escape their starting directory.
+pub(crate) fn normalize_relative(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(normalized)
+}
+
+/// Arguments that are not flags: `-` on its own and everything after `--`
+/// count as operands.
+pub(crate) fn operands(args: &[String]) -> impl Iterator<Item = &String> {
+    let mut after_separator = false;
+    args.iter().filter(move |arg| {
+        if after_separator {
+            return true;
+        }
+        if *arg == "--" {
+            after_separator = true;
+            return false;
+        }
+        *arg == "-" || !arg.starts_with('-')
+    })
+}
+
+/// Position-independent checks on the flags a command was given.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ArgConstraints {
+    /// At least one of these flags must appear.
+    pub flags_present: Vec<String>,
+    /// None of these flags may appear.
+    pub flags_absent: Vec<String>,
+}
+
+impl ArgConstraints {
+    pub fn new(flags_present: Vec<String>, flags_absent: Vec<String>) -> Result<Self> {
+        if let Some(flag) = flags_present
+            .iter()
+            .chain(&flags_absent)
+            .find(|flag| !flag.starts_with('-') || flag.trim_start_matches('-').is_empty())
+        {
+            return Err(Error::InvalidRule(format!(
+                "flags must start with `-` or `--` (got `{flag}`)"
+            )));
+        }
+        Ok(Self {
+            flags_present,
+            flags_absent,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags_present.is_empty() && self.flags_absent.is_empty()
+    }
+
+    /// Whether every command satisfying `self` also satisfies `other`.
+    /// Conservative: may return `false` for constraints that do overlap.
+    pub fn implies(&self, other: &Self) -> bool {
+        let present = other.flags_present.is_empty()
+            || (!self.flags_present.is_empty()
+                && self
+                    .flags_present
+                    .iter()
+                    .all(|flag| other.flags_present.contains(flag)));
+        present
+            && other
+                .flags_absent
+                .iter()
+                .all(|flag| self.flags_absent.contains(flag))
+    }
+
+    /// `args` excludes the program name.
+    pub fn matches(&self, args: &[String]) -> bool {
+        let present = self.flags_present.is_empty()
+            || self.flags_present.iter().any(|flag| has_flag(args, flag));
+        present && !self.flags_absent.iter().any(|flag| has_flag(args, flag))
+    }
+}
+
+/// Long flags also match their `--flag=value` form; single-letter short
+/// flags also match when bundled, e.g. `-f` in `-fu`. Nothing after `--` is
+/// treated as a flag.
+fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter()
+        .take_while(|arg| *arg != "--")
+        .any(|arg| flag_matches(arg, flag))
+}
+
+fn flag_matches(arg: &str, flag: &str) -> bool {
+    if arg == flag {
+        return true;
+    }
+    if flag.starts_with("--") {
+        return arg
+            .strip_prefix(flag)
+            .is_some_and(|value| value.starts_with('='));
+    }
+    let mut short = flag.chars().skip(1);
+    match (short.next(), short.next()) {
+        (Some(letter), None) => {
+            !arg.starts_with("--")
+                && arg
+                    .strip_prefix('-')
+                    .is_some_and(|bundle| bundle.contains(letter))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn args(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(ToString::to_string).collect()
+    }
+
+    fn in_dir(cwd: &str) -> EvaluationContext {
+        EvaluationContext {
+            cwd: Some(PathBuf::from(cwd)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn regex_must_match_whole_argument() {
+        let matcher = ArgMatcher::Regex(RegexMatcher::new("v[0-9]+").unwrap());
+        assert!(matcher.matches("v12", &in_dir(".")));
+        assert!(!matcher.matches("v12-rc", &in_dir(".")));
+        assert_eq!(matcher.to_string(), r#"regex("v[0-9]+")"#);
+    }
+
+    #[test]
+    fn glob_star_does_not_cross_directories() {
+        let matcher = ArgMatcher::Glob(GlobPattern::new("*.rs").unwrap());
+        assert!(matcher.matches("main.rs", &in_dir(".")));
+        assert!(!matcher.matches("src/main.rs", &in_dir(".")));
+    }
+
+    #[test]
+    fn path_under_stays_inside_workspace_dirs() {
+        let matcher = ArgMatcher::PathUnder(PathUnder::new(["target/"]).unwrap());
+        let root = in_dir(".");
+        assert!(matcher.matches("target/debug", &root));
+        assert!(matcher.matches("./target/../target/release", &root));
+        assert!(!matcher.matches("target/../src", &root));
+        assert!(!matcher.matches("/tmp/target", &root));
+        assert!(!matcher.matches("../target", &root));
+        assert!(PathUnder::new(["/etc"]).is_err());
+    }
+
+    #[test]
+    fn path_under_resolves_against_cwd() {
+        let matcher = ArgMatcher::PathUnder(PathUnder::new(["target"]).unwrap());
+        assert!(matcher.matches("debug", &in_dir("target")));
+        assert!(matcher.matches("../target/debug", &in_dir("src")));
+        assert!(!matcher.matches("target/debug", &in_dir("src")));
+        assert!(!matcher.matches(".", &in_dir("src")));
+        assert!(!matcher.matches("target", &in_dir("/tmp")));
+        assert!(!matcher.matches("target", &in_dir("..")));
+        assert!(!matcher.matches("target", &EvaluationContext::default()));
+    }
+
+    #[test]
+    fn flags_match_anywhere_before_separator() {
+        let no_force = ArgConstraints::new(Vec::new(), args(&["--force", "-f"])).unwrap();
+        assert!(no_force.matches(&args(&["push", "origin", "main"])));
+        assert!(!no_force.matches(&args(&["push", "origin", "main", "-f"])));
+        assert!(!no_force.matches(&args(&["push", "-uf", "origin"])));
+        assert!(!no_force.matches(&args(&["push", "--force=true"])));
+        assert!(no_force.matches(&args(&["push", "--force-with-lease"])));
+        assert!(no_force.matches(&args(&["push", "--", "-f"])));
+
+        let forced = ArgConstraints::new(args(&["--force", "-f"]), Vec::new()).unwrap();
+        assert!(forced.matches(&args(&["push", "--force"])));
+        assert!(!forced.matches(&args(&["push"])));
+        assert!(ArgConstraints::new(args(&["force"]), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn operands_skip_flags() {
+        let raw = args(&["-rf", "target", "-", "--", "-weird"]);
+        let operands: Vec<&String> = operands(&raw).collect();
+        assert_eq!(operands, vec!["target", "-", "-weird"]);
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::context::EvaluationContext;
+
+/// How the command-line options of a wrapper such as `sudo` are laid out, so
+/// the command it runs can be found.
+struct Wrapper {
+    name: &'static str,
+    /// Options that consume a value, either as the next token (`-u root`) or
+    /// attached (`-uroot`, `--user=root`).
+    options_with_value: &'static [&'static str],
+    /// Options that make the wrapper do something other than run the command,
+    /// e.g. `command -v git` only prints where `git` is.
+    no_command_options: &'static [&'static str],
+    /// Options whose value is split into further arguments of the wrapper,
+    /// e.g. `env -S 'FOO=1 make'`.
+    split_options: &'static [&'static str],
+    /// Options that change the working directory of the command.
+    chdir_options: &'static [&'static str],
+    /// Operands between the options and the command, e.g. the duration in
+    /// `timeout 5 make`.
+    operands: usize,
+    /// Whether `NAME=value` operands before the command set its environment.
+    assignments: bool,
+    /// Whether the command gets arguments that are not on the command line,
+    /// e.g. the lines `xargs` reads from stdin.
+    appends_args: bool,
+}
+
+const WRAPPERS: &[Wrapper] = &[
+    Wrapper {
+        name: "env",
+        options_with_value: &["-u", "--unset", "-C", "--chdir", "-S", "--split-string"],
+        no_command_options: &[],
+        split_options: &["-S", "--split-string"],
+        chdir_options: &["-C", "--chdir"],
+        operands: 0,
+        assignments: true,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "sudo",
+        options_with_value: &[
+            "-u",
+            "--user",
+            "-g",
+            "--group",
+            "-C",
+            "--close-from",
+            "-D",
+            "--chdir",
+            "-h",
+            "--host",
+            "-p",
+            "--prompt",
+            "-r",
+            "--role",
+            "-t",
+            "--type",
+            "-T",
+            "--command-timeout",
+            "-U",
+            "--other-user",
+        ],
+        no_command_options: &[
+            "-e",
+            "--edit",
+            "-l",
+            "--list",
+            "-v",
+            "--validate",
+            "-K",
+            "--remove-timestamp",
+            "-V",
+            "--version",
+        ],
+        split_options: &[],
+        chdir_options: &["-D", "--chdir"],
+        operands: 0,
+        assignments: true,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "time",
+        options_with_value: &["-f", "--format", "-o", "--output"],
+        no_command_options: &[],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "nice",
+        options_with_value: &["-n", "--adjustment"],
+        no_command_options: &[],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "xargs",
+        options_with_value: &[
+            "-a",
+            "--arg-file",
+            "-d",
+            "--delimiter",
+            "-E",
+            "-I",
+            "-L",
+            "-n",
+            "--max-args",
+            "-P",
+            "--max-procs",
+            "-s",
+            "--max-chars",
+            "--process-slot-var",
+        ],
+        no_command_options: &["--show-limits", "--help", "--version"],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: true,
+    },
+    Wrapper {
+        name: "command",
+        options_with_value: &[],
+        no_command_options: &["-v", "-V"],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "exec",
+        options_with_value: &["-a"],
+        no_command_options: &[],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "nohup",
+        options_with_value: &[],
+        no_command_options: &["--help", "--version"],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "timeout",
+        options_with_value: &["-k", "--kill-after", "-s", "--signal"],
+        no_command_options: &["--help", "--version"],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 1,
+        assignments: false,
+        appends_args: false,
+    },
+    Wrapper {
+        name: "stdbuf",
+        options_with_value: &["-i", "--input", "-o", "--output", "-e", "--error"],
+        no_command_options: &["--help", "--version"],
+        split_options: &[],
+        chdir_options: &[],
+        operands: 0,
+        assignments: false,
+        appends_args: false,
+    },
+];
+
+/// One step taken to turn a command into the form rules are written against.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum CommandRewrite {
+    /// A wrapper and its options were removed, e.g. `sudo -u root` or
+    /// `env FOO=1`. Its assignments and directory change still apply.
+    StripWrapper { wrapper: Vec<String> },
+    /// An absolute path in a `PATH` directory was replaced by its file name.
+    Basename { path: String, program: String },
+    /// An alias declared with `alias()` was replaced by its program.
+    Alias { alias: String, program: String },
+}
+
+/// A form of a command that rules are checked against.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NormalizedCommand {
+    pub argv: Vec<String>,
+    /// The context with any wrapper assignments and directory changes applied.
+    pub context: EvaluationContext,
+    /// How `argv` was derived from the command as written; empty for the
+    /// command itself.
+    pub rewrites: Vec<CommandRewrite>,
+    /// Whether arguments not in `argv` are added at run time, e.g. by
+    /// `xargs`. Rules that constrain arguments may not allow such a form; see
+    /// [`crate::Rule::matches_unknown_args`].
+    pub unknown_args: bool,
+}
+
+/// The command as written, followed by each rewrite of it whose program
+/// differs from every earlier form: absolute paths in a `PATH` directory are
+/// reduced to their file name, `aliases` are replaced by their program and
+/// wrappers such as `sudo`, `env`, `time`, `nice`, `timeout`, `xargs` and
+/// `exec` are stripped. The string given to `env -S` is split with `shlex`.
+/// Aliases are not chained.
+pub fn normalize_command(
+    cmd: &[String],
+    context: &EvaluationContext,
+    aliases: &HashMap<String, String>,
+) -> Vec<NormalizedCommand> {
+    let mut current = NormalizedCommand {
+        argv: cmd.to_vec(),
+        context: context.clone(),
+        rewrites: Vec::new(),
+        unknown_args: false,
+    };
+    let mut forms = vec![current.clone()];
+
+    while let Some(program) = current.argv.first().cloned() {
+        if let Some(name) = basename_on_path(&program, &current.context) {
+            current.argv[0] = name.clone();
+            current.rewrites.push(CommandRewrite::Basename {
+                path: program,
+                program: name,
+            });
+            push_form(&mut forms, &current);
+        }
+        if let Some(target) = aliases.get(&current.argv[0]) {
+            current.rewrites.push(CommandRewrite::Alias {
+                alias: std::mem::replace(&mut current.argv[0], target.clone()),
+                program: target.clone(),
+            });
+            push_form(&mut forms, &current);
+        }
+
+        let Some(wrapper) = WRAPPERS
+            .iter()
+            .find(|wrapper| wrapper.name == current.argv[0])
+        else {
+            break;
+        };
+        let Some(stripped) = wrapper.strip(&current.argv) else {
+            break;
+        };
+        current.unknown_args |= wrapper.appends_args;
+        current.context = current.context.with_env_overrides(&stripped.env);
+        if let Some(dir) = stripped.chdir {
+            current.context.cwd = current.context.cwd.map(|cwd| cwd.join(dir));
+        }
+        let mut command = current.argv.split_off(stripped.command_start);
+        if let Some(split) = stripped.split {
+            // The split arguments are read as the wrapper's own, so they can
+            // hold further options and assignments before the command.
+            command = std::iter::once(wrapper.name.to_string())
+                .chain(split)
+                .chain(command)
+                .collect();
+        }
+        current.rewrites.push(CommandRewrite::StripWrapper {
+            wrapper: std::mem::replace(&mut current.argv, command),
+        });
+        push_form(&mut forms, &current);
+    }
+    forms
+}
+
+/// Adds `form` unless an earlier form already has its program, whose rules
+/// were then checked against the less rewritten command.
+fn push_form(forms: &mut Vec<NormalizedCommand>, form: &NormalizedCommand) {
+    if !forms
+        .iter()
+        .any(|existing| existing.argv.first() == form.argv.first())
+    {
+        forms.push(form.clone());
+    }
+}
+
+/// The file name of `program` if it is an absolute path inside one of the
+/// directories on the context's `PATH`.
+fn basename_on_path(program: &str, context: &EvaluationContext) -> Option<String> {
+    let path = Path::new(program);
+    if !path.is_absolute() {
+        return None;
+    }
+    let dir = path.parent()?;
+    let search_path = context.env.get("PATH")?;
+    std::env::split_paths(search_path)
+        .any(|entry| entry == dir)
+        .then(|| path.file_name()?.to_str().map(str::to_string))
+        .flatten()
+}
+
+struct StrippedWrapper {
+    /// Index of the wrapped command's first token.
+    command_start: usize,
+    env: Vec<(String, String)>,
+    chdir: Option<String>,
+    /// The arguments split from an option such as `env -S`, which come before
+    /// the rest of the command line.
+    split: Option<Vec<String>>,
+}
+
+impl Wrapper {
+    /// Finds the command run by `argv`, which starts with this wrapper.
+    /// `None` if the wrapper does not run one.
+    fn strip(&self, argv: &[String]) -> Option<StrippedWrapper> {
+        let mut stripped = StrippedWrapper {
+            command_start: 1,
+            env: Vec::new(),
+            chdir: None,
+            split: None,
+        };
+        while let Some(token) = argv.get(stripped.command_start) {
+            let index = stripped.command_start;
+            if token == "--" {
+                stripped.command_start += 1;
+                break;
+            }
+            if let Some(long) = token.strip_prefix("--") {
+                let (name, attached) = match long.split_once('=') {
+                    Some((name, value)) => (format!("--{name}"), Some(value.to_string())),
+                    None => (token.clone(), None),
+                };
+                let value = self.option(&name, attached, argv.get(index + 1))?;
+                stripped.command_start += 1 + usize::from(value.consumed_next);
+                if self.split_options.contains(&name.as_str()) {
+                    stripped.split = Some(shlex::split(&value.value?)?);
+                    return Some(stripped);
+                }
+                self.record_chdir(&name, value.value, &mut stripped);
+                continue;
+            }
+            if let Some(flags) = token.strip_prefix('-')
+                && !flags.is_empty()
+            {
+                stripped.command_start += 1;
+                for (offset, flag) in flags.char_indices() {
+                    let name = format!("-{flag}");
+                    let rest = &flags[offset + flag.len_utf8()..];
+                    if !self.options_with_value.contains(&name.as_str()) {
+                        self.option(&name, None, None)?;
+                        continue;
+                    }
+                    let attached = (!rest.is_empty()).then(|| rest.to_string());
+                    let value = self.option(&name, attached, argv.get(index + 1))?;
+                    stripped.command_start += usize::from(value.consumed_next);
+                    if self.split_options.contains(&name.as_str()) {
+                        stripped.split = Some(shlex::split(&value.value?)?);
+                        return Some(stripped);
+                    }
+                    self.record_chdir(&name, value.value, &mut stripped);
+                    break;
+                }
+                continue;
+            }
+            if self.assignments
+                && let Some((name, value)) = token.split_once('=')
+                && is_variable_name(name)
+            {
+                stripped.env.push((name.to_string(), value.to_string()));
+                stripped.command_start += 1;
+                continue;
+            }
+            break;
+        }
+        stripped.command_start += self.operands;
+        (stripped.command_start < argv.len()).then_some(stripped)
+    }
+
+    /// Looks up one option. `None` if it means no command will run or its
+    /// value is missing.
+    fn option(
+        &self,
+        name: &str,
+        attached: Option<String>,
+        next: Option<&String>,
+    ) -> Option<OptionValue> {
+        if self.no_command_options.contains(&name) {
+            return None;
+        }
+        if !self.options_with_value.contains(&name) {
+            return Some(OptionValue {
+                value: None,
+                consumed_next: false,
+            });
+        }
+        match attached {
+            Some(value) => Some(OptionValue {
+                value: Some(value),
+                consumed_next: false,
+            }),
+            None => Some(OptionValue {
+                value: Some(next?.clone()),
+                consumed_next: true,
+            }),
+        }
+    }
+
+    fn record_chdir(&self, name: &str, value: Option<String>, stripped: &mut StrippedWrapper) {
+        if self.chdir_options.contains(&name) {
+            stripped.chdir = value;
+        }
+    }
+}
+
+struct OptionValue {
+    value: Option<String>,
+    consumed_next: bool,
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::path::PathBuf;
+
+    fn tokens(cmd: &str) -> Vec<String> {
+        cmd.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn programs(cmd: &str, aliases: &[(&str, &str)]) -> Vec<String> {
+        let context = EvaluationContext {
+            cwd: Some(PathBuf::from(".")),
+            env: [("PATH".to_string(), "/usr/local/bin:/usr/bin".to_string())].into(),
+            profile: None,
+        };
+        let aliases = aliases
+            .iter()
+            .map(|(alias, program)| (alias.to_string(), program.to_string()))
+            .collect();
+        normalize_command(&tokens(cmd), &context, &aliases)
+            .into_iter()
+            .map(|form| form.argv.join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn strips_wrappers_and_their_options() {
+        assert_eq!(
+            programs("sudo -u root -E env -i FOO=1 nice -n 5 git push", &[]),
+            vec![
+                "sudo -u root -E env -i FOO=1 nice -n 5 git push",
+                "env -i FOO=1 nice -n 5 git push",
+                "nice -n 5 git push",
+                "git push",
+            ]
+        );
+        assert_eq!(
+            programs("time -f %e xargs -0 -I{} command git add {}", &[]),
+            vec![
+                "time -f %e xargs -0 -I{} command git add {}",
+                "xargs -0 -I{} command git add {}",
+                "command git add {}",
+                "git add {}",
+            ]
+        );
+        assert_eq!(programs("command -v git", &[]), vec!["command -v git"]);
+        let forms = normalize_command(
+            &tokens("xargs sudo rm -rf"),
+            &EvaluationContext::default(),
+            &HashMap::new(),
+        );
+        assert_eq!(
+            forms
+                .iter()
+                .map(|form| (form.argv.join(" "), form.unknown_args))
+                .collect::<Vec<_>>(),
+            vec![
+                ("xargs sudo rm -rf".to_string(), false),
+                ("sudo rm -rf".to_string(), true),
+                ("rm -rf".to_string(), true),
+            ]
+        );
+        assert_eq!(programs("sudo -i", &[]), vec!["sudo -i"]);
+        assert_eq!(programs("env", &[]), vec!["env"]);
+    }
+
+    #[test]
+    fn splits_env_strings_and_skips_wrapper_operands() {
+        let argvs = |cmd: &[&str]| {
+            let cmd: Vec<String> = cmd.iter().map(|token| token.to_string()).collect();
+            normalize_command(&cmd, &EvaluationContext::default(), &HashMap::new())
+                .into_iter()
+                .map(|form| form.argv.join(" "))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            argvs(&["env", "-S", "rm -rf /"]),
+            vec!["env -S rm -rf /", "rm -rf /"]
+        );
+        assert_eq!(
+            argvs(&["env", "--split-string=FOO=1 -i 'rm' -rf", "/"]),
+            vec!["env --split-string=FOO=1 -i 'rm' -rf /", "rm -rf /"]
+        );
+        assert_eq!(argvs(&["env", "-S", "'rm"]), vec!["env -S 'rm"]);
+        assert_eq!(
+            programs(
+                "exec -a name timeout -s KILL 5 nohup stdbuf -oL rm -rf /",
+                &[]
+            ),
+            vec![
+                "exec -a name timeout -s KILL 5 nohup stdbuf -oL rm -rf /",
+                "timeout -s KILL 5 nohup stdbuf -oL rm -rf /",
+                "nohup stdbuf -oL rm -rf /",
+                "stdbuf -oL rm -rf /",
+                "rm -rf /",
+            ]
+        );
+        assert_eq!(programs("timeout 5", &[]), vec!["timeout 5"]);
+    }
+
+    #[test]
+    fn resolves_paths_on_path_and_aliases() {
+        assert_eq!(
+            programs("/usr/bin/git status", &[]),
+            vec!["/usr/bin/git status", "git status"]
+        );
+        assert_eq!(
+            programs("/opt/git/git status", &[]),
+            vec!["/opt/git/git status"]
+        );
+        assert_eq!(
+            programs(
+                "./node_modules/.bin/eslint src",
+                &[("./node_modules/.bin/eslint", "eslint")]
+            ),
+            vec!["./node_modules/.bin/eslint src", "eslint src"]
+        );
+        assert_eq!(
+            programs("/usr/bin/sudo g push", &[("g", "git")]),
+            vec!["/usr/bin/sudo g push", "sudo g push", "g push", "git push"]
+        );
+    }
+
+    #[test]
+    fn wrapper_assignments_and_directories_apply_to_the_command() {
+        let context = EvaluationContext {
+            cwd: Some(PathBuf::from(".")),
+            ..EvaluationContext::default()
+        };
+        let forms = normalize_command(
+            &tokens("env -C infra AWS_PROFILE=prod terraform apply"),
+            &context,
+            &HashMap::new(),
+        );
+        let last = forms.last().unwrap();
+        assert_eq!(last.argv, tokens("terraform apply"));
+        assert_eq!(last.context.cwd, Some(PathBuf::from("./infra")));
+        assert_eq!(
+            last.context.env.get("AWS_PROFILE").map(String::as_str),
+            Some("prod")
+        );
+        assert_eq!(
+            last.rewrites,
+            vec![CommandRewrite::StripWrapper {
+                wrapper: tokens("env -C infra AWS_PROFILE=prod"),
+            }]
+        );
+    }
+}
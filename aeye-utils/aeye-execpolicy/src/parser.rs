@@ -1,32 +1,57 @@
+use allocative::Allocative;
 use multimap::MultiMap;
 use shlex;
+use starlark::Error as StarlarkError;
 use starlark::any::ProvidesStaticType;
+use starlark::environment::FrozenModule;
 use starlark::environment::GlobalsBuilder;
 use starlark::environment::Module;
 use starlark::eval::Evaluator;
+use starlark::eval::FileLoader;
 use starlark::starlark_module;
+use starlark::starlark_simple_value;
 use starlark::syntax::AstModule;
 use starlark::syntax::Dialect;
+use starlark::values::NoSerialize;
+use starlark::values::StarlarkValue;
 use starlark::values::Value;
+use starlark::values::dict::DictRef;
 use starlark::values::list::ListRef;
 use starlark::values::list::UnpackList;
 use starlark::values::none::NoneType;
+use starlark::values::starlark_value;
+use starlark::values::tuple::UnpackTuple;
 use std::cell::RefCell;
 use std::cell::RefMut;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use crate::context::ContextConstraints;
 use crate::decision::Decision;
 use crate::error::Error;
 use crate::error::Result;
+use crate::matcher::ArgConstraints;
+use crate::matcher::ArgMatcher;
+use crate::matcher::GlobPattern;
+use crate::matcher::PathUnder;
+use crate::matcher::RegexMatcher;
+use crate::profile::BASE_PROFILE;
+use crate::profile::Profile;
 use crate::rule::PatternToken;
 use crate::rule::PrefixPattern;
 use crate::rule::PrefixRule;
 use crate::rule::RuleRef;
+use crate::rule::RuleSource;
 use crate::rule::validate_match_examples;
 use crate::rule::validate_not_match_examples;
 
 pub struct PolicyParser {
-    builder: RefCell<PolicyBuilder>,
+    state: PolicyState,
 }
 
 impl Default for PolicyParser {
@@ -38,55 +63,224 @@ impl Default for PolicyParser {
 impl PolicyParser {
     pub fn new() -> Self {
         Self {
-            builder: RefCell::new(PolicyBuilder::new()),
+            state: PolicyState::default(),
         }
     }
 
     /// Parses a policy, tagging parser errors with `policy_identifier` so failures include the
-    /// identifier alongside line numbers.
+    /// identifier alongside line numbers. Files named by `load()` and `include()` are read
+    /// relative to the directory of `policy_identifier`.
     pub fn parse(&mut self, policy_identifier: &str, policy_file_contents: &str) -> Result<()> {
+        self.state
+            .evaluate(policy_identifier, policy_file_contents.to_string())
+            .map(|_| ())
+    }
+
+    pub fn build(self) -> crate::policy::Policy {
+        self.state.builder.into_inner().build()
+    }
+}
+
+/// Shared by every policy file evaluated by one [`PolicyParser`], including
+/// the ones they load.
+#[derive(Debug, Default, ProvidesStaticType)]
+struct PolicyState {
+    builder: RefCell<PolicyBuilder>,
+    /// Evaluated files by path, so a library loaded from several policies
+    /// registers its rules once.
+    modules: RefCell<HashMap<String, FrozenModule>>,
+    /// Files whose evaluation is in progress, outermost first.
+    loading: RefCell<Vec<String>>,
+}
+
+impl PolicyState {
+    fn evaluate(&self, identifier: &str, contents: String) -> Result<FrozenModule> {
+        {
+            let mut loading = self.loading.borrow_mut();
+            if let Some(start) = loading.iter().position(|path| path == identifier) {
+                let mut cycle = loading[start..].to_vec();
+                cycle.push(identifier.to_string());
+                return Err(Error::LoadCycle(cycle));
+            }
+            loading.push(identifier.to_string());
+        }
+        let module = self.evaluate_module(identifier, contents);
+        self.loading.borrow_mut().pop();
+
+        let module = module?;
+        self.modules
+            .borrow_mut()
+            .insert(identifier.to_string(), module.clone());
+        Ok(module)
+    }
+
+    fn evaluate_module(&self, identifier: &str, contents: String) -> Result<FrozenModule> {
         let mut dialect = Dialect::Extended.clone();
         dialect.enable_f_strings = true;
-        let ast = AstModule::parse(
-            policy_identifier,
-            policy_file_contents.to_string(),
-            &dialect,
-        )
-        .map_err(Error::Starlark)?;
+        let ast = AstModule::parse(identifier, contents, &dialect).map_err(Error::Starlark)?;
         let globals = GlobalsBuilder::standard().with(policy_builtins).build();
         let module = Module::new();
         {
+            let loader = PolicyLoader {
+                state: self,
+                importer: identifier,
+            };
             let mut eval = Evaluator::new(&module);
-            eval.extra = Some(&self.builder);
+            eval.extra = Some(self);
+            eval.set_loader(&loader);
             eval.eval_module(ast, &globals).map_err(Error::Starlark)?;
         }
-        Ok(())
+        module
+            .freeze()
+            .map_err(|err| Error::Starlark(StarlarkError::from(err)))
     }
 
-    pub fn build(self) -> crate::policy::Policy {
-        self.builder.into_inner().build()
+    /// Evaluates the policy at `path`, relative to the file `importer`, unless
+    /// it has been evaluated already.
+    fn load(&self, importer: &str, path: &str) -> Result<FrozenModule> {
+        let identifier = resolve_policy_path(importer, path);
+        if let Some(module) = self.modules.borrow().get(&identifier) {
+            return Ok(module.clone());
+        }
+        let contents = fs::read_to_string(&identifier).map_err(|source| Error::ReadPolicy {
+            path: identifier.clone(),
+            source,
+        })?;
+        self.evaluate(&identifier, contents)
     }
 }
 
-#[derive(Debug, ProvidesStaticType)]
-struct PolicyBuilder {
-    rules_by_program: MultiMap<String, RuleRef>,
+/// Resolves `load("lib.rules", ...)` statements in the file `importer`.
+struct PolicyLoader<'a> {
+    state: &'a PolicyState,
+    importer: &'a str,
 }
 
-impl PolicyBuilder {
-    fn new() -> Self {
-        Self {
-            rules_by_program: MultiMap::new(),
+impl FileLoader for PolicyLoader<'_> {
+    fn load(&self, path: &str) -> starlark::Result<FrozenModule> {
+        self.state
+            .load(self.importer, path)
+            .map_err(|err| match err {
+                Error::Starlark(err) => err,
+                err => StarlarkError::new_other(err),
+            })
+    }
+}
+
+/// Resolves a `load()` / `include()` path against the directory of the file
+/// that names it, lexically removing `.` and `..` components so each file has
+/// one identifier.
+fn resolve_policy_path(importer: &str, path: &str) -> String {
+    let base = Path::new(importer).parent().unwrap_or(Path::new(""));
+    let mut resolved = PathBuf::new();
+    for component in base.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    resolved.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                resolved.pop();
+            }
+            other => resolved.push(other),
         }
     }
+    resolved.to_string_lossy().into_owned()
+}
 
+#[derive(Debug, Default)]
+struct PolicyBuilder {
+    rules_by_program: MultiMap<String, RuleRef>,
+    profiles: Vec<Profile>,
+    aliases: HashMap<String, String>,
+}
+
+impl PolicyBuilder {
     fn add_rule(&mut self, rule: RuleRef) {
         self.rules_by_program
             .insert(rule.program().to_string(), rule);
     }
 
+    fn add_profile(&mut self, profile: Profile) -> Result<()> {
+        if profile.name.is_empty() || profile.name == BASE_PROFILE {
+            return Err(Error::InvalidRule(format!(
+                "`{}` cannot be used as a profile name",
+                profile.name
+            )));
+        }
+        if let Some(existing) = self.find_profile(&profile.name) {
+            let location = existing
+                .source
+                .as_ref()
+                .map(|source| format!(" at {source}"))
+                .unwrap_or_default();
+            return Err(Error::InvalidRule(format!(
+                "profile `{}` is already declared{location}",
+                profile.name
+            )));
+        }
+        // Parents must be declared first, which also rules out cycles.
+        for parent in &profile.extends {
+            self.require_profile(parent)?;
+        }
+        self.profiles.push(profile);
+        Ok(())
+    }
+
+    fn add_alias(&mut self, alias: &str, program: &str) -> Result<()> {
+        if alias.is_empty() || program.is_empty() || alias == program {
+            return Err(Error::InvalidRule(format!(
+                "invalid alias `{alias}` for `{program}`"
+            )));
+        }
+        match self.aliases.get(alias) {
+            Some(existing) if existing != program => Err(Error::InvalidRule(format!(
+                "alias `{alias}` is already declared for `{existing}`"
+            ))),
+            _ => {
+                self.aliases.insert(alias.to_string(), program.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn require_profile(&self, name: &str) -> Result<()> {
+        match self.find_profile(name) {
+            Some(_) => Ok(()),
+            None => Err(Error::UnknownProfile(name.to_string())),
+        }
+    }
+
+    fn find_profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
     fn build(self) -> crate::policy::Policy {
         crate::policy::Policy::new(self.rules_by_program)
+            .with_profiles(self.profiles)
+            .with_aliases(self.aliases)
+    }
+}
+
+/// The Starlark value returned by `regex()`, `glob()`, `path_under()` and `any_args()`.
+#[derive(Debug, ProvidesStaticType, NoSerialize, Allocative)]
+struct PatternTokenValue(#[allocative(skip)] PatternToken);
+
+starlark_simple_value!(PatternTokenValue);
+
+#[starlark_value(type = "pattern_token")]
+impl<'v> StarlarkValue<'v> for PatternTokenValue {}
+
+impl fmt::Display for PatternTokenValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            PatternToken::Matcher(matcher) => write!(f, "{matcher}"),
+            PatternToken::AnyArgs(None) => write!(f, "any_args()"),
+            PatternToken::AnyArgs(Some(matcher)) => write!(f, "any_args({matcher})"),
+            other => write!(f, "{other:?}"),
+        }
     }
 }
 
@@ -96,15 +290,30 @@ fn parse_pattern<'v>(pattern: UnpackList<Value<'v>>) -> Result<Vec<PatternToken>
         .into_iter()
         .map(parse_pattern_token)
         .collect::<Result<_>>()?;
-    if tokens.is_empty() {
-        Err(Error::InvalidPattern("pattern cannot be empty".to_string()))
-    } else {
-        Ok(tokens)
+    let Some((first, rest)) = tokens.split_first() else {
+        return Err(Error::InvalidPattern("pattern cannot be empty".to_string()));
+    };
+    if first.alternatives().is_empty() {
+        return Err(Error::InvalidPattern(
+            "the first pattern element must be a string or list of strings".to_string(),
+        ));
+    }
+    if let Some((_, init)) = rest.split_last()
+        && init
+            .iter()
+            .any(|token| matches!(token, PatternToken::AnyArgs(_)))
+    {
+        return Err(Error::InvalidPattern(
+            "any_args() must be the last pattern element".to_string(),
+        ));
     }
+    Ok(tokens)
 }
 
 fn parse_pattern_token<'v>(value: Value<'v>) -> Result<PatternToken> {
-    if let Some(s) = value.unpack_str() {
+    if let Some(token) = PatternTokenValue::from_value(value) {
+        Ok(token.0.clone())
+    } else if let Some(s) = value.unpack_str() {
         Ok(PatternToken::Single(s.to_string()))
     } else if let Some(list) = ListRef::from_value(value) {
         let tokens: Vec<String> = list
@@ -132,7 +341,7 @@ fn parse_pattern_token<'v>(value: Value<'v>) -> Result<PatternToken> {
         }
     } else {
         Err(Error::InvalidPattern(format!(
-            "pattern element must be a string or list of strings (got {})",
+            "pattern element must be a string, list of strings, or matcher (got {})",
             value.get_type()
         )))
     }
@@ -195,24 +404,173 @@ fn parse_list_example(list: &ListRef) -> Result<Vec<String>> {
     }
 }
 
-fn policy_builder<'v, 'a>(eval: &Evaluator<'v, 'a, '_>) -> RefMut<'a, PolicyBuilder> {
+fn policy_state<'v, 'a>(eval: &Evaluator<'v, 'a, '_>) -> &'a PolicyState {
     #[expect(clippy::expect_used)]
     eval.extra
         .as_ref()
-        .expect("policy_builder requires Evaluator.extra to be populated")
-        .downcast_ref::<RefCell<PolicyBuilder>>()
-        .expect("Evaluator.extra must contain a PolicyBuilder")
-        .borrow_mut()
+        .expect("policy_state requires Evaluator.extra to be populated")
+        .downcast_ref::<PolicyState>()
+        .expect("Evaluator.extra must contain a PolicyState")
+}
+
+fn policy_builder<'v, 'a>(eval: &Evaluator<'v, 'a, '_>) -> RefMut<'a, PolicyBuilder> {
+    policy_state(eval).builder.borrow_mut()
+}
+
+/// The file and lines of the builtin call being evaluated.
+fn call_source(eval: &Evaluator<'_, '_, '_>) -> Option<RuleSource> {
+    eval.call_stack_top_location().map(|location| {
+        let span = location.resolve_span();
+        RuleSource {
+            path: location.filename().to_string(),
+            line: span.begin.line + 1,
+            end_line: span.end.line + 1,
+        }
+    })
+}
+
+fn parse_strings(flags: Option<UnpackList<&str>>) -> Vec<String> {
+    flags
+        .map(|flags| flags.items.into_iter().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// `cwd` takes one glob or a list of globs.
+fn parse_cwd<'v>(cwd: Option<Value<'v>>) -> Result<Vec<GlobPattern>> {
+    let Some(cwd) = cwd else {
+        return Ok(Vec::new());
+    };
+    let globs: Vec<&str> = if let Some(glob) = cwd.unpack_str() {
+        vec![glob]
+    } else if let Some(list) = ListRef::from_value(cwd) {
+        list.content()
+            .iter()
+            .map(|value| value.unpack_str())
+            .collect::<Option<_>>()
+            .ok_or_else(|| Error::InvalidRule("cwd globs must be strings".to_string()))?
+    } else {
+        return Err(Error::InvalidRule(format!(
+            "cwd must be a glob or list of globs (got {})",
+            cwd.get_type()
+        )));
+    };
+    globs.into_iter().map(GlobPattern::new).collect()
+}
+
+/// `env` maps variable names to a string, list of alternatives, `regex()` or `glob()`.
+fn parse_env<'v>(env: Option<Value<'v>>) -> Result<Vec<(String, PatternToken)>> {
+    let Some(env) = env else {
+        return Ok(Vec::new());
+    };
+    let dict = DictRef::from_value(env).ok_or_else(|| {
+        Error::InvalidRule(format!(
+            "env must be a dict of variable names to values (got {})",
+            env.get_type()
+        ))
+    })?;
+    dict.iter()
+        .map(|(name, value)| {
+            let name = name.unpack_str().ok_or_else(|| {
+                Error::InvalidRule(format!(
+                    "env variable names must be strings (got {})",
+                    name.get_type()
+                ))
+            })?;
+            Ok((name.to_string(), parse_pattern_token(value)?))
+        })
+        .collect()
 }
 
 #[starlark_module]
 fn policy_builtins(builder: &mut GlobalsBuilder) {
+    /// Matches one argument against a regex anchored at both ends.
+    fn regex(pattern: &str) -> anyhow::Result<PatternTokenValue> {
+        Ok(PatternTokenValue(PatternToken::Matcher(ArgMatcher::Regex(
+            RegexMatcher::new(pattern)?,
+        ))))
+    }
+
+    /// Matches one argument against a glob.
+    fn glob(pattern: &str) -> anyhow::Result<PatternTokenValue> {
+        Ok(PatternTokenValue(PatternToken::Matcher(ArgMatcher::Glob(
+            GlobPattern::new(pattern)?,
+        ))))
+    }
+
+    /// Matches one argument that is a path inside one of `dirs`.
+    fn path_under<'v>(
+        #[starlark(args)] dirs: UnpackTuple<&'v str>,
+    ) -> anyhow::Result<PatternTokenValue> {
+        Ok(PatternTokenValue(PatternToken::Matcher(
+            ArgMatcher::PathUnder(PathUnder::new(dirs.items)?),
+        )))
+    }
+
+    /// Matches all remaining arguments, optionally requiring each operand to match `each`.
+    fn any_args<'v>(each: Option<Value<'v>>) -> anyhow::Result<PatternTokenValue> {
+        let each = match each.map(parse_pattern_token).transpose()? {
+            None => None,
+            Some(PatternToken::Matcher(matcher)) => Some(matcher),
+            Some(other) => {
+                return Err(Error::InvalidPattern(format!(
+                    "any_args(each=...) must be regex(), glob() or path_under() (got {other:?})"
+                ))
+                .into());
+            }
+        };
+        Ok(PatternTokenValue(PatternToken::AnyArgs(each)))
+    }
+
+    /// Evaluates another policy file for its rules and profiles, relative to
+    /// this one. Unlike `load()`, it binds no symbols.
+    fn include<'v>(path: &str, eval: &mut Evaluator<'v, '_, '_>) -> anyhow::Result<NoneType> {
+        let importer = eval
+            .call_stack_top_location()
+            .map(|location| location.filename().to_string())
+            .unwrap_or_default();
+        policy_state(eval).load(&importer, path)?;
+        Ok(NoneType)
+    }
+
+    /// Declares a profile that rules opt into with `profile = name`. Rules
+    /// from the profiles in `extends` also apply when it is selected.
+    fn profile<'v>(
+        name: &str,
+        extends: Option<UnpackList<&'v str>>,
+        eval: &mut Evaluator<'v, '_, '_>,
+    ) -> anyhow::Result<NoneType> {
+        let source = call_source(eval);
+        policy_builder(eval).add_profile(Profile {
+            name: name.to_string(),
+            extends: parse_strings(extends),
+            source,
+        })?;
+        Ok(NoneType)
+    }
+
+    /// Declares that `alias` runs `program`, so rules for `program` also apply
+    /// to commands starting with `alias`, e.g. `alias("g", "git")`.
+    fn alias<'v>(
+        alias: &str,
+        program: &str,
+        eval: &mut Evaluator<'v, '_, '_>,
+    ) -> anyhow::Result<NoneType> {
+        policy_builder(eval).add_alias(alias, program)?;
+        Ok(NoneType)
+    }
+
+    #[expect(clippy::too_many_arguments)]
     fn prefix_rule<'v>(
         pattern: UnpackList<Value<'v>>,
         decision: Option<&'v str>,
         r#match: Option<UnpackList<Value<'v>>>,
         not_match: Option<UnpackList<Value<'v>>>,
         justification: Option<&'v str>,
+        flags_present: Option<UnpackList<&'v str>>,
+        flags_absent: Option<UnpackList<&'v str>>,
+        cwd: Option<Value<'v>>,
+        env: Option<Value<'v>>,
+        profile: Option<&'v str>,
         eval: &mut Evaluator<'v, '_, '_>,
     ) -> anyhow::Result<NoneType> {
         let decision = match decision {
@@ -229,6 +587,9 @@ fn policy_builtins(builder: &mut GlobalsBuilder) {
         };
 
         let pattern_tokens = parse_pattern(pattern)?;
+        let constraints =
+            ArgConstraints::new(parse_strings(flags_present), parse_strings(flags_absent))?;
+        let context = ContextConstraints::new(parse_cwd(cwd)?, parse_env(env)?)?;
 
         let matches: Vec<Vec<String>> =
             r#match.map(parse_examples).transpose()?.unwrap_or_default();
@@ -237,7 +598,12 @@ fn policy_builtins(builder: &mut GlobalsBuilder) {
             .transpose()?
             .unwrap_or_default();
 
+        let source = call_source(eval);
+
         let mut builder = policy_builder(eval);
+        if let Some(profile) = profile {
+            builder.require_profile(profile)?;
+        }
 
         let (first_token, remaining_tokens) = pattern_tokens
             .split_first()
@@ -245,25 +611,430 @@ fn policy_builtins(builder: &mut GlobalsBuilder) {
 
         let rest: Arc<[PatternToken]> = remaining_tokens.to_vec().into();
 
-        let rules: Vec<RuleRef> = first_token
+        let rules: Vec<PrefixRule> = first_token
             .alternatives()
             .iter()
-            .map(|head| {
-                Arc::new(PrefixRule {
-                    pattern: PrefixPattern {
-                        first: Arc::from(head.as_str()),
-                        rest: rest.clone(),
-                    },
-                    decision,
-                    justification: justification.clone(),
-                }) as RuleRef
+            .map(|head| PrefixRule {
+                pattern: PrefixPattern {
+                    first: Arc::from(head.as_str()),
+                    rest: rest.clone(),
+                },
+                constraints: constraints.clone(),
+                context: context.clone(),
+                profile: profile.map(str::to_string),
+                decision,
+                justification: justification.clone(),
+                source: source.clone(),
             })
             .collect();
 
         validate_not_match_examples(&rules, &not_matches)?;
         validate_match_examples(&rules, &matches)?;
 
-        rules.into_iter().for_each(|rule| builder.add_rule(rule));
+        rules
+            .into_iter()
+            .for_each(|rule| builder.add_rule(Arc::new(rule)));
         Ok(NoneType)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::EvaluationContext;
+    use crate::decision::Decision;
+    use pretty_assertions::assert_eq;
+
+    fn tokens(cmd: &str) -> Vec<String> {
+        cmd.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn decision_for(policy: &crate::policy::Policy, cmd: &str) -> Option<Decision> {
+        policy
+            .matches_for_command(&tokens(cmd), &EvaluationContext::workspace_root(), None)
+            .iter()
+            .map(crate::rule::RuleMatch::decision)
+            .max()
+    }
+
+    #[test]
+    fn matchers_compose_with_examples() {
+        let policy_src = r#"
+prefix_rule(
+    pattern = ["git", "push"],
+    flags_absent = ["--force", "-f"],
+    match = ["git push origin main"],
+    not_match = ["git push -f origin main", "git push origin main --force"],
+)
+prefix_rule(
+    pattern = ["git", "push"],
+    decision = "forbidden",
+    flags_present = ["--force", "-f"],
+    match = ["git push origin +main --force"],
+)
+prefix_rule(
+    pattern = ["rm", any_args(each = path_under("target"))],
+    match = ["rm -rf target/debug", "rm target/a target/b"],
+    not_match = ["rm -rf target/../src", "rm -rf /"],
+)
+prefix_rule(
+    pattern = ["git", "checkout", regex("release/v[0-9]+")],
+    match = ["git checkout release/v2"],
+    not_match = ["git checkout release/v2-rc"],
+)
+prefix_rule(
+    pattern = ["cargo", "test", glob("--package=*")],
+    match = ["cargo test --package=core"],
+)
+"#;
+        let mut parser = PolicyParser::new();
+        parser.parse("test.rules", policy_src).unwrap();
+        let policy = parser.build();
+
+        assert_eq!(
+            decision_for(&policy, "git push origin main"),
+            Some(Decision::Allow)
+        );
+        assert_eq!(
+            decision_for(&policy, "git push -f origin main"),
+            Some(Decision::Forbidden)
+        );
+        assert_eq!(
+            decision_for(&policy, "rm -rf target"),
+            Some(Decision::Allow)
+        );
+        assert_eq!(decision_for(&policy, "rm -rf target src"), None);
+        assert_eq!(decision_for(&policy, "xargs rm -rf"), None);
+        assert_eq!(decision_for(&policy, "xargs git push origin main"), None);
+        assert_eq!(
+            decision_for(&policy, "xargs git checkout release/v2"),
+            Some(Decision::Allow)
+        );
+        let shell_decision = |script: &str| {
+            policy
+                .check_shell(script, &EvaluationContext::workspace_root(), &|_| {
+                    Decision::Allow
+                })
+                .decision
+        };
+        assert_eq!(shell_decision("rm -rf target/debug"), Decision::Allow);
+        assert_eq!(shell_decision("rm -rf target/$X"), Decision::Prompt);
+        assert_eq!(shell_decision("rm -rf target/*"), Decision::Prompt);
+        assert_eq!(
+            policy.get_allowed_prefixes(),
+            vec![
+                tokens(r#"cargo test glob("--package=*")"#),
+                tokens(r#"git checkout regex("release/v[0-9]+")"#),
+                tokens(r#"rm path_under("target")..."#),
+            ]
+        );
+    }
+
+    #[test]
+    fn any_args_must_be_last() {
+        let mut parser = PolicyParser::new();
+        let err = parser
+            .parse(
+                "test.rules",
+                r#"prefix_rule(pattern = ["rm", any_args(), "-rf"])"#,
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("any_args() must be the last pattern element"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn invalid_regex_is_reported() {
+        let mut parser = PolicyParser::new();
+        let err = parser
+            .parse(
+                "test.rules",
+                r#"prefix_rule(pattern = ["git", regex("(")])"#,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("invalid regex `(`"), "{err}");
+    }
+
+    #[test]
+    fn cwd_and_env_conditions() {
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                "test.rules",
+                r#"
+prefix_rule(
+    pattern = ["terraform", "apply"],
+    decision = "forbidden",
+    cwd = ["infra", "infra/**"],
+    match = ["terraform apply -auto-approve"],
+)
+prefix_rule(pattern = ["aws"], decision = "prompt", env = {"AWS_PROFILE": glob("prod*")})
+"#,
+            )
+            .unwrap();
+        let policy = parser.build();
+        let decision_in = |cmd: &str, cwd: &str, env: &[(&str, &str)]| {
+            let context = EvaluationContext {
+                cwd: Some(cwd.into()),
+                env: env
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+                profile: None,
+            };
+            policy
+                .matches_for_command(&tokens(cmd), &context, None)
+                .iter()
+                .map(crate::rule::RuleMatch::decision)
+                .max()
+        };
+
+        assert_eq!(
+            decision_in("terraform apply", "infra/prod", &[]),
+            Some(Decision::Forbidden)
+        );
+        assert_eq!(decision_in("terraform apply", "sandbox", &[]), None);
+        assert_eq!(
+            decision_in("terraform apply", "infra", &[]),
+            Some(Decision::Forbidden)
+        );
+        assert_eq!(
+            decision_in("aws s3 ls", ".", &[("AWS_PROFILE", "prod-eu")]),
+            Some(Decision::Prompt)
+        );
+        assert_eq!(
+            decision_in("aws s3 ls", ".", &[("AWS_PROFILE", "dev")]),
+            None
+        );
+        assert_eq!(policy.get_allowed_prefixes(), Vec::<Vec<String>>::new());
+
+        let err = PolicyParser::new()
+            .parse(
+                "test.rules",
+                r#"prefix_rule(pattern = ["aws"], env = ["AWS_PROFILE"])"#,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("env must be a dict"), "{err}");
+    }
+
+    #[test]
+    fn load_and_include_resolve_relative_to_the_loading_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("lib");
+        fs::create_dir(&lib).unwrap();
+        fs::write(
+            lib.join("common.rules"),
+            r#"
+def forbid(*pattern):
+    prefix_rule(pattern = list(pattern), decision = "forbidden")
+
+include("git.rules")
+"#,
+        )
+        .unwrap();
+        fs::write(
+            lib.join("git.rules"),
+            r#"prefix_rule(pattern = ["git", "status"])"#,
+        )
+        .unwrap();
+
+        let policy_path = dir.path().join("team.rules");
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                &policy_path.display().to_string(),
+                r#"
+load("lib/common.rules", "forbid")
+include("./lib/../lib/git.rules")
+forbid("rm", "-rf")
+"#,
+            )
+            .unwrap();
+        let policy = parser.build();
+
+        assert_eq!(decision_for(&policy, "git status"), Some(Decision::Allow));
+        assert_eq!(
+            policy
+                .matches_for_command(&tokens("git status"), &EvaluationContext::default(), None)
+                .len(),
+            1
+        );
+        let rm =
+            policy.matches_for_command(&tokens("rm -rf /"), &EvaluationContext::default(), None);
+        let [
+            crate::rule::RuleMatch::PrefixRuleMatch {
+                decision,
+                source: Some(source),
+                ..
+            },
+        ] = rm.as_slice()
+        else {
+            panic!("expected one prefix rule match, got {rm:?}");
+        };
+        assert_eq!(*decision, Decision::Forbidden);
+        assert_eq!(
+            (source.path.as_str(), source.line),
+            (lib.join("common.rules").to_str().unwrap(), 3)
+        );
+    }
+
+    #[test]
+    fn load_cycles_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.rules"), r#"include("b.rules")"#).unwrap();
+        fs::write(dir.path().join("b.rules"), r#"include("a.rules")"#).unwrap();
+
+        let a = dir.path().join("a.rules").display().to_string();
+        let err = PolicyParser::new()
+            .parse(&a, r#"include("b.rules")"#)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("policy files load each other in a cycle"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn profiles_override_base_rules() {
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                "org.rules",
+                r#"
+profile("readonly")
+profile("ci", extends = ["readonly"])
+
+prefix_rule(pattern = ["git", "push"], decision = "prompt")
+prefix_rule(pattern = ["git", "push"], decision = "forbidden", profile = "readonly")
+prefix_rule(pattern = ["git", "push"], profile = "ci")
+prefix_rule(pattern = ["rm"], decision = "forbidden", profile = "readonly")
+"#,
+            )
+            .unwrap();
+        let policy = parser.build();
+        let decision_with = |cmd: &str, profile: Option<&str>| {
+            let context = EvaluationContext {
+                profile: profile.map(str::to_string),
+                ..EvaluationContext::default()
+            };
+            policy
+                .matches_for_command(&tokens(cmd), &context, None)
+                .iter()
+                .map(crate::rule::RuleMatch::decision)
+                .max()
+        };
+
+        assert_eq!(decision_with("git push", None), Some(Decision::Prompt));
+        assert_eq!(
+            decision_with("git push", Some("readonly")),
+            Some(Decision::Forbidden)
+        );
+        assert_eq!(decision_with("git push", Some("ci")), Some(Decision::Allow));
+        assert_eq!(
+            decision_with("rm -rf", Some("ci")),
+            Some(Decision::Forbidden)
+        );
+        assert_eq!(decision_with("rm -rf", None), None);
+        assert_eq!(
+            policy.resolution_order(Some("ci")).unwrap(),
+            vec!["ci", "readonly", "base"]
+        );
+
+        for (src, message) in [
+            (
+                r#"prefix_rule(pattern = ["ls"], profile = "ci")"#,
+                "unknown profile `ci`",
+            ),
+            (
+                r#"profile("ci", extends = ["readonly"])"#,
+                "unknown profile `readonly`",
+            ),
+            (
+                r#"profile("base")"#,
+                "`base` cannot be used as a profile name",
+            ),
+            (
+                "profile(\"ci\")\nprofile(\"ci\")",
+                "profile `ci` is already declared at test.rules:1",
+            ),
+        ] {
+            let err = PolicyParser::new().parse("test.rules", src).unwrap_err();
+            assert!(err.to_string().contains(message), "{err}");
+        }
+    }
+
+    #[test]
+    fn rules_apply_to_aliases_and_wrapped_commands() {
+        let mut parser = PolicyParser::new();
+        parser
+            .parse(
+                "test.rules",
+                r#"
+alias("./node_modules/.bin/eslint", "eslint")
+prefix_rule(pattern = ["git", "push"], decision = "forbidden")
+prefix_rule(pattern = ["eslint"], decision = "prompt")
+prefix_rule(pattern = ["sudo"], decision = "prompt")
+"#,
+            )
+            .unwrap();
+        let policy = parser.build();
+
+        assert_eq!(
+            decision_for(&policy, "./node_modules/.bin/eslint --fix src"),
+            Some(Decision::Prompt)
+        );
+        let matches = policy.matches_for_command(
+            &tokens("sudo env FOO=1 git push"),
+            &EvaluationContext::default(),
+            None,
+        );
+        assert_eq!(
+            matches
+                .iter()
+                .map(|rule_match| match rule_match {
+                    crate::rule::RuleMatch::PrefixRuleMatch {
+                        matched_prefix,
+                        rewrites,
+                        ..
+                    } => (matched_prefix.join(" "), rewrites.len()),
+                    other => panic!("unexpected match {other:?}"),
+                })
+                .collect::<Vec<_>>(),
+            vec![("sudo".to_string(), 0), ("git push".to_string(), 2)]
+        );
+
+        let shell_decision = |script: &str| {
+            policy
+                .check_shell(script, &EvaluationContext::workspace_root(), &|_| {
+                    Decision::Allow
+                })
+                .decision
+        };
+        for script in [
+            "env -S 'git push origin main'",
+            "env --split-string='git push' origin",
+            "exec git push",
+            "timeout 5 git push",
+            "nohup git push",
+            "stdbuf -oL git push",
+        ] {
+            assert_eq!(shell_decision(script), Decision::Forbidden, "{script}");
+        }
+
+        let err = PolicyParser::new()
+            .parse(
+                "test.rules",
+                "alias(\"g\", \"git\")\nalias(\"g\", \"grep\")",
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("alias `g` is already declared for `git`"),
+            "{err}"
+        );
+    }
+}
@@ -1,26 +1,49 @@
+use crate::context::ContextConstraints;
+use crate::context::EvaluationContext;
 use crate::decision::Decision;
 use crate::error::Error;
 use crate::error::Result;
+use crate::matcher::ArgConstraints;
+use crate::normalize::NormalizedCommand;
+use crate::normalize::normalize_command;
+use crate::profile::BASE_PROFILE;
+use crate::profile::Profile;
+use crate::profile::resolution_order;
 use crate::rule::PatternToken;
 use crate::rule::PrefixPattern;
 use crate::rule::PrefixRule;
 use crate::rule::RuleMatch;
 use crate::rule::RuleRef;
+use crate::shell::parse_shell_script;
 use multimap::MultiMap;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::sync::Arc;
 
-type HeuristicsFallback<'a> = Option<&'a dyn Fn(&[String]) -> Decision>;
+pub(crate) type HeuristicsFallback<'a> = Option<&'a dyn Fn(&[String]) -> Decision>;
 
 #[derive(Clone, Debug)]
 pub struct Policy {
     rules_by_program: MultiMap<String, RuleRef>,
+    profiles: Vec<Profile>,
+    /// Program aliases declared with `alias()`, e.g. `g` for `git`.
+    aliases: HashMap<String, String>,
 }
 
 impl Policy {
     pub fn new(rules_by_program: MultiMap<String, RuleRef>) -> Self {
-        Self { rules_by_program }
+        Self {
+            rules_by_program,
+            profiles: Vec::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    pub fn with_profiles(mut self, profiles: Vec<Profile>) -> Self {
+        self.profiles = profiles;
+        self
     }
 
     pub fn empty() -> Self {
@@ -31,6 +54,74 @@ impl Policy {
         &self.rules_by_program
     }
 
+    pub fn with_aliases(mut self, aliases: HashMap<String, String>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    pub fn aliases(&self) -> &HashMap<String, String> {
+        &self.aliases
+    }
+
+    /// The forms of `cmd` that rules are checked against; see
+    /// [`normalize_command`].
+    pub fn normalize(&self, cmd: &[String], context: &EvaluationContext) -> Vec<NormalizedCommand> {
+        normalize_command(cmd, context, &self.aliases)
+    }
+
+    /// Names of the environment variables tested by `env` conditions.
+    pub fn env_names(&self) -> BTreeSet<&str> {
+        env_names(
+            self.rules_by_program
+                .iter_all()
+                .flat_map(|(_, rules)| rules),
+        )
+    }
+
+    /// Names of the environment variables tested by the `env` conditions of
+    /// rules for any program `cmd` runs, in any of its normalised forms.
+    pub fn env_names_for_command(
+        &self,
+        cmd: &[String],
+        context: &EvaluationContext,
+    ) -> BTreeSet<&str> {
+        let forms = self.normalize(cmd, context);
+        env_names(forms.iter().flat_map(|form| self.rules_for(form)))
+    }
+
+    /// Like [`Policy::env_names_for_command`], for every command in a bash
+    /// script.
+    pub fn env_names_for_shell(&self, script: &str, context: &EvaluationContext) -> BTreeSet<&str> {
+        parse_shell_script(script)
+            .commands
+            .iter()
+            .flat_map(|command| {
+                let context = context.with_env_overrides(&command.env);
+                self.env_names_for_command(&command.argv, &context)
+            })
+            .collect()
+    }
+
+    /// Profiles in declaration order.
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    /// The layers of rules consulted when `profile` is selected, most specific
+    /// first and ending with [`BASE_PROFILE`]. Errors if `profile` was never
+    /// declared.
+    pub fn resolution_order(&self, profile: Option<&str>) -> Result<Vec<String>> {
+        resolution_order(&self.profiles, profile)
+    }
+
+    /// The layers for `context.profile`. An unknown profile selects the base
+    /// rules only; callers that take a profile from users should validate it
+    /// with [`Policy::resolution_order`] first.
+    pub(crate) fn active_layers(&self, context: &EvaluationContext) -> Vec<String> {
+        self.resolution_order(context.profile.as_deref())
+            .unwrap_or_else(|_| vec![BASE_PROFILE.to_string()])
+    }
+
     pub fn get_allowed_prefixes(&self) -> Vec<Vec<String>> {
         let mut prefixes = Vec::new();
 
@@ -39,7 +130,14 @@ impl Policy {
                 let Some(prefix_rule) = rule.as_any().downcast_ref::<PrefixRule>() else {
                     continue;
                 };
-                if prefix_rule.decision != Decision::Allow {
+                // A rule with flag or context constraints, or one that only
+                // applies under a profile, does not allow its prefix
+                // unconditionally.
+                if prefix_rule.decision != Decision::Allow
+                    || !prefix_rule.constraints.is_empty()
+                    || !prefix_rule.context.is_empty()
+                    || prefix_rule.profile.is_some()
+                {
                     continue;
                 }
 
@@ -69,26 +167,36 @@ impl Policy {
                     .collect::<Vec<_>>()
                     .into(),
             },
+            constraints: ArgConstraints::default(),
+            context: ContextConstraints::default(),
+            profile: None,
             decision,
             justification: None,
+            source: None,
         });
 
         self.rules_by_program.insert(first_token.clone(), rule);
         Ok(())
     }
 
-    pub fn check<F>(&self, cmd: &[String], heuristics_fallback: &F) -> Evaluation
+    pub fn check<F>(
+        &self,
+        cmd: &[String],
+        context: &EvaluationContext,
+        heuristics_fallback: &F,
+    ) -> Evaluation
     where
         F: Fn(&[String]) -> Decision,
     {
-        let matched_rules = self.matches_for_command(cmd, Some(heuristics_fallback));
+        let matched_rules = self.matches_for_command(cmd, context, Some(heuristics_fallback));
         Evaluation::from_matches(matched_rules)
     }
 
-    /// Checks multiple commands and aggregates the results.
+    /// Checks multiple commands run in the same context and aggregates the results.
     pub fn check_multiple<Commands, F>(
         &self,
         commands: Commands,
+        context: &EvaluationContext,
         heuristics_fallback: &F,
     ) -> Evaluation
     where
@@ -99,33 +207,113 @@ impl Policy {
         let matched_rules: Vec<RuleMatch> = commands
             .into_iter()
             .flat_map(|command| {
-                self.matches_for_command(command.as_ref(), Some(heuristics_fallback))
+                self.matches_for_command(command.as_ref(), context, Some(heuristics_fallback))
             })
             .collect();
 
         Evaluation::from_matches(matched_rules)
     }
 
+    /// Evaluates every simple command in a bash script. A script that runs no
+    /// commands is allowed; see [`Policy::matches_for_shell`].
+    pub fn check_shell<F>(
+        &self,
+        script: &str,
+        context: &EvaluationContext,
+        heuristics_fallback: &F,
+    ) -> Evaluation
+    where
+        F: Fn(&[String]) -> Decision,
+    {
+        let matched_rules = self.matches_for_shell(script, context, Some(heuristics_fallback));
+        if matched_rules.is_empty() {
+            return Evaluation {
+                decision: Decision::Allow,
+                matched_rules,
+            };
+        }
+        Evaluation::from_matches(matched_rules)
+    }
+
+    /// Returns matching rules for each simple command in a bash script, as
+    /// split by [`parse_shell_script`]. Each command is evaluated with its
+    /// own `VAR=value` assignments applied on top of `context`. Constructs
+    /// that cannot be analysed, such as `eval` or a syntax error, each add a
+    /// [`RuleMatch::UnanalyzableShellMatch`] with a `prompt` decision, so the
+    /// result is never more permissive than prompting for them.
+    pub fn matches_for_shell(
+        &self,
+        script: &str,
+        context: &EvaluationContext,
+        heuristics_fallback: HeuristicsFallback<'_>,
+    ) -> Vec<RuleMatch> {
+        let parsed = parse_shell_script(script);
+        let mut matched_rules: Vec<RuleMatch> = parsed
+            .commands
+            .iter()
+            .flat_map(|command| {
+                let context = context.with_env_overrides(&command.env);
+                self.matches_for_command(&command.argv, &context, heuristics_fallback)
+            })
+            .collect();
+        matched_rules.extend(parsed.unanalyzable.into_iter().map(|unanalyzable| {
+            RuleMatch::UnanalyzableShellMatch {
+                text: unanalyzable.text,
+                reason: unanalyzable.reason,
+                decision: Decision::Prompt,
+            }
+        }));
+        matched_rules
+    }
+
     /// Returns matching rules for the given command. If no rules match and
     /// `heuristics_fallback` is provided, returns a single
     /// `HeuristicsRuleMatch` with the decision rendered by
     /// `heuristics_fallback`.
     ///
+    /// Rules are checked against the command as written and against each of
+    /// its [normalised forms](Policy::normalize), so rules for `git` also
+    /// apply to `sudo git` or `/usr/bin/git`; such matches list the rewrites
+    /// in `rewrites`. Allow rules that check every operand or the absence of a
+    /// flag never match forms that get more arguments at run time, such as
+    /// `rm` under `xargs rm`.
+    ///
+    /// Only base rules and rules of the profiles in `context.profile`'s
+    /// [resolution order](Policy::resolution_order) are considered. When rules
+    /// from several layers match, only those from the most specific layer are
+    /// returned, so a profile can relax or tighten a base rule.
+    ///
     /// If `heuristics_fallback.is_some()`, then the returned vector is
     /// guaranteed to be non-empty.
     pub fn matches_for_command(
         &self,
         cmd: &[String],
+        context: &EvaluationContext,
         heuristics_fallback: HeuristicsFallback<'_>,
     ) -> Vec<RuleMatch> {
-        let matched_rules: Vec<RuleMatch> = match cmd.first() {
-            Some(first) => self
-                .rules_by_program
-                .get_vec(first)
-                .map(|rules| rules.iter().filter_map(|rule| rule.matches(cmd)).collect())
-                .unwrap_or_default(),
-            None => Vec::new(),
-        };
+        let layers = self.active_layers(context);
+        let layered_matches: Vec<(usize, RuleMatch)> = self
+            .normalize(cmd, context)
+            .iter()
+            .flat_map(|form| {
+                self.rules_for(form)
+                    .filter_map(|rule| {
+                        let layer = layer_index(&layers, rule.profile())?;
+                        if form.unknown_args && !rule.matches_unknown_args() {
+                            return None;
+                        }
+                        let rule_match = rule.matches(&form.argv, &form.context)?;
+                        Some((layer, rule_match.with_rewrites(&form.rewrites)))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let deciding_layer = layered_matches.iter().map(|(layer, _)| *layer).min();
+        let matched_rules: Vec<RuleMatch> = layered_matches
+            .into_iter()
+            .filter(|(layer, _)| Some(*layer) == deciding_layer)
+            .map(|(_, rule_match)| rule_match)
+            .collect();
 
         if matched_rules.is_empty()
             && let Some(heuristics_fallback) = heuristics_fallback
@@ -138,12 +326,42 @@ impl Policy {
             matched_rules
         }
     }
+
+    /// The rules registered for the program of `form`.
+    pub(crate) fn rules_for<'a>(
+        &'a self,
+        form: &NormalizedCommand,
+    ) -> impl Iterator<Item = &'a RuleRef> + use<'a> {
+        form.argv
+            .first()
+            .and_then(|program| self.rules_by_program.get_vec(program))
+            .into_iter()
+            .flatten()
+    }
+}
+
+fn env_names<'a>(rules: impl IntoIterator<Item = &'a RuleRef>) -> BTreeSet<&'a str> {
+    rules
+        .into_iter()
+        .filter_map(|rule| rule.as_any().downcast_ref::<PrefixRule>())
+        .flat_map(|rule| rule.context.env.iter().map(|(name, _)| name.as_str()))
+        .collect()
+}
+
+/// Position of the layer holding rules of `profile` in `layers`, or `None` if
+/// the profile is not active.
+pub(crate) fn layer_index(layers: &[String], profile: Option<&str>) -> Option<usize> {
+    let profile = profile.unwrap_or(BASE_PROFILE);
+    layers.iter().position(|layer| layer == profile)
 }
 
-fn render_pattern_token(token: &PatternToken) -> String {
+pub(crate) fn render_pattern_token(token: &PatternToken) -> String {
     match token {
         PatternToken::Single(value) => value.clone(),
         PatternToken::Alts(alternatives) => format!("[{}]", alternatives.join("|")),
+        PatternToken::Matcher(matcher) => matcher.to_string(),
+        PatternToken::AnyArgs(None) => "...".to_string(),
+        PatternToken::AnyArgs(Some(matcher)) => format!("{matcher}..."),
     }
 }
 
@@ -159,7 +377,7 @@ impl Evaluation {
     pub fn is_match(&self) -> bool {
         self.matched_rules
             .iter()
-            .any(|rule_match| !matches!(rule_match, RuleMatch::HeuristicsRuleMatch { .. }))
+            .any(|rule_match| matches!(rule_match, RuleMatch::PrefixRuleMatch { .. }))
     }
 
     /// Caller is responsible for ensuring that `matched_rules` is non-empty.
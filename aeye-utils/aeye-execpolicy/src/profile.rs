@@ -0,0 +1,82 @@
+use crate::error::Error;
+use crate::error::Result;
+use crate::rule::RuleSource;
+
+/// The layer holding every rule that does not name a profile. It is always
+/// active and always consulted last.
+pub const BASE_PROFILE: &str = "base";
+
+/// A named set of rules declared with `profile()`, e.g. `readonly` or `ci`,
+/// that can be selected at evaluation time.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    /// Profiles whose rules also apply, each consulted after this one.
+    pub extends: Vec<String>,
+    pub source: Option<RuleSource>,
+}
+
+/// The layers consulted when evaluating with `profile`, most specific first:
+/// the profile itself, then the profiles it extends (depth-first, in declaration
+/// order, each listed once), then [`BASE_PROFILE`].
+pub(crate) fn resolution_order(profiles: &[Profile], profile: Option<&str>) -> Result<Vec<String>> {
+    let mut order = Vec::new();
+    if let Some(name) = profile.filter(|name| *name != BASE_PROFILE) {
+        push_layers(profiles, name, &mut order)?;
+    }
+    order.push(BASE_PROFILE.to_string());
+    Ok(order)
+}
+
+fn push_layers(profiles: &[Profile], name: &str, order: &mut Vec<String>) -> Result<()> {
+    let profile = profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| Error::UnknownProfile(name.to_string()))?;
+    if order.contains(&profile.name) {
+        return Ok(());
+    }
+    order.push(profile.name.clone());
+    for parent in &profile.extends {
+        push_layers(profiles, parent, order)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn profile(name: &str, extends: &[&str]) -> Profile {
+        Profile {
+            name: name.to_string(),
+            extends: extends.iter().map(ToString::to_string).collect(),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn layers_are_most_specific_first_and_deduplicated() {
+        let profiles = vec![
+            profile("readonly", &[]),
+            profile("ci", &["readonly"]),
+            profile("developer", &["ci", "readonly"]),
+        ];
+        assert_eq!(
+            resolution_order(&profiles, Some("developer")).unwrap(),
+            vec!["developer", "ci", "readonly", "base"]
+        );
+        assert_eq!(resolution_order(&profiles, None).unwrap(), vec!["base"]);
+        assert_eq!(
+            resolution_order(&profiles, Some("base")).unwrap(),
+            vec!["base"]
+        );
+        assert_eq!(
+            resolution_order(&profiles, Some("release"))
+                .unwrap_err()
+                .to_string(),
+            "unknown profile `release`"
+        );
+    }
+}
@@ -1,32 +1,49 @@
+use crate::context::ContextConstraints;
+use crate::context::EvaluationContext;
 use crate::decision::Decision;
 use crate::error::Error;
 use crate::error::Result;
+use crate::matcher::ArgConstraints;
+use crate::matcher::ArgMatcher;
+use crate::matcher::operands;
+use crate::normalize::CommandRewrite;
+use crate::shell::UnanalyzableReason;
 use serde::Deserialize;
 use serde::Serialize;
 use shlex::try_join;
 use std::any::Any;
+use std::fmt;
 use std::fmt::Debug;
 use std::sync::Arc;
 
-/// Matches a single command token, either a fixed string or one of several allowed alternatives.
+/// Matches a single command token, either a fixed string, one of several allowed alternatives,
+/// or an [`ArgMatcher`]. [`PatternToken::AnyArgs`] instead consumes every remaining token.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum PatternToken {
     Single(String),
     Alts(Vec<String>),
+    Matcher(ArgMatcher),
+    /// Matches the rest of the command; only valid as the last token. When a matcher is given,
+    /// every remaining operand (non-flag argument) must satisfy it.
+    AnyArgs(Option<ArgMatcher>),
 }
 
 impl PatternToken {
-    fn matches(&self, token: &str) -> bool {
+    pub(crate) fn matches(&self, token: &str, context: &EvaluationContext) -> bool {
         match self {
             Self::Single(expected) => expected == token,
             Self::Alts(alternatives) => alternatives.iter().any(|alt| alt == token),
+            Self::Matcher(matcher) => matcher.matches(token, context),
+            Self::AnyArgs(_) => true,
         }
     }
 
+    /// The literal strings this token accepts; empty for matcher tokens.
     pub fn alternatives(&self) -> &[String] {
         match self {
             Self::Single(expected) => std::slice::from_ref(expected),
             Self::Alts(alternatives) => alternatives,
+            Self::Matcher(_) | Self::AnyArgs(_) => &[],
         }
     }
 }
@@ -40,19 +57,33 @@ pub struct PrefixPattern {
 }
 
 impl PrefixPattern {
-    pub fn matches_prefix(&self, cmd: &[String]) -> Option<Vec<String>> {
-        let pattern_length = self.rest.len() + 1;
-        if cmd.len() < pattern_length || cmd[0] != self.first.as_ref() {
+    /// `context` is only consulted by [`ArgMatcher::PathUnder`] tokens.
+    pub fn matches_prefix(
+        &self,
+        cmd: &[String],
+        context: &EvaluationContext,
+    ) -> Option<Vec<String>> {
+        let (program, args) = cmd.split_first()?;
+        if program != self.first.as_ref() {
             return None;
         }
 
-        for (pattern_token, cmd_token) in self.rest.iter().zip(&cmd[1..pattern_length]) {
-            if !pattern_token.matches(cmd_token) {
+        for (index, pattern_token) in self.rest.iter().enumerate() {
+            if let PatternToken::AnyArgs(each) = pattern_token {
+                let remaining = &args[index..];
+                if let Some(each) = each
+                    && !operands(remaining).all(|operand| each.matches(operand, context))
+                {
+                    return None;
+                }
+                return Some(cmd.to_vec());
+            }
+            if !pattern_token.matches(args.get(index)?, context) {
                 return None;
             }
         }
 
-        Some(cmd[..pattern_length].to_vec())
+        Some(cmd[..self.rest.len() + 1].to_vec())
     }
 }
 
@@ -69,11 +100,25 @@ pub enum RuleMatch {
         /// (e.g., prompt reasons or rejection messages).
         #[serde(skip_serializing_if = "Option::is_none")]
         justification: Option<String>,
+        /// Where the matching rule was defined.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<RuleSource>,
+        /// How the command was rewritten before this rule matched it, e.g.
+        /// `sudo` stripped from `sudo git push`; empty if it matched as written.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        rewrites: Vec<CommandRewrite>,
     },
     HeuristicsRuleMatch {
         command: Vec<String>,
         decision: Decision,
     },
+    /// A part of a shell script that could not be split into commands; see
+    /// [`crate::Policy::check_shell`].
+    UnanalyzableShellMatch {
+        text: String,
+        reason: UnanalyzableReason,
+        decision: Decision,
+    },
 }
 
 impl RuleMatch {
@@ -81,53 +126,138 @@ impl RuleMatch {
         match self {
             Self::PrefixRuleMatch { decision, .. } => *decision,
             Self::HeuristicsRuleMatch { decision, .. } => *decision,
+            Self::UnanalyzableShellMatch { decision, .. } => *decision,
         }
     }
+
+    /// Records that the command was rewritten before this rule matched it.
+    pub(crate) fn with_rewrites(mut self, applied: &[CommandRewrite]) -> Self {
+        if let Self::PrefixRuleMatch { rewrites, .. } = &mut self {
+            rewrites.extend_from_slice(applied);
+        }
+        self
+    }
+}
+
+/// The policy file and lines spanned by the `prefix_rule` call that defined a rule.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSource {
+    pub path: String,
+    /// 1-based.
+    pub line: usize,
+    /// 1-based and inclusive; equal to `line` for single-line calls.
+    pub end_line: usize,
+}
+
+impl fmt::Display for RuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.path, self.line)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PrefixRule {
     pub pattern: PrefixPattern,
+    /// Flag checks applied to the whole command, wherever the flags appear.
+    pub constraints: ArgConstraints,
+    /// Working-directory and environment conditions.
+    pub context: ContextConstraints,
+    /// The profile the rule belongs to; `None` for base rules, which apply
+    /// whichever profile is selected.
+    pub profile: Option<String>,
     pub decision: Decision,
     pub justification: Option<String>,
+    /// `None` for rules added programmatically, e.g. via [`crate::Policy::add_prefix_rule`].
+    pub source: Option<RuleSource>,
 }
 
 pub trait Rule: Any + Debug + Send + Sync {
     fn program(&self) -> &str;
 
-    fn matches(&self, cmd: &[String]) -> Option<RuleMatch>;
+    fn matches(&self, cmd: &[String], context: &EvaluationContext) -> Option<RuleMatch>;
+
+    /// The profile the rule belongs to, or `None` for the base layer.
+    fn profile(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether the rule may match a command that gets more arguments at run
+    /// time, e.g. `rm` under `xargs rm`.
+    fn matches_unknown_args(&self) -> bool {
+        true
+    }
 
     fn as_any(&self) -> &dyn Any;
 }
 
 pub type RuleRef = Arc<dyn Rule>;
 
+impl PrefixRule {
+    /// Checks the pattern and flag constraints only, ignoring the `cwd` and
+    /// `env` conditions. `context` still locates `path_under` arguments.
+    /// Returns the matched prefix.
+    pub(crate) fn matches_argv(
+        &self,
+        cmd: &[String],
+        context: &EvaluationContext,
+    ) -> Option<Vec<String>> {
+        self.pattern
+            .matches_prefix(cmd, context)
+            .filter(|_| self.constraints.matches(&cmd[1..]))
+    }
+}
+
 impl Rule for PrefixRule {
     fn program(&self) -> &str {
         self.pattern.first.as_ref()
     }
 
-    fn matches(&self, cmd: &[String]) -> Option<RuleMatch> {
-        self.pattern
-            .matches_prefix(cmd)
+    fn matches(&self, cmd: &[String], context: &EvaluationContext) -> Option<RuleMatch> {
+        self.matches_argv(cmd, context)
+            .filter(|_| self.context.matches(context))
             .map(|matched_prefix| RuleMatch::PrefixRuleMatch {
                 matched_prefix,
                 decision: self.decision,
                 justification: self.justification.clone(),
+                source: self.source.clone(),
+                rewrites: Vec::new(),
             })
     }
 
+    fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    /// An allow rule checking every operand or the absence of a flag cannot
+    /// vouch for arguments it does not see.
+    fn matches_unknown_args(&self) -> bool {
+        self.decision != Decision::Allow
+            || (self.constraints.flags_absent.is_empty()
+                && !self
+                    .pattern
+                    .rest
+                    .iter()
+                    .any(|token| matches!(token, PatternToken::AnyArgs(Some(_)))))
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
 }
 
 /// Count how many rules match each provided example and error if any example is unmatched.
-pub(crate) fn validate_match_examples(rules: &[RuleRef], matches: &[Vec<String>]) -> Result<()> {
+/// Examples are plain commands run from the workspace root, so `cwd` / `env`
+/// conditions are not checked.
+pub(crate) fn validate_match_examples(rules: &[PrefixRule], matches: &[Vec<String>]) -> Result<()> {
+    let context = EvaluationContext::workspace_root();
     let mut unmatched_examples = Vec::new();
 
     for example in matches {
-        if rules.iter().any(|rule| rule.matches(example).is_some()) {
+        if rules
+            .iter()
+            .any(|rule| rule.matches_argv(example, &context).is_some())
+        {
             continue;
         }
 
@@ -149,11 +279,15 @@ pub(crate) fn validate_match_examples(rules: &[RuleRef], matches: &[Vec<String>]
 
 /// Ensure that no rule matches any provided negative example.
 pub(crate) fn validate_not_match_examples(
-    rules: &[RuleRef],
+    rules: &[PrefixRule],
     not_matches: &[Vec<String>],
 ) -> Result<()> {
+    let context = EvaluationContext::workspace_root();
     for example in not_matches {
-        if let Some(rule) = rules.iter().find(|rule| rule.matches(example).is_some()) {
+        if let Some(rule) = rules
+            .iter()
+            .find(|rule| rule.matches_argv(example, &context).is_some())
+        {
             return Err(Error::ExampleDidMatch {
                 rule: format!("{rule:?}"),
                 example: try_join(example.iter().map(String::as_str))
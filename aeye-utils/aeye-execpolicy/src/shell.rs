@@ -0,0 +1,565 @@
+use std::fmt;
+
+use serde::Deserialize;
+use serde::Serialize;
+use tree_sitter::Node;
+use tree_sitter::Parser;
+use tree_sitter_bash::LANGUAGE as BASH;
+
+/// Shells whose `-c` script argument is parsed recursively.
+const WRAPPER_SHELLS: &[&str] = &["sh", "bash", "zsh", "dash"];
+
+/// Long shell options that take no value.
+const SHELL_LONG_FLAGS: &[&str] = &[
+    "--login",
+    "--noediting",
+    "--noprofile",
+    "--norc",
+    "--posix",
+    "--restricted",
+    "--verbose",
+];
+
+/// Long shell options whose value is the next argument.
+const SHELL_LONG_OPTIONS: &[&str] = &["--init-file", "--rcfile"];
+
+/// The simple commands a shell script would run, in source order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ShellCommands {
+    /// One entry per simple command, including those inside pipelines,
+    /// `&&` / `||` / `;` lists, subshells, `$(...)`, control flow and
+    /// `sh -c` / `bash -lc` wrappers.
+    pub commands: Vec<SimpleCommand>,
+    /// Parts of the script whose effect cannot be determined statically.
+    pub unanalyzable: Vec<Unanalyzable>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SimpleCommand {
+    /// Arguments containing expansions are kept as written, e.g. `$HOME`,
+    /// and the command is also reported as [`Unanalyzable`].
+    pub argv: Vec<String>,
+    /// Assignments written before the command, e.g. `AWS_PROFILE=prod` in
+    /// `AWS_PROFILE=prod terraform apply`, in source order.
+    pub env: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Unanalyzable {
+    /// The source text of the construct.
+    pub text: String,
+    pub reason: UnanalyzableReason,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UnanalyzableReason {
+    /// The script is not valid shell syntax.
+    ParseError,
+    /// `eval` runs a string built at run time.
+    Eval,
+    /// `source` / `.` runs another script.
+    Source,
+    /// The command name comes from an expansion, e.g. `$cmd args`.
+    DynamicCommand,
+    /// An argument or assignment is only known at run time, e.g. `$dir`,
+    /// `$(cat list)` or `*.rs`.
+    DynamicArgument,
+    /// A shell was given options that could not be parsed, so whether and
+    /// what it runs from `-c` is unknown.
+    ShellOptions,
+}
+
+impl fmt::Display for UnanalyzableReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::ParseError => "the script could not be parsed",
+            Self::Eval => "`eval` runs a command built at run time",
+            Self::Source => "sourcing a script runs commands that cannot be checked",
+            Self::DynamicCommand => "the command name is only known at run time",
+            Self::DynamicArgument => "an argument is only known at run time",
+            Self::ShellOptions => "the shell's options could not be parsed",
+        })
+    }
+}
+
+/// Splits a bash script into the simple commands it would run.
+pub fn parse_shell_script(script: &str) -> ShellCommands {
+    let mut parsed = ShellCommands::default();
+    collect_script(script, &mut parsed);
+    parsed
+}
+
+fn collect_script(script: &str, parsed: &mut ShellCommands) {
+    let mut parser = Parser::new();
+    let tree = parser
+        .set_language(&BASH.into())
+        .ok()
+        .and_then(|()| parser.parse(script, None));
+    let Some(tree) = tree else {
+        parsed.unanalyzable.push(Unanalyzable {
+            text: script.to_string(),
+            reason: UnanalyzableReason::ParseError,
+        });
+        return;
+    };
+
+    let root = tree.root_node();
+    if root.has_error() {
+        // Keep whatever commands were recognised so forbidding rules still
+        // apply, but never treat a broken script as fully understood.
+        parsed.unanalyzable.push(Unanalyzable {
+            text: script.to_string(),
+            reason: UnanalyzableReason::ParseError,
+        });
+    }
+    collect_node(root, script.as_bytes(), parsed);
+}
+
+fn collect_node(node: Node<'_>, src: &[u8], parsed: &mut ShellCommands) {
+    if node.kind() == "command" {
+        collect_command(node, src, parsed);
+    }
+    // Nested commands can appear almost anywhere: in arguments, assignments,
+    // heredoc bodies, redirect targets and compound statements.
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_node(child, src, parsed);
+    }
+}
+
+fn collect_command(node: Node<'_>, src: &[u8], parsed: &mut ShellCommands) {
+    let Some(name) = node.child_by_field_name("name") else {
+        // A bare assignment such as `FOO=1`.
+        return;
+    };
+    let Some(program) = literal_text(name, src) else {
+        parsed.unanalyzable.push(Unanalyzable {
+            text: node_text(node, src).to_string(),
+            reason: UnanalyzableReason::DynamicCommand,
+        });
+        return;
+    };
+
+    // Words only known at run time are kept as written so forbidding rules
+    // can still see them, but must never let an allow rule match.
+    let mut dynamic_args = Vec::new();
+    let mut argv = vec![program];
+    let mut cursor = node.walk();
+    for argument in node.children_by_field_name("argument", &mut cursor) {
+        if literal_text(argument, src).is_none() {
+            dynamic_args.push(argv.len());
+        }
+        argv.push(word_text(argument, src));
+    }
+    let mut dynamic_env = false;
+    let mut env = Vec::new();
+    let mut cursor = node.walk();
+    for assignment in node
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "variable_assignment")
+    {
+        let Some(name) = assignment.child_by_field_name("name") else {
+            continue;
+        };
+        let value = assignment.child_by_field_name("value");
+        dynamic_env |= value.is_some_and(|value| literal_text(value, src).is_none());
+        env.push((
+            node_text(name, src).to_string(),
+            value.map(|value| word_text(value, src)).unwrap_or_default(),
+        ));
+    }
+
+    let reason = match argv[0].as_str() {
+        "eval" => Some(UnanalyzableReason::Eval),
+        "source" | "." => Some(UnanalyzableReason::Source),
+        _ => match shell_wrapper(&argv) {
+            ShellWrapper::Script(index) if !dynamic_args.contains(&index) => {
+                let nested_from = parsed.commands.len();
+                collect_script(&argv[index], parsed);
+                // The wrapper's assignments are inherited by everything it runs.
+                for nested in &mut parsed.commands[nested_from..] {
+                    nested.env.splice(0..0, env.iter().cloned());
+                }
+                if dynamic_env {
+                    parsed.unanalyzable.push(Unanalyzable {
+                        text: node_text(node, src).to_string(),
+                        reason: UnanalyzableReason::DynamicArgument,
+                    });
+                }
+                return;
+            }
+            ShellWrapper::Unknown => Some(UnanalyzableReason::ShellOptions),
+            ShellWrapper::Script(_) | ShellWrapper::None => (dynamic_env
+                || !dynamic_args.is_empty())
+            .then_some(UnanalyzableReason::DynamicArgument),
+        },
+    };
+    if let Some(reason) = reason {
+        parsed.unanalyzable.push(Unanalyzable {
+            text: node_text(node, src).to_string(),
+            reason,
+        });
+    }
+    parsed.commands.push(SimpleCommand { argv, env });
+}
+
+/// How a command runs a shell script given on its command line.
+enum ShellWrapper {
+    /// Not a shell, or a shell reading its script from a file or stdin.
+    None,
+    /// The index of the script passed to `sh -c`, `bash -e -lc` and similar
+    /// invocations. Arguments after it only set `$0`, `$1`, ...
+    Script(usize),
+    /// A shell whose options could not be parsed.
+    Unknown,
+}
+
+fn shell_wrapper(command: &[String]) -> ShellWrapper {
+    let Some((program, args)) = command.split_first() else {
+        return ShellWrapper::None;
+    };
+    let program = program.rsplit('/').next().unwrap_or(program);
+    if !WRAPPER_SHELLS.contains(&program) {
+        return ShellWrapper::None;
+    }
+
+    let mut has_script = false;
+    // Indices are into `command`, past the program name.
+    let mut args = args.iter().zip(1..).peekable();
+    while let Some((arg, _)) = args.next_if(|(arg, _)| arg.starts_with(['-', '+'])) {
+        if arg == "-" || arg == "--" {
+            break;
+        }
+        if arg.starts_with("--") {
+            if SHELL_LONG_OPTIONS.contains(&arg.as_str()) {
+                if args.next().is_none() {
+                    return ShellWrapper::Unknown;
+                }
+            } else if !SHELL_LONG_FLAGS.contains(&arg.as_str()) {
+                return ShellWrapper::Unknown;
+            }
+            continue;
+        }
+        for letter in arg[1..].chars() {
+            match letter {
+                'c' => has_script = true,
+                // `-o pipefail`, `-O extglob`: the value is the next argument.
+                'o' | 'O' => {
+                    if args.next().is_none() {
+                        return ShellWrapper::Unknown;
+                    }
+                }
+                letter if letter.is_ascii_alphabetic() => {}
+                _ => return ShellWrapper::Unknown,
+            }
+        }
+    }
+
+    match (has_script, args.next()) {
+        (true, Some((_, index))) => ShellWrapper::Script(index),
+        (true, None) => ShellWrapper::Unknown,
+        (false, _) => ShellWrapper::None,
+    }
+}
+
+/// The value of a word after quote removal, or its source text if it
+/// contains an expansion.
+fn word_text(node: Node<'_>, src: &[u8]) -> String {
+    literal_text(node, src).unwrap_or_else(|| node_text(node, src).to_string())
+}
+
+/// The value of a word after quote removal, or `None` if it contains an
+/// expansion and so is only known at run time.
+fn literal_text(node: Node<'_>, src: &[u8]) -> Option<String> {
+    let text = node_text(node, src);
+    match node.kind() {
+        "command_name" | "concatenation" => {
+            let mut cursor = node.walk();
+            // The brackets of `file[0-9]` can end up in separate words.
+            let unquoted: String = node
+                .named_children(&mut cursor)
+                .map(|child| match child.kind() {
+                    "word" => node_text(child, src),
+                    _ => "_",
+                })
+                .collect();
+            if is_pattern(&unquoted) {
+                return None;
+            }
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor)
+                .map(|child| literal_text(child, src))
+                .collect()
+        }
+        "word" if is_pattern(text) => None,
+        "word" | "number" => Some(unescape(text, |_| true)),
+        "raw_string" => Some(
+            text.trim_start_matches('\'')
+                .trim_end_matches('\'')
+                .to_string(),
+        ),
+        "string" => {
+            let mut cursor = node.walk();
+            node.named_children(&mut cursor)
+                .map(|child| {
+                    (child.kind() == "string_content").then(|| {
+                        unescape(node_text(child, src), |escaped| {
+                            matches!(escaped, '"' | '\\' | '$' | '`' | '\n')
+                        })
+                    })
+                })
+                .collect()
+        }
+        _ => None,
+    }
+}
+
+/// Whether an unquoted word is subject to tilde, brace or pathname
+/// expansion, e.g. `~/bin`, `{src,target}`, `*.rs` or `file[0-9]`.
+fn is_pattern(word: &str) -> bool {
+    if word.starts_with('~') {
+        return true;
+    }
+    let mut chars = word.chars().peekable();
+    let mut bracket = false;
+    // `{}` on its own, as in `find -exec`, is not expanded.
+    let mut brace = false;
+    let mut brace_list = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' => return true,
+            '[' => bracket = true,
+            ']' if bracket => return true,
+            '{' => brace = true,
+            ',' => brace_list |= brace,
+            '.' if chars.peek() == Some(&'.') => brace_list |= brace,
+            '}' if brace_list => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Removes the backslash from escape sequences whose escaped character
+/// satisfies `is_escapable`; other backslashes are kept, as in bash.
+fn unescape(text: &str, is_escapable: impl Fn(char) -> bool) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\n') => {}
+            Some(escaped) if is_escapable(escaped) => unescaped.push(escaped),
+            Some(escaped) => {
+                unescaped.push(c);
+                unescaped.push(escaped);
+            }
+            None => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn node_text<'a>(node: Node<'_>, src: &'a [u8]) -> &'a str {
+    std::str::from_utf8(&src[node.byte_range()]).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn commands(raw: &[&[&str]]) -> Vec<SimpleCommand> {
+        raw.iter()
+            .map(|command| SimpleCommand {
+                argv: command.iter().map(ToString::to_string).collect(),
+                env: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn splits_lists_pipes_and_substitutions() {
+        let parsed = parse_shell_script(
+            "cargo build && cargo test | tee out.txt; ls || (cd src && rm -f \"$(cat list)\")",
+        );
+        assert_eq!(
+            parsed.commands,
+            commands(&[
+                &["cargo", "build"],
+                &["cargo", "test"],
+                &["tee", "out.txt"],
+                &["ls"],
+                &["cd", "src"],
+                &["rm", "-f", "\"$(cat list)\""],
+                &["cat", "list"],
+            ])
+        );
+        assert_eq!(
+            parsed.unanalyzable,
+            vec![Unanalyzable {
+                text: "rm -f \"$(cat list)\"".to_string(),
+                reason: UnanalyzableReason::DynamicArgument,
+            }]
+        );
+    }
+
+    #[test]
+    fn flags_arguments_only_known_at_run_time() {
+        for script in [
+            "rm -rf target/$X",
+            "rm -rf target/*",
+            "ls ~/src",
+            "cp file[0-9] target",
+            "rm {src,target}",
+            "DIR=$HOME ls",
+            "bash -c \"$CMD\"",
+        ] {
+            let parsed = parse_shell_script(script);
+            assert_eq!(
+                parsed.unanalyzable,
+                vec![Unanalyzable {
+                    text: script.to_string(),
+                    reason: UnanalyzableReason::DynamicArgument,
+                }],
+                "{script}"
+            );
+        }
+
+        for script in [
+            r"rm target/\*",
+            "rm 'target/*' \"~/src\"",
+            "echo ] a=[b",
+            r"find . -exec rm {} \;",
+        ] {
+            let parsed = parse_shell_script(script);
+            assert_eq!(parsed.unanalyzable, Vec::new(), "{script}");
+        }
+        assert_eq!(
+            parse_shell_script(r"rm target/\* 'a*'").commands,
+            commands(&[&["rm", "target/*", "a*"]])
+        );
+    }
+
+    #[test]
+    fn removes_quotes_and_unwraps_shell_wrappers() {
+        let parsed = parse_shell_script(
+            r#"bash -lc 'git status && rm -rf target' ; /bin/sh -c "echo \"hi\" 'a b'" x\ y"#,
+        );
+        assert_eq!(
+            parsed.commands,
+            commands(&[
+                &["git", "status"],
+                &["rm", "-rf", "target"],
+                &["echo", "hi", "a b"],
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_shell_options_around_the_script() {
+        for script in [
+            "sh -c 'rm -rf /' sh",
+            "bash -e -c 'rm -rf /'",
+            "bash --norc -c 'rm -rf /'",
+            "bash -o pipefail --rcfile rc -xc 'rm -rf /' bash arg",
+            "zsh +x -c -- 'rm -rf /'",
+        ] {
+            let parsed = parse_shell_script(script);
+            assert_eq!(
+                parsed.commands,
+                commands(&[&["rm", "-rf", "/"]]),
+                "{script}"
+            );
+            assert_eq!(parsed.unanalyzable, Vec::new(), "{script}");
+        }
+
+        let parsed = parse_shell_script("bash script.sh -c 'rm -rf /'");
+        assert_eq!(
+            parsed.commands,
+            commands(&[&["bash", "script.sh", "-c", "rm -rf /"]])
+        );
+        assert_eq!(parsed.unanalyzable, Vec::new());
+    }
+
+    #[test]
+    fn flags_shell_options_that_cannot_be_parsed() {
+        for script in [
+            "bash --frobnicate -c 'rm -rf /'",
+            "sh -c",
+            "bash -e -o",
+            "bash -1 -c 'rm -rf /'",
+        ] {
+            let parsed = parse_shell_script(script);
+            assert_eq!(
+                parsed.unanalyzable,
+                vec![Unanalyzable {
+                    text: script.to_string(),
+                    reason: UnanalyzableReason::ShellOptions,
+                }],
+                "{script}"
+            );
+        }
+    }
+
+    #[test]
+    fn records_assignments_before_commands() {
+        let parsed =
+            parse_shell_script("AWS_PROFILE=prod bash -c 'TF_LOG=\"$LEVEL\" terraform apply'; X=1");
+        assert_eq!(
+            parsed.commands,
+            vec![SimpleCommand {
+                argv: vec!["terraform".to_string(), "apply".to_string()],
+                env: vec![
+                    ("AWS_PROFILE".to_string(), "prod".to_string()),
+                    ("TF_LOG".to_string(), "\"$LEVEL\"".to_string()),
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn flags_constructs_that_cannot_be_analysed() {
+        let parsed = parse_shell_script("eval \"$cmd\"; $EDITOR notes.txt; . ./env.sh");
+        assert_eq!(
+            parsed.unanalyzable,
+            vec![
+                Unanalyzable {
+                    text: "eval \"$cmd\"".to_string(),
+                    reason: UnanalyzableReason::Eval,
+                },
+                Unanalyzable {
+                    text: "$EDITOR notes.txt".to_string(),
+                    reason: UnanalyzableReason::DynamicCommand,
+                },
+                Unanalyzable {
+                    text: ". ./env.sh".to_string(),
+                    reason: UnanalyzableReason::Source,
+                },
+            ]
+        );
+        assert_eq!(
+            parsed.commands,
+            commands(&[&["eval", "\"$cmd\""], &[".", "./env.sh"]])
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let parsed = parse_shell_script("echo 'unterminated");
+        assert_eq!(
+            parsed.unanalyzable,
+            vec![Unanalyzable {
+                text: "echo 'unterminated".to_string(),
+                reason: UnanalyzableReason::ParseError,
+            }]
+        );
+    }
+}
@@ -14,18 +14,19 @@ pub fn create_symlink(
     Ok(())
 }
 
+/// Windows has separate file and directory links, so the kind is taken from
+/// what `source`, the link target resolved against the link's directory,
+/// currently points to. A dangling target becomes a file link.
 #[cfg(windows)]
 pub fn create_symlink(
     source: &Path,
     link_target: &Path,
     destination: &Path,
 ) -> Result<(), GitToolingError> {
-    use std::os::windows::fs::FileTypeExt;
     use std::os::windows::fs::symlink_dir;
     use std::os::windows::fs::symlink_file;
 
-    let metadata = std::fs::symlink_metadata(source)?;
-    if metadata.file_type().is_symlink_dir() {
+    if std::fs::metadata(source).is_ok_and(|metadata| metadata.is_dir()) {
         symlink_dir(link_target, destination)?;
     } else {
         symlink_file(link_target, destination)?;
@@ -34,12 +34,13 @@ use crate::parse_command::ParsedCommand;
 use crate::plan_tool::UpdatePlanArgs;
 use crate::request_user_input::RequestUserInputResponse;
 use crate::user_input::UserInput;
-use aeye_utils_absolute_path::AbsolutePathBuf;
+use aeye_execpolicy::ApprovalOutcome;
 use aeye_mcp_types::CallToolResult;
 use aeye_mcp_types::RequestId;
 use aeye_mcp_types::Resource as McpResource;
 use aeye_mcp_types::ResourceTemplate as McpResourceTemplate;
 use aeye_mcp_types::Tool as McpTool;
+use aeye_utils_absolute_path::AbsolutePathBuf;
 use schemars::JsonSchema;
 use serde::Deserialize;
 use serde::Serialize;
@@ -2300,6 +2301,23 @@ impl ReviewDecision {
     }
 }
 
+/// Lets execpolicy audit logs record how the user answered an approval prompt.
+impl From<&ReviewDecision> for ApprovalOutcome {
+    fn from(decision: &ReviewDecision) -> Self {
+        match decision {
+            ReviewDecision::Approved => ApprovalOutcome::Approved,
+            ReviewDecision::ApprovedExecpolicyAmendment {
+                proposed_execpolicy_amendment,
+            } => ApprovalOutcome::ApprovedExecpolicyAmendment {
+                prefix: proposed_execpolicy_amendment.command().to_vec(),
+            },
+            ReviewDecision::ApprovedForSession => ApprovalOutcome::ApprovedForSession,
+            ReviewDecision::Denied => ApprovalOutcome::Denied,
+            ReviewDecision::Abort => ApprovalOutcome::Abort,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, JsonSchema, TS)]
 #[serde(tag = "type", rename_all = "snake_case")]
 #[ts(tag = "type")]
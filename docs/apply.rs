@@ -1,4 +1,5 @@
 use crate::aeye::config;
+use crate::aeye::decision::PolicyDecision;
 use crate::aeye::policy::PolicyEngine;
 use anyhow::{Context, Result};
 use clap::Parser;
@@ -36,12 +37,18 @@ pub async fn run(cmd: ApplyCommand, policy_engine: &PolicyEngine) -> Result<()>
 
     // 3. Check write permissions for all files in the patch
     let files_in_patch = parse_files_from_diff(&patch_content)?;
+    let mut needs_approval = Vec::new();
     for file in &files_in_patch {
-        if !policy_engine.check_write(file) {
-            anyhow::bail!(
-                "Policy violation: Attempting to write to '{}', which is not in the `write_allowlist` of your a-eye.yaml.",
-                file.display()
-            );
+        match policy_engine.check_write(file) {
+            PolicyDecision::Allow(_) => {}
+            PolicyDecision::NeedsApproval(reason) => needs_approval.push((file, reason)),
+            PolicyDecision::Deny(reason) => {
+                anyhow::bail!(
+                    "Policy violation: refusing to write to '{}': {}",
+                    file.display(),
+                    reason.justification
+                );
+            }
         }
     }
 
@@ -80,6 +87,12 @@ pub async fn run(cmd: ApplyCommand, policy_engine: &PolicyEngine) -> Result<()>
         return Ok(());
     }
 
+    if !needs_approval.is_empty() {
+        println!("{}", "The following files need your approval:".yellow());
+        for (file, reason) in &needs_approval {
+            println!("  - {}: {}", file.display(), reason.justification);
+        }
+    }
     if !prompt_for_approval("Apply this patch?")? {
         println!("Apply operation cancelled by user.");
         return Ok(());
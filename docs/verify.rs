@@ -1,4 +1,5 @@
 use crate::aeye::config;
+use crate::aeye::decision::PolicyDecision;
 use crate::aeye::policy::PolicyEngine;
 use crate::aeye::scanner::SystemProfile;
 use anyhow::{Context, Result};
@@ -51,12 +52,27 @@ pub async fn run(_cmd: VerifyCommand, policy_engine: &PolicyEngine) -> Result<()
         }
 
         for command_str in &system_profile.verify_commands {
-            // Check against shell denylist
-            if !policy_engine.check_shell(command_str) {
-                anyhow::bail!(
-                    "Policy violation: Execution of command `{}` is forbidden by a `shell_deny_patterns` rule in your a-eye.yaml.",
-                    command_str
-                );
+            // Check against the shell policy; commands that need approval
+            // get their own prompt on top of the one above.
+            match policy_engine.check_shell(command_str) {
+                PolicyDecision::Allow(_) => {}
+                PolicyDecision::NeedsApproval(reason) => {
+                    let prompt = format!(
+                        "`{}` needs approval ({}). Run it?",
+                        command_str, reason.justification
+                    );
+                    if !prompt_for_approval(&prompt)? {
+                        println!("Skipped `{}`.", command_str);
+                        continue;
+                    }
+                }
+                PolicyDecision::Deny(reason) => {
+                    anyhow::bail!(
+                        "Policy violation: execution of command `{}` is denied: {}",
+                        command_str,
+                        reason.justification
+                    );
+                }
             }
 
             println!("\n> {}", command_str.bold());